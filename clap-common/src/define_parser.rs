@@ -0,0 +1,83 @@
+use crate::Define;
+use clap::builder::TypedValueParser;
+use clap::error::ErrorKind;
+use clap::Error as ClapError;
+use clap::{Arg, Command};
+use std::ffi::OsStr;
+
+#[derive(Debug, Copy, Clone)]
+pub struct DefineParser {}
+
+impl TypedValueParser for DefineParser {
+    type Value = Define;
+
+    fn parse_ref(
+        &self,
+        _cmd: &Command,
+        _arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, ClapError> {
+        parse_define(&value.to_string_lossy())
+            .map_err(|msg| ClapError::raw(ErrorKind::InvalidValue, msg))
+    }
+}
+
+/// Parse `NAME=value` or `NAME`, values can be decimal, hex (`0x1F`) or binary (`0b1010`)
+pub fn parse_define(text: &str) -> Result<Define, String> {
+    let (name, value) = text.split_once('=').unwrap_or((text, "1"));
+    let name = name.trim();
+    let mut chars = name.chars();
+    let valid_start = chars
+        .next()
+        .map(|chr| chr.is_ascii_alphabetic() || chr == '_')
+        .unwrap_or(false);
+    if !valid_start || !chars.all(|chr| chr.is_ascii_alphanumeric() || chr == '_') {
+        return Err(format!("Define name '{name}' is invalid"));
+    }
+    let value = value.trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let parsed = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else {
+        digits.parse::<i64>()
+    };
+    let value = parsed.map_err(|_| format!("Define value '{value}' is invalid"))?;
+    Ok(Define {
+        name: name.to_string(),
+        value: if negative { -value } else { value },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::define_parser::parse_define;
+    use crate::Define;
+
+    fn define(name: &str, value: i64) -> Result<Define, String> {
+        Ok(Define {
+            name: name.to_string(),
+            value,
+        })
+    }
+
+    #[test]
+    fn check_parse_define() {
+        assert_eq!(parse_define("DEBUG"), define("DEBUG", 1));
+        assert_eq!(parse_define("SPEED=4"), define("SPEED", 4));
+        assert_eq!(parse_define("MASK = 0x1F"), define("MASK", 31));
+        assert_eq!(parse_define("LOW=-0b11"), define("LOW", -3));
+        assert_eq!(
+            parse_define("1A=2"),
+            Err(String::from("Define name '1A' is invalid"))
+        );
+        assert_eq!(
+            parse_define("A=two"),
+            Err(String::from("Define value 'two' is invalid"))
+        );
+    }
+}
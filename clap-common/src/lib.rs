@@ -1,5 +1,6 @@
 pub mod arg_matcher;
 pub mod check_level_parser;
+pub mod define_parser;
 pub mod level_filter_parser;
 pub mod macros;
 
@@ -8,4 +9,11 @@ pub enum CheckLevel {
     Off,
     Warn,
     Error
-}
\ No newline at end of file
+}
+
+/// Constant set on the command line, `NAME=value` or `NAME` (for 1)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Define {
+    pub name: String,
+    pub value: i64,
+}
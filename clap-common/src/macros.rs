@@ -33,4 +33,13 @@ macro_rules! arg_check_level {
                 .value_parser($crate::check_level_parser::CheckLevelParser {})
                 .default_value($default)
     };
-}
\ No newline at end of file
+}
+
+#[macro_export]
+macro_rules! arg_define {
+    () => {
+         clap::arg!(-D --define <DEFINE> "Define a constant (NAME=value or NAME for 1), can be used multiple times")
+                .value_parser($crate::define_parser::DefineParser {})
+                .action(clap::ArgAction::Append)
+    };
+}
@@ -1,25 +1,36 @@
+use clap::ValueHint::FilePath;
+use clap::{arg, command, value_parser, ArgMatches};
+use clap_common::arg_matcher::{create_output_default, ArgMatchesFiles};
+use clap_common::{
+    arg_check_level, arg_define, arg_input_file, arg_log_level, arg_output_file, CheckLevel, Define,
+};
+use color_eyre::eyre::eyre;
 use color_eyre::Result;
+use ec8_common::symbols::Symbols;
 use env_logger::Builder;
 use log::LevelFilter;
+use std::fs;
 use std::path::PathBuf;
-use clap::ValueHint::FilePath;
-use clap::{arg, command, value_parser, ArgMatches};
-use clap_common::{arg_check_level, arg_input_file, arg_log_level, arg_output_file};
-use clap_common::arg_matcher::{ArgMatchesFiles, create_output_default};
-use color_eyre::eyre::eyre;
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub input_file: PathBuf,
     pub output_file: PathBuf,
     pub desc_file: Option<PathBuf>,
-    pub suppress_ec8_warning: bool,
+    pub listing_file: Option<PathBuf>,
+    pub debug_file: Option<PathBuf>,
+    pub symbols: Symbols,
+    pub ec8_level: CheckLevel,
+    pub lint_level: CheckLevel,
+    pub octo: bool,
+    pub defines: Vec<Define>,
+    pub decimal: bool,
 }
 
 pub fn arg_matches() -> ArgMatches {
     command!()
         .arg(
-            arg_input_file!("EC8 ASM file (*.eca)"),
+            arg_input_file!("EC8 ASM file (*.eca) or Octo file (*.8o)"),
         )
         .arg(
             arg_output_file!(),
@@ -29,8 +40,28 @@ pub fn arg_matches() -> ArgMatches {
                 .value_parser(value_parser!(PathBuf))
                 .value_hint(FilePath),
         )
+        .arg(
+            arg!(-L --listing [FILE] "Generate listing file")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(FilePath),
+        )
+        .arg(
+            arg!(-g --debug [FILE] "Generate debug info file (JSON)")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(FilePath),
+        )
+        .arg(
+            arg!(-s --symbols <FILE> "Symbols file (from ec8-ll-compiler), names addresses in the describe file")
+                .requires("desc")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(FilePath),
+        )
+        .arg(arg!(--octo "Read input as Octo (default for *.8o files)"))
+        .arg(arg!(--decimal "Read numbers without a prefix as decimal instead of hex"))
+        .arg(arg_define!())
         .arg(arg_log_level!())
         .arg(arg_check_level!(ec8, e, "EC8 check level", "warn"))
+        .arg(arg_check_level!(warnings, w, "Lint/warnings check level", "warn"))
         .get_matches()
 }
 
@@ -50,23 +81,83 @@ pub fn setup_logging(matches: &ArgMatches) {
 }
 
 pub fn read_options(matches: &ArgMatches) -> Result<Options> {
-    let input_file = matches.get_file("INPUT_FILE", "Input file").map_err(|txt| eyre!(txt))?;
+    let input_file = matches
+        .get_file("INPUT_FILE", "Input file")
+        .map_err(|txt| eyre!(txt))?;
     let default_output = create_output_default(&input_file, ".c8", "Output file");
-    let output = matches.get_output_file("output", "Output file", default_output).map_err(|txt| eyre!(txt))?;
+    let output = matches
+        .get_output_file("output", "Output file", default_output)
+        .map_err(|txt| eyre!(txt))?;
 
     let mut desc_file = None;
     if matches.contains_id("desc") {
         let default_output = create_output_default(&input_file, ".desc", "Describe file");
-        let file = matches.get_output_file("desc", "Describe file", default_output).map_err(|txt| eyre!(txt))?;
+        let file = matches
+            .get_output_file("desc", "Describe file", default_output)
+            .map_err(|txt| eyre!(txt))?;
         desc_file = Some(file);
     }
 
-    let suppress_ec8_warning = matches.contains_id("ec8");
+    let mut listing_file = None;
+    if matches.contains_id("listing") {
+        let default_output = create_output_default(&input_file, "lst", "Listing file");
+        let file = matches
+            .get_output_file("listing", "Listing file", default_output)
+            .map_err(|txt| eyre!(txt))?;
+        listing_file = Some(file);
+    }
+
+    let mut debug_file = None;
+    if matches.contains_id("debug") {
+        let default_output = create_output_default(&input_file, "debug.json", "Debug info file");
+        let file = matches
+            .get_output_file("debug", "Debug info file", default_output)
+            .map_err(|txt| eyre!(txt))?;
+        debug_file = Some(file);
+    }
+
+    let symbols = if matches.contains_id("symbols") {
+        let file = matches
+            .get_file("symbols", "Symbols file")
+            .map_err(|txt| eyre!(txt))?;
+        fs::read_to_string(file)?
+            .parse::<Symbols>()
+            .map_err(|err| eyre!("Invalid symbols file: {err}"))?
+    } else {
+        Symbols::default()
+    };
+
+    let ec8_level = *matches
+        .get_one::<CheckLevel>("ec8")
+        .expect("Invalid EC8 arg");
+    let lint_level = *matches
+        .get_one::<CheckLevel>("warnings")
+        .expect("Invalid Lint arg");
+
+    let octo = matches.get_flag("octo")
+        || input_file
+            .extension()
+            .map(|ext| ext == "8o")
+            .unwrap_or(false);
+
+    let decimal = matches.get_flag("decimal");
+
+    let defines = matches
+        .get_many::<Define>("define")
+        .map(|defines| defines.cloned().collect())
+        .unwrap_or_default();
 
     Ok(Options {
         input_file,
         output_file: output,
         desc_file,
-        suppress_ec8_warning,
+        listing_file,
+        debug_file,
+        symbols,
+        ec8_level,
+        lint_level,
+        octo,
+        defines,
+        decimal,
     })
 }
@@ -0,0 +1,239 @@
+//! Checks
+//!
+//! Problems found after assembling, each is reported as a warning, an error or not at all
+//! depending on the [CheckLevel] for its kind
+//!
+//! EC8 level
+//! - Instructions only supported by EC8
+//!
+//! Lint level
+//! - System calls
+//! - Jumps and calls to odd addresses, data, the middle of an instruction or outside the program
+//! - Skips followed by data
+//! - Subroutines that never return
+//! - Code that falls through into data
+//! - Numbers without a prefix that have a different value in hex and decimal (found while
+//!   assembling)
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::program::Line::*;
+use crate::program::{Line, Program};
+use clap_common::CheckLevel;
+use ec8_common::instruction::Instruction;
+use std::collections::HashSet;
+
+impl Program {
+    /// Warnings found while assembling, followed by the problems found by each check
+    pub fn warnings(&self, ec8_level: CheckLevel, lint_level: CheckLevel) -> Vec<Diagnostic> {
+        let mut diagnostics = self.diagnostics.clone();
+        for lint in &self.lints {
+            match lint_level {
+                CheckLevel::Off => {}
+                CheckLevel::Warn => diagnostics.push(lint.clone()),
+                CheckLevel::Error => diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    ..lint.clone()
+                }),
+            }
+        }
+        let placed: Vec<(usize, &Line)> = self
+            .placed()
+            .into_iter()
+            .filter(|(_, line)| matches!(line, Code { .. } | Data { .. }))
+            .collect();
+        for (i, (addr, line)) in placed.iter().enumerate() {
+            let Code {
+                idx, instruction, ..
+            } = line
+            else {
+                continue;
+            };
+            let mut report = |level: CheckLevel, message: String| {
+                let diagnostic = match level {
+                    CheckLevel::Off => return,
+                    CheckLevel::Warn => Diagnostic::warning(&message),
+                    CheckLevel::Error => Diagnostic::error(&message),
+                };
+                diagnostics.push(diagnostic.at(self.source[*idx].span.clone()));
+            };
+            match instruction.opcode() {
+                _ if matches!(instruction, Instruction::SysCall { .. }) => {
+                    report(lint_level, String::from("Contains system call (0x0nnn)"))
+                }
+                Some(opcode) if opcode.is_ec8_only() => {
+                    report(ec8_level, format!("Contains EC8 only call {opcode:?}"))
+                }
+                _ => {}
+            }
+            if let Instruction::Jump { nnn } | Instruction::Call { nnn } = instruction {
+                if let Some(problem) = target_problem(&placed, *nnn as usize) {
+                    let kind = if matches!(instruction, Instruction::Jump { .. }) {
+                        "Jump"
+                    } else {
+                        "Call"
+                    };
+                    report(lint_level, format!("{kind} target 0x{nnn:03X} {problem}"));
+                } else if matches!(instruction, Instruction::Call { .. })
+                    && !returns(&placed, *nnn as usize)
+                {
+                    let message = format!("Subroutine at 0x{nnn:03X} never returns");
+                    report(lint_level, message);
+                }
+            }
+            let next = placed
+                .get(i + 1)
+                .filter(|(next_addr, _)| *next_addr == line.next_addr(*addr));
+            if let Some((_, Data { idx: data, .. })) = next {
+                let location = &self.source[*data].location;
+                if is_skip(instruction) {
+                    let message = format!("Skip is followed by data (line {location})");
+                    report(lint_level, message);
+                } else if !is_terminator(instruction) {
+                    let message = format!("Falls through into data (line {location})");
+                    report(lint_level, message);
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Line containing `addr`, and its address
+fn line_at<'a>(placed: &[(usize, &'a Line)], addr: usize) -> Option<(usize, &'a Line)> {
+    placed
+        .iter()
+        .find(|(start, line)| (*start..line.next_addr(*start)).contains(&addr))
+        .copied()
+}
+
+/// Why `addr` isn't a valid jump or call target, if it isn't
+fn target_problem(placed: &[(usize, &Line)], addr: usize) -> Option<&'static str> {
+    match line_at(placed, addr) {
+        None => Some("is outside the program"),
+        Some((_, Data { .. })) => Some("is inside data"),
+        Some((start, _)) if start != addr => Some("is in the middle of an instruction"),
+        Some(_) if addr % 2 == 1 => Some("is an odd address"),
+        Some(_) => None,
+    }
+}
+
+/// If a `ret` can be reached from `addr`, following jumps, skips and calls
+///
+/// Indirect jumps (`jpo`) are assumed to return
+fn returns(placed: &[(usize, &Line)], addr: usize) -> bool {
+    let mut visited = HashSet::new();
+    let mut pending = vec![addr];
+    while let Some(addr) = pending.pop() {
+        if !visited.insert(addr) {
+            continue;
+        }
+        let Some((start, Code { instruction, .. })) = line_at(placed, addr) else {
+            continue;
+        };
+        if start != addr {
+            continue;
+        }
+        match instruction {
+            Instruction::Return | Instruction::JumpOffset { .. } => return true,
+            Instruction::Jump { nnn } => pending.push(*nnn as usize),
+            _ if is_skip(instruction) => pending.extend([addr + 2, addr + 4]),
+            _ => pending.push(addr + 2),
+        }
+    }
+    false
+}
+
+fn is_skip(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::SkipIfEqualNum { .. }
+            | Instruction::SkipIfNotEqualNum { .. }
+            | Instruction::SkipIfEqualReg { .. }
+            | Instruction::SkipIfNotEqualReg { .. }
+            | Instruction::SkipIfKeyPressed { .. }
+            | Instruction::SkipIfKeyNotPressed { .. }
+    )
+}
+
+/// Instructions that never continue to the next instruction
+fn is_terminator(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Jump { .. } | Instruction::JumpOffset { .. } | Instruction::Return
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Severity;
+    use crate::parser::{parse, Settings};
+    use clap_common::CheckLevel;
+    use std::path::Path;
+
+    /// `line: message` for each warning
+    fn warnings(source: Vec<&str>, lint_level: CheckLevel) -> Vec<String> {
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        program
+            .warnings(CheckLevel::Warn, lint_level)
+            .into_iter()
+            .map(|warning| {
+                let line = warning.span.map(|span| span.line).unwrap_or_default();
+                format!("{line}: {}", warning.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_targets() {
+        let source = vec![
+            "start: jmp data",
+            "jmp 0x300",
+            "cal start + 1",
+            "ske v0, 1",
+            "data: db 1",
+            "odd: ret",
+            "jmp odd",
+        ];
+        assert_eq!(
+            warnings(source.clone(), CheckLevel::Warn),
+            vec![
+                "1: Jump target 0x208 is inside data",
+                "2: Jump target 0x300 is outside the program",
+                "3: Call target 0x201 is in the middle of an instruction",
+                "4: Skip is followed by data (line 5)",
+                "7: Jump target 0x209 is an odd address",
+            ]
+        );
+        assert!(warnings(source, CheckLevel::Off).is_empty());
+    }
+
+    #[test]
+    fn check_flow() {
+        let source = vec![
+            "cal wait",
+            "cal forever",
+            "asc v0",
+            "wait: rdt v0",
+            "ske v0, 0",
+            "jmp wait",
+            "ret",
+            "forever: jmp forever",
+            "add v0, 1",
+            "db 1",
+        ];
+        assert_eq!(
+            warnings(source.clone(), CheckLevel::Warn),
+            vec![
+                "2: Subroutine at 0x20E never returns",
+                "3: Contains EC8 only call SetMemRegToAsciiSprite",
+                "9: Falls through into data (line 10)",
+            ]
+        );
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        let diagnostics = program.warnings(CheckLevel::Off, CheckLevel::Error);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Severity::Error));
+    }
+}
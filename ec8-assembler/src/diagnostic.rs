@@ -0,0 +1,208 @@
+//! Diagnostics
+//!
+//! Errors and warnings with the source they refer to, rendered as
+//! ```text
+//! error: Undefined label 'lop'
+//!  --> game.eca:4:5
+//!   |
+//! 4 | jmp lop
+//!   |     ^^^
+//!   = help: a label with a similar name exists: 'loop'
+//! ```
+
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Part of a source line
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Span {
+    pub file: String,
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Columns to underline, starting at 0
+    pub columns: Range<usize>,
+    /// Text of the line
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    /// Where the line was included or expanded from
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: &str) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    fn new(severity: Severity, message: &str) -> Self {
+        Self {
+            severity,
+            message: message.to_string(),
+            span: None,
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    pub fn into_warning(self) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..self
+        }
+    }
+
+    pub fn at(self, span: Span) -> Self {
+        Self {
+            span: Some(span),
+            ..self
+        }
+    }
+
+    pub fn with_help(self, help: &str) -> Self {
+        Self {
+            help: Some(help.to_string()),
+            ..self
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(f, "{severity}: {}", self.message)?;
+        let gutter = self
+            .span
+            .as_ref()
+            .map(|span| span.line.to_string().len())
+            .unwrap_or(1);
+        let pad = " ".repeat(gutter);
+        if let Some(span) = &self.span {
+            let text = span.text.trim_end();
+            let start = span.columns.start.min(text.len());
+            let len = span.columns.len().max(1);
+            writeln!(f, "{pad}--> {}:{}:{}", span.file, span.line, start + 1)?;
+            writeln!(f, "{pad} |")?;
+            writeln!(f, "{} | {text}", span.line)?;
+            writeln!(f, "{pad} | {}{}", " ".repeat(start), "^".repeat(len))?;
+        }
+        for note in &self.notes {
+            writeln!(f, "{pad} = note: {note}")?;
+        }
+        if let Some(help) = &self.help {
+            writeln!(f, "{pad} = help: {help}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every error (and any warnings) found when assembling
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in &self.0 {
+            writeln!(f, "{diagnostic}")?;
+        }
+        let errors = self
+            .0
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count();
+        match errors {
+            1 => write!(f, "Unable to assemble due to 1 error"),
+            count => write!(f, "Unable to assemble due to {count} errors"),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// The name in `names` closest to `name`, if any are close enough to be a typo
+pub fn similar<'a>(name: &str, names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max = (name.len() / 3).max(1);
+    names
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(dist, _)| *dist <= max)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance between `lhs` and `rhs`, ignoring case
+fn distance(lhs: &str, rhs: &str) -> usize {
+    let lhs: Vec<char> = lhs.to_lowercase().chars().collect();
+    let rhs: Vec<char> = rhs.to_lowercase().chars().collect();
+    let mut row: Vec<usize> = (0..=rhs.len()).collect();
+    for (i, left) in lhs.iter().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, right) in rhs.iter().enumerate() {
+            let cost = if left == right { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[rhs.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{similar, Diagnostic, Span};
+
+    #[test]
+    fn check_render() {
+        let mut diagnostic = Diagnostic::error("Undefined label 'lop'")
+            .at(Span {
+                file: String::from("game.eca"),
+                line: 12,
+                columns: 4..7,
+                text: String::from("jmp lop ;back"),
+            })
+            .with_help("a label with a similar name exists: 'loop'");
+        diagnostic
+            .notes
+            .push(String::from("expanded from game.eca:3"));
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: Undefined label 'lop'
+  --> game.eca:12:5
+   |
+12 | jmp lop ;back
+   |     ^^^
+   = note: expanded from game.eca:3
+   = help: a label with a similar name exists: 'loop'
+"
+        );
+        assert_eq!(
+            Diagnostic::warning("Odd address").to_string(),
+            "warning: Odd address\n"
+        );
+    }
+
+    #[test]
+    fn check_similar() {
+        let names = ["loop", "start", "end"];
+        assert_eq!(similar("lop", names.into_iter()), Some("loop"));
+        assert_eq!(similar("START", names.into_iter()), Some("start"));
+        assert_eq!(similar("middle", names.into_iter()), None);
+    }
+}
@@ -1,11 +1,15 @@
 mod args;
+mod checks;
+mod diagnostic;
 mod parser;
 mod program;
 
-use crate::args::{read_options, setup_logging, arg_matches, Options};
-use crate::parser::parse;
+use crate::args::{arg_matches, read_options, setup_logging, Options};
+use crate::diagnostic::{Diagnostics, Severity};
+use crate::parser::{parse, parse_octo, Settings};
 use color_eyre::Result;
 use std::fs;
+use std::process::exit;
 
 fn main() -> Result<()> {
     color_eyre::install()?;
@@ -17,7 +21,16 @@ fn main() -> Result<()> {
 
     let source = fs::read_to_string(&options.input_file)?;
 
-    let bytes = process(source.lines().collect(), &options)?;
+    let bytes = match process(source.lines().collect(), &options) {
+        Err(err) => match err.downcast::<Diagnostics>() {
+            Ok(diagnostics) => {
+                eprintln!("{diagnostics}");
+                exit(1);
+            }
+            Err(err) => return Err(err),
+        },
+        Ok(bytes) => bytes,
+    };
 
     fs::write(options.output_file, bytes)?;
 
@@ -25,19 +38,48 @@ fn main() -> Result<()> {
 }
 
 fn process(source: Vec<&str>, options: &Options) -> Result<Vec<u8>> {
-    let program = parse(source)?;
+    let settings = Settings {
+        defines: options.defines.clone(),
+        decimal: options.decimal,
+    };
+    let program = if options.octo {
+        parse_octo(source, &options.input_file, &settings)?
+    } else {
+        parse(source, &options.input_file, &settings)?
+    };
 
-    if let Some(text) = program.warnings(options.suppress_ec8_warning) {
-        eprintln!("Warning:\n{text}");
+    let warnings = program.warnings(options.ec8_level, options.lint_level);
+    if warnings
+        .iter()
+        .any(|warning| warning.severity == Severity::Error)
+    {
+        return Err(Diagnostics(warnings).into());
+    }
+    for warning in warnings {
+        eprintln!("{warning}");
     }
 
     if let Some(desc_file) = &options.desc_file {
-        let result = fs::write(desc_file, program.describe());
+        let result = fs::write(desc_file, program.describe(&options.symbols));
         if let Err(err) = result {
             eprintln!("Error writing desc file: {err}");
         }
     }
 
+    if let Some(listing_file) = &options.listing_file {
+        let result = fs::write(listing_file, program.listing());
+        if let Err(err) = result {
+            eprintln!("Error writing listing file: {err}");
+        }
+    }
+
+    if let Some(debug_file) = &options.debug_file {
+        let result = fs::write(debug_file, program.debug_info().to_json());
+        if let Err(err) = result {
+            eprintln!("Error writing debug info file: {err}");
+        }
+    }
+
     Ok(program.into_bytes())
 }
 
@@ -45,24 +87,48 @@ fn process(source: Vec<&str>, options: &Options) -> Result<Vec<u8>> {
 mod test {
     use crate::args::Options;
     use crate::process;
+    use clap_common::CheckLevel;
+    use ec8_common::disassembler::disassemble;
+    use std::fs;
 
     fn make_options() -> Options {
         Options {
             input_file: Default::default(),
             output_file: Default::default(),
             desc_file: None,
-            suppress_ec8_warning: false,
+            listing_file: None,
+            debug_file: None,
+            symbols: Default::default(),
+            ec8_level: CheckLevel::Warn,
+            lint_level: CheckLevel::Warn,
+            octo: false,
+            defines: vec![],
+            decimal: false,
         }
     }
 
     #[test]
     fn check_process_basic() {
-        let input = vec![
-            "set v0, 5",
-            "dat [aaaa]",
-            "add v2, v1",
-        ];
+        let input = vec!["set v0, 5", "dat [aaaa]", "add v2, v1"];
         let output = process(input, &make_options()).unwrap();
         assert_eq!(output, vec![0x60, 0x05, 0xAA, 0xAA, 0x82, 0x14]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_disassembly_round_trip() {
+        let mut files = vec![String::from("../examples/test.c8")];
+        for entry in fs::read_dir("../roms/third-party").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map(|ext| ext == "c8").unwrap_or(false) {
+                files.push(path.to_string_lossy().to_string());
+            }
+        }
+        for file in files {
+            let rom = fs::read(&file).unwrap();
+            let source = disassemble(&rom).to_source();
+            let output = process(source.lines().collect(), &make_options())
+                .unwrap_or_else(|err| panic!("{file}: {err}"));
+            assert_eq!(output, rom, "{file}");
+        }
+    }
+}
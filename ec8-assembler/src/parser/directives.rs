@@ -0,0 +1,316 @@
+//! Directives
+//!
+//! - `org addr` Place the following lines at `addr`
+//! - `align n` Move to the next address that's a multiple of `n`, gaps are filled with zeros
+//! - `db a, b, ..` Bytes, each item is a number or a string
+//! - `dw a, b, ..` Words (big endian)
+//! - `fill n, byte` `n` copies of `byte` (defaults to 0)
+//! - `string "text"` ASCII codes of `text`, add `, asc` to use the index of each ASC glyph instead
+//! - `incbin "file"` Contents of `file`, relative to the source file
+//!
+//! Values that change the layout (`org`, `align` and the count for `fill`) can only use labels
+//! defined before them
+
+use crate::parser::labels::Labels;
+use crate::parser::{parse_num, unquoted};
+use crate::program::Line;
+use ec8_common::graphics::alpha_index;
+use ec8_common::{MAX_ADDRESS, PROG_START_ADDRESS};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Directive {
+    Org,
+    Align,
+    Db,
+    Dw,
+    Fill,
+    String,
+    Incbin,
+}
+
+impl Directive {
+    pub const ALL: [Directive; 7] = [
+        Directive::Org,
+        Directive::Align,
+        Directive::Db,
+        Directive::Dw,
+        Directive::Fill,
+        Directive::String,
+        Directive::Incbin,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Directive::Org => "org",
+            Directive::Align => "align",
+            Directive::Db => "db",
+            Directive::Dw => "dw",
+            Directive::Fill => "fill",
+            Directive::String => "string",
+            Directive::Incbin => "incbin",
+        }
+    }
+
+    pub fn find(word: &str) -> Option<Directive> {
+        Directive::ALL
+            .into_iter()
+            .find(|directive| word.eq_ignore_ascii_case(directive.name()))
+    }
+
+    /// Address after this line, `labels` may only contain the labels before this line
+    pub fn next_addr(
+        &self,
+        i: usize,
+        params: &str,
+        addr: usize,
+        labels: &Labels,
+        dir: &Path,
+    ) -> Result<usize, String> {
+        match self {
+            Directive::Org => org(i, params, labels),
+            Directive::Align => Ok(addr + align(i, params, addr, labels)?),
+            Directive::Db => {
+                let mut len = 0;
+                for item in split_list(params) {
+                    len += match item.starts_with('"') {
+                        true => parse_string(i, item)?.len(),
+                        false => 1,
+                    };
+                }
+                Ok(addr + len)
+            }
+            Directive::Dw => Ok(addr + split_list(params).len() * 2),
+            Directive::Fill => Ok(addr + fill_count(i, params, labels)?),
+            Directive::String => Ok(addr + string(i, params)?.len()),
+            Directive::Incbin => Ok(addr + incbin(i, params, dir)?.len()),
+        }
+    }
+
+    pub fn parse(
+        &self,
+        i: usize,
+        params: &str,
+        addr: usize,
+        labels: &Labels,
+        dir: &Path,
+    ) -> Result<Line, String> {
+        let bytes = match self {
+            Directive::Org => return Ok(Line::new_org(i, org(i, params, labels)? as u16)),
+            Directive::Align => {
+                let addr = addr + align(i, params, addr, labels)?;
+                return Ok(Line::new_org(i, addr as u16));
+            }
+            Directive::Db => {
+                let mut bytes = vec![];
+                for item in split_list(params) {
+                    if item.starts_with('"') {
+                        bytes.extend(parse_string(i, item)?);
+                    } else {
+                        bytes.push(parse_num(i, item, labels, "Byte", -128, 255)? as u8);
+                    }
+                }
+                bytes
+            }
+            Directive::Dw => {
+                let mut bytes = vec![];
+                for item in split_list(params) {
+                    let word = parse_num(i, item, labels, "Word", -32768, 65535)? as u16;
+                    bytes.extend_from_slice(&word.to_be_bytes());
+                }
+                bytes
+            }
+            Directive::Fill => {
+                let count = fill_count(i, params, labels)?;
+                let byte = match split_list(params).get(1) {
+                    Some(byte) => parse_num(i, byte, labels, "Byte", -128, 255)? as u8,
+                    None => 0,
+                };
+                vec![byte; count]
+            }
+            Directive::String => string(i, params)?,
+            Directive::Incbin => incbin(i, params, dir)?,
+        };
+        if bytes.is_empty() {
+            return Err(format!("Line {i}) No bytes"));
+        }
+        Ok(Line::new_data(i, bytes))
+    }
+}
+
+fn org(i: usize, params: &str, labels: &Labels) -> Result<usize, String> {
+    let addr = parse_num(
+        i,
+        params,
+        labels,
+        "Address",
+        PROG_START_ADDRESS as i64,
+        MAX_ADDRESS as i64,
+    )?;
+    Ok(addr as usize)
+}
+
+/// Number of bytes needed to align `addr`
+fn align(i: usize, params: &str, addr: usize, labels: &Labels) -> Result<usize, String> {
+    let size = parse_num(i, params, labels, "Alignment", 1, MAX_ADDRESS as i64)? as usize;
+    Ok((size - addr % size) % size)
+}
+
+fn fill_count(i: usize, params: &str, labels: &Labels) -> Result<usize, String> {
+    let list = split_list(params);
+    if list.is_empty() || list.len() > 2 {
+        return Err(format!("Line {i}) Expected 'fill count, byte'"));
+    }
+    Ok(parse_num(i, list[0], labels, "Count", 1, MAX_ADDRESS as i64)? as usize)
+}
+
+fn string(i: usize, params: &str) -> Result<Vec<u8>, String> {
+    let list = split_list(params);
+    match list.as_slice() {
+        [text] => parse_string(i, text),
+        [text, mode] if mode.eq_ignore_ascii_case("asc") => parse_string(i, text)?
+            .iter()
+            .map(|code| {
+                alpha_index(*code as char)
+                    .map(|idx| idx as u8)
+                    .ok_or_else(|| format!("Line {i}) '{}' has no ASC glyph", *code as char))
+            })
+            .collect(),
+        _ => Err(format!(
+            "Line {i}) Expected 'string \"text\"' or 'string \"text\", asc'"
+        )),
+    }
+}
+
+fn incbin(i: usize, params: &str, dir: &Path) -> Result<Vec<u8>, String> {
+    let file = String::from_utf8(parse_string(i, params.trim())?)
+        .map_err(|_| format!("Line {i}) Invalid file name"))?;
+    fs::read(dir.join(&file)).map_err(|err| format!("Line {i}) Unable to read '{file}': {err}"))
+}
+
+/// Bytes of a quoted string, supports `\"`, `\\`, `\n` and `\0`
+pub fn parse_string(i: usize, text: &str) -> Result<Vec<u8>, String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .filter(|_| text.len() >= 2)
+        .ok_or_else(|| format!("Line {i}) Expected quoted string but found '{text}'"))?;
+    let mut bytes = vec![];
+    let mut chars = inner.chars();
+    while let Some(chr) = chars.next() {
+        let chr = match chr {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('0') => '\0',
+                Some(chr @ ('"' | '\\')) => chr,
+                _ => return Err(format!("Line {i}) Invalid escape in {text}")),
+            },
+            '"' => return Err(format!("Line {i}) Unescaped quote in {text}")),
+            chr => chr,
+        };
+        if !chr.is_ascii() {
+            return Err(format!("Line {i}) '{chr}' is not ASCII"));
+        }
+        bytes.push(chr as u8);
+    }
+    Ok(bytes)
+}
+
+/// Split on commas outside of quotes and brackets
+pub fn split_list(params: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, chr) in unquoted(params) {
+        match chr {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(params[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(params[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::directives::{parse_string, split_list, Directive};
+    use crate::parser::labels::Labels;
+    use crate::program::Line;
+    use std::path::Path;
+
+    fn parse(line: &str, addr: usize) -> Result<Line, String> {
+        let (word, params) = line.split_once(' ').unwrap();
+        let directive = Directive::find(word).unwrap();
+        let dir = Path::new("../examples");
+        let labels = Labels::default();
+        let line = directive.parse(1, params, addr, &labels, dir)?;
+        let next = directive.next_addr(1, params, addr, &labels, dir)?;
+        match &line {
+            Line::Data { bytes, .. } => assert_eq!(next, addr + bytes.len(), "{line:?}"),
+            Line::Org { addr, .. } => assert_eq!(next, *addr as usize),
+            _ => {}
+        }
+        Ok(line)
+    }
+
+    #[test]
+    fn check_directives() {
+        assert_eq!(parse("org 0x300", 0x200), Ok(Line::new_org(1, 0x300)));
+        assert_eq!(
+            parse("org 0x100", 0x200),
+            Err("Line 1) Address 256 is out of range (512 to 4095)".to_string())
+        );
+        assert_eq!(parse("align 4", 0x202), Ok(Line::new_org(1, 0x204)));
+        assert_eq!(parse("align 4", 0x204), Ok(Line::new_org(1, 0x204)));
+        assert_eq!(
+            parse("db 1, 'a', \"b,c\", -1, hi(0x1234)", 0x200),
+            Ok(Line::new_data(1, vec![1, 97, 98, 44, 99, 255, 0x12]))
+        );
+        assert_eq!(
+            parse("dw 0x1234, 5", 0x200),
+            Ok(Line::new_data(1, vec![0x12, 0x34, 0, 5]))
+        );
+        assert_eq!(
+            parse("fill 3, 0xAA", 0x200),
+            Ok(Line::new_data(1, vec![0xAA; 3]))
+        );
+        assert_eq!(parse("fill 2", 0x200), Ok(Line::new_data(1, vec![0; 2])));
+        assert_eq!(
+            parse("string \"Hi\\n\"", 0x200),
+            Ok(Line::new_data(1, vec![b'H', b'i', b'\n']))
+        );
+        assert_eq!(
+            parse("string \"a0\", asc", 0x200),
+            Ok(Line::new_data(1, vec![10, 0]))
+        );
+        assert_eq!(
+            parse("string \"~\", asc", 0x200),
+            Err("Line 1) '~' has no ASC glyph".to_string())
+        );
+        assert_eq!(
+            parse("incbin \"test.c8\"", 0x200),
+            Ok(Line::new_data(
+                1,
+                std::fs::read("../examples/test.c8").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn check_helpers() {
+        assert_eq!(
+            split_list("1, \"a,\\\"b\", ',', hi(x), "),
+            vec!["1", "\"a,\\\"b\"", "','", "hi(x)"]
+        );
+        assert_eq!(parse_string(0, "\"a\\\\\""), Ok(vec![b'a', b'\\']));
+        assert!(parse_string(0, "\"").is_err());
+        assert!(parse_string(0, "abc").is_err());
+    }
+}
@@ -0,0 +1,352 @@
+//! Constant expressions
+//!
+//! Numeric operands can be any constant expression, such as `(W - 8) / 2` or `hi(table + 2)`
+//!
+//! Literals
+//! - `1F` Hexadecimal, or decimal with `--decimal`
+//! - `0x1F` Hexadecimal
+//! - `0b1010` Binary
+//! - `'a'` ASCII character
+//!
+//! Operators, from highest to lowest precedence
+//! - `-x`, `~x`, `!x` (1 if `x` is 0, otherwise 0)
+//! - `*`, `/`, `%`
+//! - `+`, `-`
+//! - `<<`, `>>`
+//! - `&`
+//! - `^`
+//! - `|`
+//! - `==`, `!=`, `<`, `<=`, `>`, `>=` (1 if true, otherwise 0)
+//!
+//! `hi(x)` is the high byte of `x` and `lo(x)` is the low byte, names are replaced with the
+//! address of the label or the value of the constant
+
+use crate::parser::labels::Labels;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+/// Operators, longer ones first so they aren't split
+const OPS: [&str; 18] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "<", ">", "!", "+", "-", "*", "/", "%", "&", "|", "^", "~",
+];
+
+/// Evaluate `text`, names are looked up in `labels`
+pub fn eval(text: &str, labels: &Labels) -> Result<i64, String> {
+    let tokens = tokenize(text, labels.is_decimal())?;
+    if tokens.is_empty() {
+        return Err(String::from("Missing value"));
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        labels,
+    };
+    let value = parser.expr(0)?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(token) => Err(format!("Unexpected {} in '{text}'", describe(token))),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Num(num) => format!("number {num}"),
+        Token::Name(name) => format!("name '{name}'"),
+        Token::Op(op) => format!("'{op}'"),
+        Token::Open => String::from("'('"),
+        Token::Close => String::from("')'"),
+    }
+}
+
+fn tokenize(text: &str, decimal: bool) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&chr) = chars.peek() {
+        if chr.is_whitespace() {
+            chars.next();
+        } else if chr == '(' {
+            chars.next();
+            tokens.push(Token::Open);
+        } else if chr == ')' {
+            chars.next();
+            tokens.push(Token::Close);
+        } else if chr == '\'' {
+            chars.next();
+            let value = chars.next().ok_or("Unclosed character literal")?;
+            if chars.next() != Some('\'') || !value.is_ascii() {
+                return Err(String::from(
+                    "Character literals must be a single ASCII char",
+                ));
+            }
+            tokens.push(Token::Num(value as i64));
+        } else if chr.is_ascii_digit() {
+            tokens.push(Token::Num(number(&mut chars, decimal)?));
+        } else if chr.is_ascii_alphabetic() || chr == '_' {
+            let mut name = String::new();
+            while let Some(&chr) = chars
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+            {
+                name.push(chr);
+                chars.next();
+            }
+            tokens.push(Token::Name(name));
+        } else {
+            let rest: String = chars.clone().take(2).collect();
+            let op = OPS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| format!("Unexpected '{chr}'"))?;
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            tokens.push(Token::Op(op));
+        }
+    }
+    Ok(tokens)
+}
+
+fn number(chars: &mut Peekable<Chars>, decimal: bool) -> Result<i64, String> {
+    let mut text = String::new();
+    while let Some(&chr) = chars
+        .peek()
+        .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+    {
+        text.push(chr);
+        chars.next();
+    }
+    let lower = text.to_lowercase().replace('_', "");
+    let result = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b").filter(|bin| is_binary(bin)) {
+        i64::from_str_radix(bin, 2)
+    } else if decimal {
+        lower.parse::<i64>()
+    } else {
+        i64::from_str_radix(&lower, 16)
+    };
+    result.map_err(|_| format!("Invalid number '{text}'"))
+}
+
+/// `0b` followed by something that isn't binary (such as `0B` or `0B1F`) is a hex number
+fn is_binary(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c == '0' || c == '1')
+}
+
+/// Numbers without a prefix in `text` that have a different value in hex and decimal
+pub fn ambiguous_numbers(text: &str) -> Vec<String> {
+    let mut numbers = vec![];
+    let mut word = String::new();
+    let mut quote = None;
+    for chr in text.chars().chain([' ']) {
+        match quote {
+            Some(end) if chr == end => quote = None,
+            Some(_) => {}
+            None if chr.is_ascii_alphanumeric() || chr == '_' => word.push(chr),
+            None => {
+                if !word.is_empty()
+                    && word.chars().all(|c| c.is_ascii_digit())
+                    && word.parse::<u64>().map_or(true, |num| num >= 10)
+                {
+                    numbers.push(word.clone());
+                }
+                word.clear();
+                quote = match chr {
+                    '\'' | '"' => Some(chr),
+                    '[' => Some(']'),
+                    _ => None,
+                };
+            }
+        }
+    }
+    numbers
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    labels: &'a Labels,
+}
+
+/// Binary operators for each precedence level, lowest first
+const LEVELS: [&[&str]; 7] = [
+    &["==", "!=", "<", "<=", ">", ">="],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expr(&mut self, level: usize) -> Result<i64, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.expr(level + 1)?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            let op = *op;
+            if !LEVELS[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(level + 1)?;
+            lhs = match op {
+                "==" => (lhs == rhs) as i64,
+                "!=" => (lhs != rhs) as i64,
+                "<" => (lhs < rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                ">=" => (lhs >= rhs) as i64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "<<" => lhs.checked_shl(rhs as u32).ok_or("Shift is too large")?,
+                ">>" => lhs.checked_shr(rhs as u32).ok_or("Shift is too large")?,
+                "+" => lhs.checked_add(rhs).ok_or("Value is too large")?,
+                "-" => lhs.checked_sub(rhs).ok_or("Value is too large")?,
+                "*" => lhs.checked_mul(rhs).ok_or("Value is too large")?,
+                "/" => lhs.checked_div(rhs).ok_or("Division by zero")?,
+                _ => lhs.checked_rem(rhs).ok_or("Division by zero")?,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        match self.next() {
+            Some(Token::Op("-")) => self
+                .unary()?
+                .checked_neg()
+                .ok_or(String::from("Value is too large")),
+            Some(Token::Op("~")) => Ok(!self.unary()?),
+            Some(Token::Op("!")) => Ok((self.unary()? == 0) as i64),
+            Some(Token::Num(num)) => Ok(num),
+            Some(Token::Open) => {
+                let value = self.expr(0)?;
+                self.close()?;
+                Ok(value)
+            }
+            Some(Token::Name(name)) => {
+                if let Some(Token::Open) = self.tokens.get(self.pos) {
+                    self.pos += 1;
+                    let value = self.expr(0)?;
+                    self.close()?;
+                    match name.as_str() {
+                        "hi" => Ok((value >> 8) & 0xFF),
+                        "lo" => Ok(value & 0xFF),
+                        _ => Err(format!("Unknown function '{name}'")),
+                    }
+                } else if self.labels.alias(&name).is_some() {
+                    Err(format!("Register alias '{name}' can't be used as a number"))
+                } else {
+                    self.labels
+                        .value(&name)
+                        .or_else(|| self.labels.hex(&name))
+                        .ok_or_else(|| format!("Undefined label '{name}'"))
+                }
+            }
+            Some(token) => Err(format!("Unexpected {}", describe(&token))),
+            None => Err(String::from("Missing value")),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Close) => Ok(()),
+            _ => Err(String::from("Missing ')'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::clean_up;
+    use crate::parser::expr::{ambiguous_numbers, eval};
+    use crate::parser::labels::Labels;
+    use crate::parser::sources::Sources;
+    use crate::parser::Settings;
+    use std::path::Path;
+
+    #[test]
+    fn check_literals() {
+        let labels = Labels::default();
+        assert_eq!(eval("12", &labels), Ok(0x12));
+        assert_eq!(eval("FF", &labels), Ok(0xFF));
+        assert_eq!(eval("0x1F", &labels), Ok(0x1F));
+        assert_eq!(eval("0B1010", &labels), Ok(10));
+        assert_eq!(eval("0B", &labels), Ok(0x0B));
+        assert_eq!(eval("'a'", &labels), Ok(97));
+        assert_eq!(eval("1G", &labels), Err("Invalid number '1G'".to_string()));
+        assert_eq!(eval("", &labels), Err("Missing value".to_string()));
+
+        let labels = Labels::new(&Settings {
+            defines: vec![],
+            decimal: true,
+        });
+        assert_eq!(eval("12", &labels), Ok(12));
+        assert_eq!(eval("0x1F", &labels), Ok(0x1F));
+        assert_eq!(eval("0b1010", &labels), Ok(10));
+        assert_eq!(eval("1A", &labels), Err("Invalid number '1A'".to_string()));
+        assert_eq!(eval("FF", &labels), Err("Undefined label 'FF'".to_string()));
+    }
+
+    #[test]
+    fn check_ambiguous_numbers() {
+        assert_eq!(ambiguous_numbers("set v0, 10"), vec!["10"]);
+        assert_eq!(ambiguous_numbers("drw v0, v1, 5"), Vec::<String>::new());
+        assert_eq!(
+            ambiguous_numbers("fill 0x10, 09 + 0b11"),
+            Vec::<String>::new()
+        );
+        assert_eq!(ambiguous_numbers("dat [1234]"), Vec::<String>::new());
+        assert_eq!(ambiguous_numbers("string \"99\""), Vec::<String>::new());
+        assert_eq!(ambiguous_numbers("equ W 64 - 1F"), vec!["64"]);
+    }
+
+    #[test]
+    fn check_expressions() {
+        let labels = {
+            let source = vec!["clr", "table: dat [00]"];
+            let sources =
+                Sources::load(source.clone(), Path::new("test.eca"), &Settings::default()).unwrap();
+            Labels::collect(&clean_up(source), &sources).0
+        };
+        assert_eq!(eval("(0x40 - 8) / 2", &labels), Ok(28));
+        assert_eq!(eval("1 + 2 * 3", &labels), Ok(7));
+        assert_eq!(eval("1 << 4 | 1", &labels), Ok(17));
+        assert_eq!(eval("-1", &labels), Ok(-1));
+        assert_eq!(eval("1 + 1 == 2", &labels), Ok(1));
+        assert_eq!(eval("table >= 0x300", &labels), Ok(0));
+        assert_eq!(eval("1 << 2 < 5 != !0", &labels), Ok(0));
+        assert_eq!(eval("table + 2", &labels), Ok(0x204));
+        assert_eq!(eval("hi(table)", &labels), Ok(0x02));
+        assert_eq!(eval("lo(table + 1)", &labels), Ok(0x03));
+        assert_eq!(
+            eval("missing", &labels),
+            Err("Undefined label 'missing'".to_string())
+        );
+        assert_eq!(eval("(1 + 2", &labels), Err("Missing ')'".to_string()));
+        assert_eq!(eval("4 / 0", &labels), Err("Division by zero".to_string()));
+        assert_eq!(
+            eval("1 2", &labels),
+            Err("Unexpected number 2 in '1 2'".to_string())
+        );
+    }
+}
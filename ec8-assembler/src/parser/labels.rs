@@ -0,0 +1,496 @@
+//! Labels
+//!
+//! A label is a name followed by a colon at the start of a line, i.e. `loop: add v0, 1`
+//! and can be used instead of an address, i.e. `jmp loop`
+//!
+//! Labels are found in a first pass over the source so they can be used before they're defined
+//!
+//! The same pass defines constants (`equ NAME expr`) and register aliases (`alias NAME vX`),
+//! constants are evaluated once every label is known, but only constants defined before a
+//! layout directive (`org`, `align` and the count for `fill`) can be used by it
+
+use crate::parser::directives::Directive;
+use crate::parser::expr::eval;
+use crate::parser::macros::Macro;
+use crate::parser::sources::Sources;
+use crate::parser::{parse_reg, unquoted, Line, Settings};
+use crate::program::Label;
+use ec8_common::{MAX_ADDRESS, PROG_START_ADDRESS};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Labels {
+    /// Name to address and line it was defined on
+    labels: HashMap<String, (u16, usize)>,
+    /// Name to value and line it was defined on
+    constants: HashMap<String, (i64, usize)>,
+    /// Name to register and line it was defined on
+    aliases: HashMap<String, (u8, usize)>,
+    /// Constants that use labels defined after them, name to expression and line
+    pending: HashMap<String, (String, usize)>,
+    /// Constants set on the command line, these replace constants with the same name
+    defines: HashMap<String, i64>,
+    /// If numbers without a prefix are decimal rather than hex
+    decimal: bool,
+    /// Every name defined in the source, these are never read as hex numbers
+    declared: HashSet<String>,
+}
+
+impl Labels {
+    pub fn new(settings: &Settings) -> Labels {
+        Labels {
+            defines: settings
+                .defines
+                .iter()
+                .map(|define| (define.name.clone(), define.value))
+                .collect(),
+            decimal: settings.decimal,
+            ..Labels::default()
+        }
+    }
+
+    pub fn is_decimal(&self) -> bool {
+        self.decimal
+    }
+
+    /// Value of `name` read as a hex number, unless numbers are decimal or it's defined in the
+    /// source (such as `add` or `cafe`)
+    pub fn hex(&self, name: &str) -> Option<i64> {
+        if self.decimal || self.declared.contains(name) {
+            return None;
+        }
+        i64::from_str_radix(name, 16).ok()
+    }
+
+    pub fn get(&self, name: &str) -> Option<u16> {
+        self.labels.get(name).map(|(addr, _)| *addr)
+    }
+
+    /// Value of a label or constant
+    pub fn value(&self, name: &str) -> Option<i64> {
+        self.get(name)
+            .map(i64::from)
+            .or_else(|| self.constants.get(name).map(|(value, _)| *value))
+            .or_else(|| self.defines.get(name).copied())
+    }
+
+    /// Register number for an alias
+    pub fn alias(&self, name: &str) -> Option<u8> {
+        self.aliases.get(name).map(|(reg, _)| *reg)
+    }
+
+    /// Kind and line of the definition of `name`
+    fn defined(&self, name: &str) -> Option<(&'static str, usize)> {
+        let label = self.labels.get(name).map(|(_, i)| ("label", *i));
+        let constant = self.constants.get(name).map(|(_, i)| ("constant", *i));
+        let alias = self.aliases.get(name).map(|(_, i)| ("alias", *i));
+        let pending = self.pending.get(name).map(|(_, i)| ("constant", *i));
+        label.or(constant).or(alias).or(pending)
+    }
+
+    /// Error if `name` can't be used for a new `kind` on line `i`
+    fn check_name(&self, i: usize, kind: &str, name: &str) -> Result<(), String> {
+        let upper = kind[..1].to_uppercase() + &kind[1..];
+        if !is_label(name) {
+            Err(format!("Line {i}) Invalid {kind} name '{name}'"))
+        } else if parse_reg(name, 1).is_ok() {
+            Err(format!("Line {i}) {upper} '{name}' is a register name"))
+        } else if self.defines.contains_key(name) {
+            Err(format!(
+                "Line {i}) {upper} '{name}' has the same name as a command line define"
+            ))
+        } else if let Some((first_kind, first)) = self.defined(name) {
+            if first_kind == kind {
+                Err(format!(
+                    "Line {i}) Duplicate {kind} '{name}', first defined on line {first}"
+                ))
+            } else {
+                Err(format!(
+                    "Line {i}) {upper} '{name}' has the same name as the {first_kind} on line {first}"
+                ))
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Add the constant or alias defined by `code`, returns false if it's not a definition
+    ///
+    /// Constants with the same name as a command line define are ignored
+    pub fn define(&mut self, i: usize, code: &str) -> Result<bool, String> {
+        let Some((kind, name, value)) = definition(code) else {
+            return Ok(false);
+        };
+        if kind == "constant" && self.defines.contains_key(name) {
+            return Ok(true);
+        }
+        self.check_name(i, kind, name)?;
+        if kind == "constant" {
+            match eval(value, self) {
+                Ok(value) => {
+                    self.constants.insert(name.to_string(), (value, i));
+                }
+                Err(_) => {
+                    self.pending
+                        .insert(name.to_string(), (value.to_string(), i));
+                }
+            }
+        } else {
+            let reg = register(value, 2, self)
+                .map_err(|_| format!("Line {i}) Alias '{name}' must be a register (v0 to vF)"))?;
+            self.aliases.insert(name.to_string(), (reg, i));
+        }
+        Ok(true)
+    }
+
+    /// Evaluate constants that used labels defined after them, in any order
+    fn resolve_pending(&mut self) -> Vec<String> {
+        while !self.pending.is_empty() {
+            let resolved: Vec<(String, i64, usize)> = self
+                .pending
+                .iter()
+                .filter_map(|(name, (expr, i))| {
+                    eval(expr, self).ok().map(|value| (name.clone(), value, *i))
+                })
+                .collect();
+            if resolved.is_empty() {
+                break;
+            }
+            for (name, value, i) in resolved {
+                self.pending.remove(&name);
+                self.constants.insert(name, (value, i));
+            }
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let mut errors: Vec<(usize, String)> = pending
+            .iter()
+            .map(|(name, (expr, i))| {
+                let err = eval(expr, self).err().unwrap_or_default();
+                let cycle = pending
+                    .keys()
+                    .any(|other| err.contains(&format!("'{other}'")));
+                if cycle {
+                    (
+                        *i,
+                        format!("Line {i}) Constant '{name}' is defined using itself"),
+                    )
+                } else {
+                    (*i, format!("Line {i}) {err}"))
+                }
+            })
+            .collect();
+        errors.sort();
+        errors.into_iter().map(|(_, err)| err).collect()
+    }
+
+    /// Macro params that have the same name as a label, constant or alias
+    pub fn shadowed<'a>(&self, macros: impl Iterator<Item = (usize, &'a Macro)>) -> Vec<String> {
+        let mut warnings = vec![];
+        for (i, mac) in macros {
+            for param in mac.params() {
+                if let Some((kind, first)) = self.defined(param) {
+                    warnings.push(format!(
+                        "Line {i}) Param '{param}' of macro '{}' shadows the {kind} defined on line {first}",
+                        mac.name
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Find the address of every label in `source`, along with every label that is invalid,
+    /// defined more than once or past the end of memory
+    ///
+    /// Labels that can't be added are left out, so the rest of the source can still be checked
+    pub fn collect(source: &[(usize, String, String)], sources: &Sources) -> (Labels, Vec<String>) {
+        let mut labels = Labels::new(sources.settings());
+        for (i, code, _) in source {
+            if let Ok((label, code)) = split_label(*i, code) {
+                let name = definition(code).map(|(_, name, _)| name);
+                labels
+                    .declared
+                    .extend(label.into_iter().chain(name).map(String::from));
+            }
+        }
+        let mut errors = vec![];
+        let mut addr = PROG_START_ADDRESS as usize;
+        for (i, code, _) in source {
+            let (label, code) = match split_label(*i, code) {
+                Ok(split) => split,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+            if let Some(name) = label {
+                if addr > MAX_ADDRESS as usize {
+                    errors.push(format!(
+                        "Line {i}) Label '{name}' is past the end of memory"
+                    ));
+                } else if let Err(err) = labels.check_name(*i, "label", name) {
+                    errors.push(err);
+                } else {
+                    labels.labels.insert(name.to_string(), (addr as u16, *i));
+                }
+            }
+            match labels.define(*i, code) {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            }
+            match next_addr(*i, code, addr, &labels, sources.dir(*i)) {
+                Ok(next) => addr = next,
+                Err(err) => errors.push(err),
+            }
+        }
+        errors.extend(labels.resolve_pending());
+        (labels, errors)
+    }
+
+    /// Names of every label and constant
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.labels
+            .keys()
+            .chain(self.constants.keys())
+            .map(String::as_str)
+    }
+
+    /// Every label sorted by address, with the lines in `source` that use it
+    pub fn symbols(&self, source: &[(usize, String, String)]) -> Vec<Label> {
+        let mut references: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, code, _) in source {
+            let (_, code) = split_label(*i, code).unwrap_or((None, code));
+            let params = code.split_once(char::is_whitespace).unwrap_or_default().1;
+            for name in words(params) {
+                if let Some((name, _)) = self.labels.get_key_value(name) {
+                    let lines = references.entry(name).or_default();
+                    if lines.last() != Some(i) {
+                        lines.push(*i);
+                    }
+                }
+            }
+        }
+        let mut symbols: Vec<Label> = self
+            .labels
+            .iter()
+            .map(|(name, (addr, defined))| Label {
+                name: name.clone(),
+                addr: *addr,
+                defined: *defined,
+                references: references.remove(name.as_str()).unwrap_or_default(),
+            })
+            .collect();
+        symbols.sort_by(|lhs, rhs| (lhs.addr, &lhs.name).cmp(&(rhs.addr, &rhs.name)));
+        symbols
+    }
+}
+
+/// Names in `text` outside of quotes
+fn words(text: &str) -> Vec<&str> {
+    let mut words = vec![];
+    let mut start = None;
+    let mut next = 0;
+    for (idx, chr) in unquoted(text).into_iter().chain([(text.len(), ' ')]) {
+        if let Some(first) = start.filter(|_| idx != next || !is_word(chr)) {
+            words.push(&text[first..next]);
+            start = None;
+        }
+        if start.is_none() && is_word(chr) {
+            start = Some(idx);
+        }
+        next = idx + chr.len_utf8();
+    }
+    words.retain(|word| is_label(word));
+    words
+}
+
+fn is_word(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || chr == '_'
+}
+
+/// Split `name:` from the start of `code`, returning the name (if any) and the rest of the line
+pub fn split_label(i: usize, code: &str) -> Result<(Option<&str>, &str), String> {
+    let colon = unquoted(code).into_iter().find(|(_, chr)| *chr == ':');
+    match colon.map(|(idx, _)| (&code[..idx], &code[idx + 1..])) {
+        None => Ok((None, code)),
+        Some((name, rest)) => {
+            let name = name.trim();
+            if parse_reg(name, 1).is_ok() {
+                Err(format!("Line {i}) Label '{name}' is a register name"))
+            } else if is_label(name) {
+                Ok((Some(name), rest.trim()))
+            } else {
+                Err(format!("Line {i}) Invalid label '{name}'"))
+            }
+        }
+    }
+}
+
+/// Kind, name and value of an `equ NAME expr` or `alias NAME vX` line
+pub fn definition(code: &str) -> Option<(&'static str, &str, &str)> {
+    let (word, params) = code.split_once(char::is_whitespace)?;
+    let kind = match word.to_lowercase().as_str() {
+        "equ" => "constant",
+        "alias" => "alias",
+        _ => return None,
+    };
+    let params = params.trim();
+    let (name, value) = params
+        .split_once(char::is_whitespace)
+        .unwrap_or((params, ""));
+    Some((kind, name, value.trim()))
+}
+
+/// Register number for `param`, which can be an alias
+pub fn register(param: &str, which: usize, labels: &Labels) -> Result<u8, String> {
+    match labels.alias(param.trim()) {
+        Some(reg) => Ok(reg),
+        //the second register is returned in the high nibble
+        None => parse_reg(param, which).map(|reg| if which == 2 { reg >> 4 } else { reg }),
+    }
+}
+
+/// Labels start with a letter or underscore, followed by letters, digits or underscores
+pub fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(chr) if chr.is_ascii_alphabetic() || chr == '_' => {
+            chars.all(|chr| chr.is_ascii_alphanumeric() || chr == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Address after `code`, without evaluating any operands
+pub fn next_addr(
+    i: usize,
+    code: &str,
+    addr: usize,
+    labels: &Labels,
+    dir: &Path,
+) -> Result<usize, String> {
+    let (word, params) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    if code.is_empty() {
+        Ok(addr)
+    } else if let Some(directive) = Directive::find(word) {
+        directive.next_addr(i, params, addr, labels, dir)
+    } else if code.to_lowercase().starts_with("dat") {
+        Ok(Line::data(i, &code[3..])?.next_addr(addr))
+    } else {
+        Ok(addr + 2)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::clean_up;
+    use crate::parser::labels::{is_label, split_label, Labels};
+    use crate::parser::sources::Sources;
+    use crate::parser::Settings;
+    use std::path::Path;
+
+    fn collect(source: Vec<&str>) -> Result<Labels, Vec<String>> {
+        let sources =
+            Sources::load(source.clone(), Path::new("test.eca"), &Settings::default()).unwrap();
+        let (labels, errors) = Labels::collect(&clean_up(source), &sources);
+        if errors.is_empty() {
+            Ok(labels)
+        } else {
+            Err(errors)
+        }
+    }
+
+    #[test]
+    fn check_collect() {
+        let labels = collect(vec![
+            "start: clr",
+            ";comment",
+            "dat [AABBCC]",
+            "end:",
+            "jmp end",
+        ])
+        .unwrap();
+        assert_eq!(labels.get("start"), Some(0x200));
+        assert_eq!(labels.get("end"), Some(0x205));
+        assert_eq!(labels.get("middle"), None);
+
+        assert_eq!(
+            collect(vec!["a: clr", "", "a: ret", "1a: clr", "a: ret"]),
+            Err(vec![
+                "Line 2) Duplicate label 'a', first defined on line 0".to_string(),
+                "Line 3) Invalid label '1a'".to_string(),
+                "Line 4) Duplicate label 'a', first defined on line 0".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn check_definitions() {
+        let labels = collect(vec![
+            "equ WIDTH 40",
+            "equ MID end - WIDTH / 2",
+            "alias x v3",
+            "alias y x",
+            "end: clr",
+        ])
+        .unwrap();
+        assert_eq!(labels.value("WIDTH"), Some(0x40));
+        assert_eq!(labels.value("MID"), Some(0x200 - 32));
+        assert_eq!(labels.value("end"), Some(0x200));
+        assert_eq!(labels.alias("y"), Some(3));
+        assert_eq!(labels.value("x"), None);
+
+        assert_eq!(
+            collect(vec![
+                "equ a 1",
+                "alias a v1",
+                "equ a 2",
+                "alias vf v1",
+                "alias b 4",
+                "a: clr"
+            ]),
+            Err(vec![
+                "Line 1) Alias 'a' has the same name as the constant on line 0".to_string(),
+                "Line 2) Duplicate constant 'a', first defined on line 0".to_string(),
+                "Line 3) Alias 'vf' is a register name".to_string(),
+                "Line 4) Alias 'b' must be a register (v0 to vF)".to_string(),
+                "Line 5) Label 'a' has the same name as the constant on line 0".to_string(),
+            ])
+        );
+        assert_eq!(
+            collect(vec!["equ a b + 1", "equ b a", "equ c missing"]),
+            Err(vec![
+                "Line 0) Constant 'a' is defined using itself".to_string(),
+                "Line 1) Constant 'b' is defined using itself".to_string(),
+                "Line 2) Undefined label 'missing'".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn check_split_label() {
+        assert_eq!(split_label(0, "clr"), Ok((None, "clr")));
+        assert_eq!(split_label(0, "loop: clr"), Ok((Some("loop"), "clr")));
+        assert_eq!(split_label(0, " _end :"), Ok((Some("_end"), "")));
+        assert_eq!(
+            split_label(3, "1a: clr"),
+            Err("Line 3) Invalid label '1a'".to_string())
+        );
+        assert_eq!(
+            split_label(5, "vA: clr"),
+            Err("Line 5) Label 'vA' is a register name".to_string())
+        );
+        assert_eq!(
+            split_label(0, "string \"a:b\""),
+            Ok((None, "string \"a:b\""))
+        );
+        assert!(is_label("loop_2"));
+        assert!(!is_label("loop-2"));
+        assert!(!is_label(""));
+    }
+}
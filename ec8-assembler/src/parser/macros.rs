@@ -0,0 +1,235 @@
+//! Macros
+//!
+//! ```text
+//! macro draw_at x, y, sprite
+//!     set v0, x
+//!     set v1, y
+//!     sti sprite
+//!     drw v0, v1, 5
+//! endm
+//!
+//! draw_at 4, 8, ship
+//! draw_at y=8, x=4, sprite=ship
+//! ```
+//!
+//! Params can be passed by position or by name, each use of a param in the body is replaced with
+//! the text passed in. Labels in the body starting with `@` are local, they are renamed for each
+//! expansion so a macro can be used more than once
+//!
+//! Macros must be defined before they're used and can use other macros, up to [MAX_DEPTH] deep
+
+use crate::parser::directives::{split_list, Directive};
+use crate::parser::labels::is_label;
+use crate::parser::{comment_start, unquoted};
+use ec8_common::definitions::by_mnemonic;
+
+/// Max number of nested macro expansions
+pub const MAX_DEPTH: usize = 16;
+
+/// Words that aren't instructions or directives but can't be used as macro names
+pub const RESERVED: [&str; 7] = ["dat", "if", "elif", "else", "endif", "equ", "alias"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro {
+    pub name: String,
+    params: Vec<String>,
+    /// Index of each line (in [Sources](crate::parser::sources::Sources)) and the line
+    pub body: Vec<(usize, String)>,
+}
+
+impl Macro {
+    /// Start a macro from a `macro name a, b` line
+    pub fn new(params: &str) -> Result<Macro, String> {
+        let (name, params) = params
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((params.trim(), ""));
+        if !is_label(name) {
+            return Err(format!("Invalid macro name '{name}'"));
+        }
+        if !by_mnemonic(name).is_empty()
+            || Directive::find(name).is_some()
+            || RESERVED.iter().any(|word| name.eq_ignore_ascii_case(word))
+        {
+            return Err(format!(
+                "Macro name '{name}' is already used by an instruction"
+            ));
+        }
+        let params: Vec<String> = split_list(params).iter().map(|p| p.to_string()).collect();
+        for (i, param) in params.iter().enumerate() {
+            if !is_label(param) {
+                return Err(format!("Invalid param name '{param}'"));
+            }
+            if params[..i].contains(param) {
+                return Err(format!("Param '{param}' is used more than once"));
+            }
+        }
+        Ok(Macro {
+            name: name.to_string(),
+            params,
+            body: vec![],
+        })
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    /// Lines of the body with params and local labels replaced, `id` must be unique for each
+    /// expansion
+    pub fn expand(&self, args: &str, id: usize) -> Result<Vec<(usize, String)>, String> {
+        let values = self.values(args)?;
+        Ok(self
+            .body
+            .iter()
+            .map(|(i, line)| (*i, self.replace(line, &values, id)))
+            .collect())
+    }
+
+    /// Value for each param
+    fn values(&self, args: &str) -> Result<Vec<String>, String> {
+        let mut values: Vec<Option<String>> = vec![None; self.params.len()];
+        for (i, arg) in split_list(args).into_iter().enumerate() {
+            let named = arg
+                .split_once('=')
+                .filter(|(name, _)| is_label(name.trim()));
+            let (idx, value) = match named {
+                Some((name, value)) => {
+                    let name = name.trim();
+                    let idx = self
+                        .params
+                        .iter()
+                        .position(|param| param == name)
+                        .ok_or_else(|| format!("Macro '{}' has no param '{name}'", self.name))?;
+                    (idx, value.trim())
+                }
+                None => (i, arg),
+            };
+            if idx >= values.len() {
+                return Err(format!("{} expected", self.signature()));
+            }
+            if values[idx].is_some() {
+                return Err(format!(
+                    "Param '{}' for macro '{}' is set more than once",
+                    self.params[idx], self.name
+                ));
+            }
+            values[idx] = Some(value.to_string());
+        }
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(idx, value)| {
+                value.ok_or_else(|| {
+                    format!(
+                        "Missing param '{}', {} expected",
+                        self.params[idx],
+                        self.signature()
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn signature(&self) -> String {
+        format!("'{} {}'", self.name, self.params.join(", "))
+    }
+
+    /// Replace params and local labels in `line`, text in quotes and comments isn't changed
+    fn replace(&self, line: &str, values: &[String], id: usize) -> String {
+        let (line, comment) = line.split_at(comment_start(line).unwrap_or(line.len()));
+        let unquoted: Vec<usize> = unquoted(line).into_iter().map(|(idx, _)| idx).collect();
+        let mut output = String::new();
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, chr)) = chars.next() {
+            let is_word = |chr: char| chr.is_ascii_alphanumeric() || chr == '_';
+            if !unquoted.contains(&start) || !(is_word(chr) || chr == '@') {
+                output.push(chr);
+                continue;
+            }
+            let mut end = start + chr.len_utf8();
+            while let Some((idx, chr)) = chars.peek().copied() {
+                if !is_word(chr) {
+                    break;
+                }
+                end = idx + chr.len_utf8();
+                chars.next();
+            }
+            let word = &line[start..end];
+            if let Some(local) = word.strip_prefix('@') {
+                output.push_str(&format!("__{}_{id}_{local}", self.name));
+            } else if let Some(idx) = self.params.iter().position(|param| param == word) {
+                output.push_str(&values[idx]);
+            } else {
+                output.push_str(word);
+            }
+        }
+        output.push_str(comment);
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::macros::Macro;
+
+    fn make(def: &str, body: &[&str]) -> Macro {
+        let mut mac = Macro::new(def).unwrap();
+        mac.body = body
+            .iter()
+            .enumerate()
+            .map(|(i, line)| (i, line.to_string()))
+            .collect();
+        mac
+    }
+
+    #[test]
+    fn check_expand() {
+        let mac = make(
+            "draw_at x, y",
+            &[
+                "@loop: set v0, x ;x",
+                "db \"x\", 'y', y",
+                "jmp @loop",
+                "xy: set v1, (y)",
+            ],
+        );
+        let expected = vec![
+            (0, "__draw_at_3_loop: set v0, 4 ;x".to_string()),
+            (1, "db \"x\", 'y', 8 + 1".to_string()),
+            (2, "jmp __draw_at_3_loop".to_string()),
+            (3, "xy: set v1, (8 + 1)".to_string()),
+        ];
+        assert_eq!(mac.expand("4, 8 + 1", 3), Ok(expected.clone()));
+        assert_eq!(mac.expand("y = 8 + 1, x=4", 3), Ok(expected));
+    }
+
+    #[test]
+    fn check_errors() {
+        let mac = make("draw_at x, y", &[]);
+        assert_eq!(
+            mac.expand("1", 0),
+            Err("Missing param 'y', 'draw_at x, y' expected".to_string())
+        );
+        assert_eq!(
+            mac.expand("1, 2, 3", 0),
+            Err("'draw_at x, y' expected".to_string())
+        );
+        assert_eq!(
+            mac.expand("1, x=2", 0),
+            Err("Param 'x' for macro 'draw_at' is set more than once".to_string())
+        );
+        assert_eq!(
+            mac.expand("z=1", 0),
+            Err("Macro 'draw_at' has no param 'z'".to_string())
+        );
+        assert_eq!(
+            Macro::new("add a"),
+            Err("Macro name 'add' is already used by an instruction".to_string())
+        );
+        assert_eq!(
+            Macro::new("m a, a"),
+            Err("Param 'a' is used more than once".to_string())
+        );
+    }
+}
@@ -1,35 +1,181 @@
-use crate::program::{Line, Program};
-use color_eyre::eyre::eyre;
-use color_eyre::Result;
-use ec8_common::nibbler::Nibbler;
-use ec8_common::OpCodes;
-use ec8_common::OpCodes::*;
+mod directives;
+mod expr;
+mod labels;
+mod macros;
+mod octo;
+mod sources;
 
-pub fn parse(source: Vec<&str>) -> Result<Program> {
-    let source = clean_up(source);
+use crate::diagnostic::{similar, Diagnostic, Diagnostics};
+use crate::parser::directives::Directive;
+use crate::parser::expr::{ambiguous_numbers, eval};
+use crate::parser::labels::{definition, next_addr, register, split_label, Labels};
+use crate::parser::macros::RESERVED;
+use crate::parser::sources::Sources;
+use crate::program::{Line, Program, SourceLine};
+use clap_common::Define;
+use ec8_common::definitions::OperandKind::*;
+use ec8_common::definitions::{by_mnemonic, OperandKind, Operands, DEFINITIONS};
+use ec8_common::instruction::Instruction;
+use ec8_common::{MAX_ADDRESS, PROG_START_ADDRESS};
+use std::path::Path;
+
+/// How source is read, set from the command line
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Constants set with `-D`
+    pub defines: Vec<Define>,
+    /// Read numbers without a prefix as decimal instead of hex
+    pub decimal: bool,
+}
+
+/// Assemble `source`, which was read from `path`
+///
+/// Paths used by `include` and `incbin` are relative to the file containing them
+///
+/// # Errors
+/// Every error found, label and line errors are reported together but lines are only assembled
+/// once every include, macro and condition is valid
+pub fn parse(source: Vec<&str>, path: &Path, settings: &Settings) -> Result<Program, Diagnostics> {
+    let sources = Sources::load(source, path, settings).map_err(Diagnostics)?;
+    assemble(sources)
+}
+
+/// Assemble Octo `source`, which was read from `path`
+///
+/// Numbers are always decimal, as they are in Octo
+///
+/// # Errors
+/// Every error found, lines are only assembled once every statement is valid and supported
+pub fn parse_octo(
+    source: Vec<&str>,
+    path: &Path,
+    settings: &Settings,
+) -> Result<Program, Diagnostics> {
+    let statements = octo::translate(&source, path).map_err(Diagnostics)?;
+    let settings = Settings {
+        decimal: true,
+        ..settings.clone()
+    };
+    assemble(Sources::translated(source, path, &settings, statements))
+}
+
+fn assemble(sources: Sources) -> Result<Program, Diagnostics> {
+    let source = clean_up(sources.lines.iter().map(String::as_str).collect());
+    let (labels, mut errors) = Labels::collect(&source, &sources);
+    let to_diagnostics = |errors: Vec<String>| {
+        let diagnostics = sources.diagnostics(&errors);
+        Diagnostics(
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| add_help(diagnostic, &labels))
+                .collect(),
+        )
+    };
 
     let mut lines = vec![];
-    for (i, code, comment) in source {
-        if code.is_empty() {
-            lines.push(Line::new_comment(i, &comment));
+    let mut lints = vec![];
+    let mut addr = PROG_START_ADDRESS as usize;
+    for (i, code, comment) in &source {
+        let (i, comment) = (*i, comment.as_str());
+        //invalid labels were reported while collecting them
+        let Ok((_, code)) = split_label(i, code) else {
+            continue;
+        };
+        if !labels.is_decimal() {
+            for number in ambiguous_numbers(code) {
+                lints.push(format!(
+                    "Line {i}) Number '{number}' is read as hex (0x{number}), \
+                     write 0x{number} or use --decimal"
+                ));
+            }
+        }
+        if code.is_empty() || definition(code).is_some() {
+            if !comment.is_empty() {
+                lines.push(Line::new_comment(i, comment));
+            }
         } else {
-            lines.push(
-                parse_line(i, &code)
-                    .map_err(|txt| eyre!(txt))?
-                    .append_comment(&comment),
-            );
+            match parse_line(i, code, addr, &labels, sources.dir(i)) {
+                Ok(line) => {
+                    let line = line.append_comment(comment);
+                    addr = line.next_addr(addr);
+                    lines.push(line);
+                }
+                Err(error) => {
+                    //addresses are worked out while collecting labels too, so skip repeats
+                    if !errors.contains(&error) {
+                        errors.push(error);
+                    }
+                    addr = next_addr(i, code, addr, &labels, sources.dir(i)).unwrap_or(addr + 2);
+                }
+            }
         }
     }
+    if !errors.is_empty() {
+        return Err(to_diagnostics(errors));
+    }
+
+    let listing = (0..sources.lines.len())
+        .map(|i| {
+            let (file, line) = sources.position(i);
+            SourceLine {
+                location: sources.short_location(i),
+                file,
+                line,
+                column: sources.column(i),
+                span: sources.span(i, ""),
+                text: sources.original[i].clone(),
+                depth: sources.depth(i),
+            }
+        })
+        .collect();
+    let mut program = Program::new(lines, listing, labels.symbols(&source), sources.files());
+    program.check_layout().map_err(to_diagnostics)?;
+    program.diagnostics = sources
+        .diagnostics(&labels.shadowed(sources.macros()))
+        .into_iter()
+        .map(Diagnostic::into_warning)
+        .collect();
+    program.lints = sources
+        .diagnostics(&lints)
+        .into_iter()
+        .map(Diagnostic::into_warning)
+        .collect();
+    Ok(program)
+}
 
-    Ok(Program::new(lines))
+/// Suggest a similar name for undefined labels and unknown mnemonics
+fn add_help(diagnostic: Diagnostic, labels: &Labels) -> Diagnostic {
+    let name = diagnostic.message.split('\'').nth(1).unwrap_or_default();
+    let similar_name = if diagnostic.message.starts_with("Undefined label") {
+        similar(name, labels.names())
+    } else if diagnostic.message.starts_with("Unknown mnemonic") {
+        let mnemonics = DEFINITIONS.iter().map(|def| def.mnemonic);
+        let directives = Directive::ALL.iter().map(Directive::name);
+        similar(
+            name,
+            mnemonics
+                .chain(directives)
+                .chain(RESERVED)
+                .filter(|name| !name.is_empty()),
+        )
+    } else {
+        None
+    };
+    match similar_name {
+        Some(similar_name) => {
+            diagnostic.with_help(&format!("a similar name exists: '{similar_name}'"))
+        }
+        None => diagnostic,
+    }
 }
 
+/// Split each line into code and comment, semicolons in quotes are part of the code
 fn clean_up(source: Vec<&str>) -> Vec<(usize, String, String)> {
     source
         .iter()
         .map(|line| {
-            line.split_once(';')
-                .map(|(source, comment)| (source.to_string(), comment.to_string()))
+            comment_start(line)
+                .map(|idx| (line[..idx].to_string(), line[idx + 1..].to_string()))
                 .unwrap_or((line.to_string(), String::new()))
         })
         .enumerate()
@@ -38,57 +184,101 @@ fn clean_up(source: Vec<&str>) -> Vec<(usize, String, String)> {
         .collect()
 }
 
-fn parse_line(i: usize, line: &str) -> Result<Line, String> {
+/// Index of the first semicolon outside of quotes
+fn comment_start(line: &str) -> Option<usize> {
+    unquoted(line)
+        .into_iter()
+        .find(|(_, chr)| *chr == ';')
+        .map(|(idx, _)| idx)
+}
+
+/// Characters of `text` (and their byte index) that aren't inside a string or char literal
+pub fn unquoted(text: &str) -> Vec<(usize, char)> {
+    let mut output = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, chr) in text.char_indices() {
+        match (quote, chr) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(open), chr) if chr == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(chr),
+            (None, chr) => output.push((idx, chr)),
+        }
+    }
+    output
+}
+
+fn parse_line(
+    i: usize,
+    line: &str,
+    addr: usize,
+    labels: &Labels,
+    dir: &Path,
+) -> Result<Line, String> {
     let line = line.trim();
+    let (word, params) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if let Some(directive) = Directive::find(word) {
+        return directive.parse(i, params, addr, labels, dir);
+    }
     if line.chars().count() < 3 {
-        return Err(format!("Line {i} is invalid"));
+        return Err(format!("Line {i}) Unknown mnemonic '{line}'"));
     }
     let (op, params) = line.split_at(3);
-    match op.to_lowercase().trim() {
-        "dat" => Line::data(i, params),
-        "clr" => Ok(Line::no_params(i, ClearDisplay, [0x00, 0xE0])),
-        "ret" => Ok(Line::no_params(i, Return, [0x00, 0xEE])),
-        "jmp" => Line::nnn(i, Jump, 0x10, params),
-        "cal" => Line::nnn(i, Call, 0x20, params),
-        "ske" => Line::xnn_xy(i, (SkipIfEqualNum, 0x30), (SkipIfEqualReg, 0x50, 0), params),
-        "skn" => Line::xnn_xy(
+    let op = op.to_lowercase();
+    let op = op.trim();
+    if op == "dat" {
+        return Line::data(i, params);
+    }
+    let defs = by_mnemonic(op);
+    let with_kind = |kind: OperandKind| defs.iter().find(|def| def.operands == kind);
+    if let (Some(num_def), Some(reg_def)) = (with_kind(RegByte), with_kind(RegReg)) {
+        return Line::xnn_xy(
+            i,
+            |x, nn| num_def.instruction(Operands::reg_byte(x, nn)),
+            |x, y| reg_def.instruction(Operands::reg_reg(x, y)),
+            params,
+            labels,
+        );
+    }
+    let def = match defs.first() {
+        None => return Err(format!("Line {i}) Unknown mnemonic '{op}'")),
+        Some(def) => def,
+    };
+    match def.operands {
+        Empty => Ok(Line::no_params(i, def.instruction(Operands::default()))),
+        Address => Line::nnn(
+            i,
+            |nnn| def.instruction(Operands::address(nnn)),
+            params,
+            labels,
+        ),
+        Reg => Line::x(i, |x| def.instruction(Operands::reg(x)), params, labels),
+        RegByte => Line::xnn(
+            i,
+            |x, nn| def.instruction(Operands::reg_byte(x, nn)),
+            params,
+            labels,
+        ),
+        RegReg => Line::xy(
             i,
-            (SkipIfNotEqualNum, 0x40),
-            (SkipIfNotEqualReg, 0x90, 0),
+            |x, y| def.instruction(Operands::reg_reg(x, y)),
             params,
+            labels,
+        ),
+        RegRegNibble => Line::xyn(
+            i,
+            |x, y, n| def.instruction(Operands::reg_reg_nibble(x, y, n)),
+            params,
+            labels,
         ),
-        "set" => Line::xnn_xy(i, (SetRegFromNum, 0x60), (SetRegFromReg, 0x80, 0), params),
-        "add" => Line::xnn_xy(i, (AddNumToReg, 0x70), (AddReg, 0x80, 4), params),
-        "or" => Line::xy(i, BitwiseOr, 0x80, 1, params),
-        "and" => Line::xy(i, BitwiseAnd, 0x80, 2, params),
-        "xor" => Line::xy(i, BitwiseXor, 0x80, 3, params),
-        "sub" => Line::xy(i, SubRightReg, 0x80, 5, params),
-        "shr" => Line::xy(i, ShiftRight, 0x80, 6, params),
-        "sbr" => Line::xy(i, SubLeftReg, 0x80, 7, params),
-        "shl" => Line::xy(i, ShiftLeft, 0x80, 0xE, params),
-        "sti" => Line::nnn(i, SetMemReg, 0xA0, params),
-        "jpo" => Line::nnn(i, JumpOffset, 0xB0, params),
-        "rnd" => Line::xnn(i, SetRegRand, 0xC0, params),
-        "drw" => Line::xyn(i, DrawSprite, 0xD0, params),
-        "skp" => Line::x(i, SkipIfKeyPressed, 0xE0, 0x9E, params),
-        "skr" => Line::x(i, SkipIfKeyNotPressed, 0xE0, 0xA1, params),
-        "rdt" => Line::x(i, SetRegFromTimer, 0xF0, 0x07, params),
-        "key" => Line::x(i, WaitForKey, 0xF0, 0x0A, params),
-        "sdt" => Line::x(i, SetDelayTimer, 0xF0, 0x15, params),
-        "sst" => Line::x(i, SetSoundTimer, 0xF0, 0x18, params),
-        "adi" => Line::x(i, AddMemReg, 0xF0, 0x1E, params),
-        "chr" => Line::x(i, SetMemRegToDigitSprite, 0xF0, 0x29, params),
-        "asc" => Line::x(i, SetMemRegToAsciiSprite, 0xF0, 0x30, params),
-        "bcd" => Line::x(i, StoreBcd, 0xF0, 0x33, params),
-        "str" => Line::x(i, StoreRegs, 0xF0, 0x55, params),
-        "ldr" => Line::x(i, LoadRegs, 0xF0, 0x65, params),
-        _ => Err(format!("Line {i}) mnemonic {op} is unknown")),
     }
 }
 
 impl Line {
-    pub fn no_params(i: usize, opcode: OpCodes, bytes: [u8; 2]) -> Self {
-        Line::new_code(i, opcode, bytes)
+    pub fn no_params(i: usize, instruction: Instruction) -> Self {
+        Line::new_code(i, instruction)
     }
 
     pub fn data(i: usize, params: &str) -> std::result::Result<Line, String> {
@@ -102,7 +292,7 @@ impl Line {
             .skip(start.unwrap() + 1)
             .take(end.unwrap() - start.unwrap() - 1)
             .collect();
-        if byte_str.len() % 2 != 0 {
+        if !byte_str.len().is_multiple_of(2) {
             return Err(format!("Line {i}) Data format is invalid (length)"));
         }
         let mut bytes = vec![];
@@ -119,47 +309,49 @@ impl Line {
         Ok(Line::new_data(i, bytes))
     }
 
-    pub fn x(i: usize, opcode: OpCodes, first: u8, last: u8, params: &str) -> Result<Self, String> {
-        let vx = parse_reg(params, 1).map_err(|err| format!("Line {i}) {err}"))?;
+    pub fn x(
+        i: usize,
+        instruction: impl Fn(u8) -> Instruction,
+        params: &str,
+        labels: &Labels,
+    ) -> Result<Self, String> {
+        let vx = register(params, 1, labels).map_err(|err| format!("Line {i}) {err}"))?;
 
-        let bytes = [first | vx, last];
-        Ok(Line::no_params(i, opcode, bytes))
+        Ok(Line::no_params(i, instruction(vx)))
     }
 
     pub fn xy(
         i: usize,
-        opcode: OpCodes,
-        first: u8,
-        last: u8,
+        instruction: impl Fn(u8, u8) -> Instruction,
         params: &str,
+        labels: &Labels,
     ) -> Result<Self, String> {
         let (vx, vy) = params
             .split_once(',')
             .ok_or(format!("Line {i}) Two registers required"))?;
-        let vx = parse_reg(vx, 1).map_err(|err| format!("Line {i}) {err}"))?;
-        let vy = parse_reg(vy, 2).map_err(|err| format!("Line {i}) {err}"))?;
+        let vx = register(vx, 1, labels).map_err(|err| format!("Line {i}) {err}"))?;
+        let vy = register(vy, 2, labels).map_err(|err| format!("Line {i}) {err}"))?;
 
-        let bytes = [first | vx, last | vy];
-        Ok(Line::no_params(i, opcode, bytes))
+        Ok(Line::no_params(i, instruction(vx, vy)))
     }
 
-    pub fn nnn(i: usize, opcode: OpCodes, first: u8, addr_param: &str) -> Result<Self, String> {
-        let addr_param = addr_param.trim();
-        if addr_param.chars().count() > 3 {
-            return Err(format!("Line {i}) Address param is too long"));
-        }
-        match u16::from_str_radix(addr_param, 16) {
-            Ok(addr) => {
-                let addr = addr.to_be_bytes();
-                let bytes = [first | addr[0].second_nibble(), addr[1]];
-                Ok(Line::no_params(i, opcode, bytes))
-            }
-            Err(err) => Err(format!("Line {i}) Unable to parse address {err}")),
-        }
+    pub fn nnn(
+        i: usize,
+        instruction: impl Fn(u16) -> Instruction,
+        addr_param: &str,
+        labels: &Labels,
+    ) -> Result<Self, String> {
+        check_literal(i, addr_param, labels, "Address", 3)?;
+        let addr = parse_num(i, addr_param, labels, "Address", 0, MAX_ADDRESS as i64)?;
+        Ok(Line::no_params(i, instruction(addr as u16)))
     }
 
-    pub fn xyn(i: usize, opcode: OpCodes, first: u8, params: &str) -> Result<Line, String> {
-        let params = params.to_lowercase();
+    pub fn xyn(
+        i: usize,
+        instruction: impl Fn(u8, u8, u8) -> Instruction,
+        params: &str,
+        labels: &Labels,
+    ) -> Result<Line, String> {
         let params = params
             .split(',')
             .map(|str| str.trim())
@@ -167,73 +359,91 @@ impl Line {
         if params.len() != 3 {
             return Err(format!("Line {i}) Three params required"));
         }
-        let x = parse_reg(params[0], 1)?;
-        let y = parse_reg(params[1], 2)?;
-        match u8::from_str_radix(params[2], 16) {
-            Ok(num) => {
-                if num > 15 {
-                    return Err(format!("Line {i}) Number param {:02X} is too high, max is 15", num));
-                }
-                let bytes = [first | x, y | num];
-                Ok(Line::no_params(i, opcode, bytes))
-            }
-            Err(err) => Err(format!("Line {i}) Unable to parse number {err}")),
-        }
+        let x = register(params[0], 1, labels).map_err(|err| format!("Line {i}) {err}"))?;
+        let y = register(params[1], 2, labels).map_err(|err| format!("Line {i}) {err}"))?;
+        check_literal(i, params[2], labels, "Number", 1)?;
+        let n = parse_num(i, params[2], labels, "Number", 0, 15)?;
+        Ok(Line::no_params(i, instruction(x, y, n as u8)))
     }
 
-    pub fn xnn(i: usize, opcode: OpCodes, first: u8, params: &str) -> Result<Line, String> {
-        let params = params.to_lowercase();
+    pub fn xnn(
+        i: usize,
+        instruction: impl Fn(u8, u8) -> Instruction,
+        params: &str,
+        labels: &Labels,
+    ) -> Result<Line, String> {
         let (x, nn) = params
             .split_once(',')
             .ok_or(format!("Line {i}) Two params required"))?;
-        let x = parse_reg(x, 1)?;
-        let nn = nn.trim();
-        if nn.chars().count() > 2 {
-            return Err(format!("Line {i}) Number param is too long"));
-        }
-        match u8::from_str_radix(nn, 16) {
-            Ok(num) => {
-                let bytes = [first | x, num];
-                Ok(Line::no_params(i, opcode, bytes))
-            }
-            Err(err) => Err(format!("Line {i}) Unable to parse number {err}")),
-        }
+        let x = register(x, 1, labels).map_err(|err| format!("Line {i}) {err}"))?;
+        check_literal(i, nn, labels, "Number", 2)?;
+        let nn = parse_num(i, nn, labels, "Number", -128, 255)?;
+        Ok(Line::no_params(i, instruction(x, nn as u8)))
     }
 
     pub fn xnn_xy(
         i: usize,
-        (xnn_opcode, xnn_first): (OpCodes, u8),
-        (xy_opcode, xy_first, xy_last): (OpCodes, u8, u8),
+        xnn_instruction: impl Fn(u8, u8) -> Instruction,
+        xy_instruction: impl Fn(u8, u8) -> Instruction,
         params: &str,
+        labels: &Labels,
     ) -> Result<Line, String> {
-        let params = params.to_lowercase();
         let (x, nn_y) = params
             .split_once(',')
             .ok_or(format!("Line {i}) Two params required"))?;
-        let x = parse_reg(x, 1)?;
-        let nn_y = nn_y.trim();
-        match nn_y.contains('v') {
-            true => {
-                let y = parse_reg(nn_y, 2)?;
-                let bytes = [xy_first | x, xy_last | y];
-                Ok(Line::no_params(i, xy_opcode, bytes))
-            }
-            false => {
-                if nn_y.chars().count() > 2 {
-                    return Err(format!("Line {i}) Number param is too long"));
-                }
-                match u8::from_str_radix(nn_y, 16) {
-                    Ok(num) => {
-                        let bytes = [xnn_first | x, num];
-                        Ok(Line::no_params(i, xnn_opcode, bytes))
-                    }
-                    Err(err) => Err(format!("Line {i}) Unable to parse number {err}")),
-                }
+        let x = register(x, 1, labels).map_err(|err| format!("Line {i}) {err}"))?;
+        match register(nn_y, 2, labels) {
+            Ok(y) => Ok(Line::no_params(i, xy_instruction(x, y))),
+            Err(_) => {
+                check_literal(i, nn_y, labels, "Number", 2)?;
+                let nn = parse_num(i, nn_y, labels, "Number", -128, 255)?;
+                Ok(Line::no_params(i, xnn_instruction(x, nn as u8)))
             }
         }
     }
 }
 
+/// Evaluate `param` and check it's between `min` and `max`
+fn parse_num(
+    i: usize,
+    param: &str,
+    labels: &Labels,
+    name: &str,
+    min: i64,
+    max: i64,
+) -> Result<i64, String> {
+    let value = eval(param, labels).map_err(|err| format!("Line {i}) {err}"))?;
+    if value < min || value > max {
+        return Err(format!(
+            "Line {i}) {name} {value} is out of range ({min} to {max})"
+        ));
+    }
+    Ok(value)
+}
+
+/// Error if `param` is a plain hex number with more than `digits` digits, or a register
+fn check_literal(
+    i: usize,
+    param: &str,
+    labels: &Labels,
+    name: &str,
+    digits: usize,
+) -> Result<(), String> {
+    let param = param.trim();
+    if labels.hex(param).is_some() && param.chars().count() > digits {
+        return Err(format!("Line {i}) {name} param is too long"));
+    }
+    if parse_reg(param, 1).is_ok() {
+        if let Err(err) = u16::from_str_radix(param, 16) {
+            return Err(format!(
+                "Line {i}) Unable to parse {} {err}",
+                name.to_lowercase()
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn parse_reg(reg: &str, which: usize) -> Result<u8, String> {
     let reg = reg.trim().to_lowercase();
     if reg.chars().count() != 2 {
@@ -253,32 +463,51 @@ fn parse_reg(reg: &str, which: usize) -> Result<u8, String> {
 
 #[cfg(test)]
 mod test {
-    use crate::parser::{clean_up, parse, parse_line, parse_reg};
+    use crate::parser::labels::Labels;
+    use crate::parser::{clean_up, parse, parse_line, parse_octo, parse_reg, Settings};
     use crate::program::Line;
-    use ec8_common::OpCodes::*;
+    use clap_common::Define;
+    use ec8_common::debug_info::{DataInfo, RegionKind};
+    use ec8_common::definitions::{Operands, DEFINITIONS};
+    use ec8_common::instruction::Instruction::*;
+    use ec8_common::symbols::Symbols;
+    use std::path::Path;
+
+    /// `line: message` for each error
+    fn errors(source: Vec<&str>) -> Vec<String> {
+        parse(source, Path::new("test.eca"), &Settings::default())
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|diagnostic| {
+                let line = diagnostic.span.map(|span| span.line).unwrap_or_default();
+                format!("{line}: {}", diagnostic.message)
+            })
+            .collect()
+    }
 
     #[test]
     fn check_parse() {
         let source = vec!["CLR", "RET", "JMP 123", "ADD V0, ve"];
-        let program = parse(source).unwrap();
-        assert_eq!(program.describe(), "00E0 Clear the display \n00EE Return from subroutine \n1123 Jump to 123 \n80E4 Set V0 to V0 + VE \n".to_string());
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(program.describe(&Symbols::default()), "00E0 Clear the display \n00EE Return from subroutine \n1123 Jump to 123 \n80E4 Set V0 to V0 + VE \n".to_string());
         assert_eq!(
             program.into_bytes(),
             vec![0x00, 0xE0, 0x00, 0xEE, 0x11, 0x23, 0x80, 0xE4]
         );
 
         let source = vec![";test", "CLR", "RET;no ret", "JMP 123", "ADD V0, ve"];
-        let program = parse(source).unwrap();
-        assert_eq!(program.describe(), ";test\n00E0 Clear the display \n00EE Return from subroutine ;no ret\n1123 Jump to 123 \n80E4 Set V0 to V0 + VE \n".to_string());
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(program.describe(&Symbols::default()), ";test\n00E0 Clear the display \n00EE Return from subroutine ;no ret\n1123 Jump to 123 \n80E4 Set V0 to V0 + VE \n".to_string());
         assert_eq!(
             program.into_bytes(),
             vec![0x00, 0xE0, 0x00, 0xEE, 0x11, 0x23, 0x80, 0xE4]
         );
 
         let source = vec!["DAT [3411FAFA]", "CLR"];
-        let program = parse(source).unwrap();
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
         assert_eq!(
-            program.describe(),
+            program.describe(&Symbols::default()),
             "DATA 3411FAFA\n00E0 Clear the display \n".to_string()
         );
         assert_eq!(
@@ -287,15 +516,308 @@ mod test {
         );
     }
 
+    #[test]
+    fn check_labels() {
+        let source = vec![
+            "start: jmp end",
+            "loop:",
+            "add v0, 01",
+            "jmp loop",
+            "end: sti data",
+            "data: dat [FF]",
+        ];
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(
+            program.into_bytes(),
+            vec![0x12, 0x06, 0x70, 0x01, 0x12, 0x02, 0xA2, 0x08, 0xFF]
+        );
+
+        let source = vec!["clr", "jmp mising", "missing:"];
+        let err = parse(source, Path::new("test.eca"), &Settings::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error: Undefined label 'mising'
+ --> test.eca:2:5
+  |
+2 | jmp mising
+  |     ^^^^^^
+  = help: a similar name exists: 'missing'
+
+Unable to assemble due to 1 error"
+        );
+        assert_eq!(
+            errors(vec!["a: clr", "a: ret", "jmp g", "jpm a", "set v0, h"]),
+            vec![
+                "2: Duplicate label 'a', first defined on line 1",
+                "3: Undefined label 'g'",
+                "4: Unknown mnemonic 'jpm'",
+                "5: Undefined label 'h'"
+            ]
+        );
+        assert_eq!(
+            errors(vec!["a: clr", "a: fill size", "jmp g"]),
+            vec![
+                "2: Duplicate label 'a', first defined on line 1",
+                "2: Undefined label 'size'",
+                "3: Undefined label 'g'"
+            ]
+        );
+        assert_eq!(
+            errors(vec!["a: clr", "jmp g", "jpm a", "set v0, h"]),
+            vec![
+                "2: Undefined label 'g'",
+                "3: Unknown mnemonic 'jpm'",
+                "4: Undefined label 'h'"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_layout() {
+        let source = vec![
+            "jmp start",
+            "org 0x204",
+            "start: sti text",
+            "align 4",
+            "text: string \"a;b\"",
+        ];
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(
+            program.describe(&Symbols::default()),
+            "1204 Jump to 204 \nORG 204\nA208 Set I to 208 \nORG 208\nDATA 613B62\n"
+        );
+        assert_eq!(
+            program.into_bytes(),
+            vec![0x12, 0x04, 0, 0, 0xA2, 0x08, 0, 0, 0x61, 0x3B, 0x62]
+        );
+
+        assert_eq!(
+            errors(vec!["clr", "clr", "org 0x202", "db 1"]),
+            vec!["4: 0x202 to 0x202 overlaps line 2 (0x202 to 0x203)"]
+        );
+        assert_eq!(
+            errors(vec!["org 0xFFF", "clr"]),
+            vec!["2: 0xFFF to 0x1000 is past the end of memory"]
+        );
+        assert_eq!(
+            errors(vec!["org later", "later: clr"]),
+            vec!["1: Undefined label 'later'"]
+        );
+        let err = parse(vec!["fil 4"], Path::new("test.eca"), &Settings::default()).unwrap_err();
+        assert_eq!(
+            err.0[0].help,
+            Some(String::from("a similar name exists: 'fill'"))
+        );
+    }
+
+    #[test]
+    fn check_listing() {
+        let source = vec![
+            "macro wait reg",
+            "  @loop: rdt reg",
+            "  ske reg, 0",
+            "  jmp @loop",
+            "endm",
+            "start: set v0, 1E ;delay",
+            "sdt v0",
+            "wait v0",
+            "sti text",
+            "jmp start",
+            "org 0x210",
+            "",
+            "text: string \"Hello\" ;greeting",
+        ];
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(
+            program.listing(),
+            "LINE  ADDR  BYTES        SOURCE                          EXPLANATION
+1                        macro wait reg
+2                          @loop: rdt reg
+3                          ske reg, 0
+4                          jmp @loop
+5                        endm
+6     200   60 1E        start: set v0, 1E ;delay        Set V0 to 1E
+7     202   F0 15        sdt v0                          Set delay timer to V0
+8                        wait v0
+2+    204   F0 07          __wait_1_loop: rdt v0         Set V0 to delay timer
+3+    206   30 00          ske v0, 0                     Skip if V0 == 00
+4+    208   12 04          jmp __wait_1_loop             Jump to __wait_1_loop (0x204)
+9     20A   A2 10        sti text                        Set I to data `text` +0
+10    20C   12 00        jmp start                       Jump to start (0x200)
+11    210                org 0x210                       Move to 0x210
+12
+13    210   48 65 6C 6C  text: string \"Hello\" ;greeting  5 bytes
+      214   6F
+
+SYMBOL         ADDR  DEFINED  REFERENCES
+start          200   6        10
+__wait_1_loop  204   2+       4+
+text           210   13       9
+"
+        );
+        assert!(program
+            .describe(&Symbols::default())
+            .ends_with("DATA 48656C6C6F;greeting\n"));
+    }
+
+    #[test]
+    fn check_debug_info() {
+        let source = vec![
+            "macro wait reg",
+            "  @loop: rdt reg",
+            "  jmp @loop",
+            "endm",
+            "start: wait v0",
+            "  sti text",
+            "text:",
+            "  db \"Hi\"",
+        ];
+        let info = parse(source, Path::new("test.eca"), &Settings::default())
+            .unwrap()
+            .debug_info();
+        assert_eq!(info.files, vec!["test.eca"]);
+        assert_eq!(info.location(0x200), Some(String::from("test.eca:2:10")));
+        assert_eq!(info.location(0x203), Some(String::from("test.eca:3:3")));
+        assert_eq!(info.location(0x204), Some(String::from("test.eca:6:3")));
+        assert_eq!(info.location(0x207), Some(String::from("test.eca:8:3")));
+        assert_eq!(info.location(0x208), None);
+        assert_eq!(
+            info.data,
+            vec![DataInfo {
+                name: String::from("text"),
+                addr: 0x206,
+                len: 2
+            }]
+        );
+        let kinds: Vec<(u16, u16, RegionKind)> = info
+            .regions
+            .iter()
+            .map(|region| (region.start, region.end, region.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (0x200, 0x206, RegionKind::Code),
+                (0x206, 0x208, RegionKind::Data)
+            ]
+        );
+        assert_eq!(info.labels.len(), 3);
+    }
+
+    #[test]
+    fn check_include() {
+        let dir = std::env::temp_dir().join("ec8-assembler-parse-include");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lib.eca"), "sub: ret\nbad: jmp missing").unwrap();
+        std::fs::write(dir.join("data.bin"), [0xAB]).unwrap();
+        let main = dir.join("main.eca");
+        let source = vec!["cal sub", "include \"lib.eca\"", "incbin \"data.bin\""];
+        let err = parse(source, &main, &Settings::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "error: Undefined label 'missing'
+ --> {}:2:10
+  |
+2 | bad: jmp missing
+  |          ^^^^^^^
+  = note: included from {}:2
+
+Unable to assemble due to 1 error",
+                dir.join("lib.eca").display(),
+                main.display()
+            )
+        );
+
+        std::fs::write(dir.join("lib.eca"), "sub: ret").unwrap();
+        let source = vec!["cal sub", "include \"lib.eca\"", "incbin \"data.bin\""];
+        let program = parse(source, &main, &Settings::default()).unwrap();
+        assert_eq!(program.into_bytes(), vec![0x22, 0x02, 0x00, 0xEE, 0xAB]);
+    }
+
+    #[test]
+    fn check_macros() {
+        let source = vec![
+            "macro wait reg, time",
+            "  set reg, time",
+            "  sdt reg",
+            "  @loop: rdt reg",
+            "  ske reg, 0",
+            "  jmp @loop",
+            "endm",
+            "macro pause",
+            "  wait v1, 3C",
+            "endm",
+            "start: wait time=1E, reg=v0",
+            "pause",
+        ];
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(
+            program.describe(&Symbols::default()),
+            ";wait time=1E, reg=v0
+601E Set V0 to 1E 
+F015 Set delay timer to V0 
+F007 Set V0 to delay timer 
+3000 Skip if V0 == 00 
+1204 Jump to 204 
+;pause
+;wait v1, 3C
+613C Set V1 to 3C 
+F115 Set delay timer to V1 
+F107 Set V1 to delay timer 
+3100 Skip if V1 == 00 
+120E Jump to 20E 
+"
+        );
+
+        let source = vec!["macro m a", "  set v0, a", "endm", "m FF + 1"];
+        let err = parse(source, Path::new("test.eca"), &Settings::default()).unwrap_err();
+        assert_eq!(err.0[0].message, "Number 256 is out of range (-128 to 255)");
+        assert_eq!(err.0[0].notes, vec!["expanded from test.eca:4"]);
+        let source = vec!["macro m", "  m", "endm", "m"];
+        assert_eq!(
+            errors(source),
+            vec!["2: Macros are nested more than 16 deep"]
+        );
+        assert_eq!(
+            errors(vec!["macro m", "clr"]),
+            vec!["1: Macro 'm' is missing endm"]
+        );
+    }
+
+    #[test]
+    fn check_every_definition() {
+        let operands = Operands::reg_reg_nibble(0xA, 3, 7);
+        for def in DEFINITIONS.iter().filter(|def| !def.mnemonic.is_empty()) {
+            let instruction = def.instruction(Operands {
+                nn: 0x9C,
+                nnn: 0x4F2,
+                ..operands
+            });
+            let source = instruction.to_asm().unwrap();
+            assert_eq!(
+                parse_line(0, &source, 0x200, &Labels::default(), Path::new(".")),
+                Ok(Line::no_params(0, instruction)),
+                "{source}"
+            );
+        }
+    }
+
     #[test]
     fn check_parse_line() {
         assert_eq!(
-            parse_line(4, "JMP 41A"),
-            Ok(Line::no_params(4, Jump, [0x14, 0x1A]))
+            parse_line(4, "JMP 0x41A", 0x200, &Labels::default(), Path::new(".")),
+            Ok(Line::no_params(4, Jump { nnn: 0x41A }))
         );
         assert_eq!(
-            parse_line(6, " XOR  V3 , va"),
-            Ok(Line::no_params(6, BitwiseXor, [0x83, 0xA3]))
+            parse_line(
+                6,
+                " XOR  V3 , va",
+                0x200,
+                &Labels::default(),
+                Path::new(".")
+            ),
+            Ok(Line::no_params(6, BitwiseXor { x: 3, y: 0xA }))
         );
     }
 
@@ -311,7 +833,7 @@ mod test {
             ]
         );
 
-        let prog = "\nASM 1\n;whole line\nASM 2;note";
+        let prog = "\nASM 1\n;whole line\nASM 2;note\nDB ';', \"\\\";\" ;end";
         let cleaned = clean_up(prog.lines().collect());
         assert_eq!(
             cleaned,
@@ -319,6 +841,7 @@ mod test {
                 (1, "ASM 1".to_string(), "".to_string()),
                 (2, "".to_string(), "whole line".to_string()),
                 (3, "ASM 2".to_string(), "note".to_string()),
+                (4, "DB ';', \"\\\";\"".to_string(), "end".to_string()),
             ]
         );
     }
@@ -334,87 +857,131 @@ mod test {
     #[test]
     fn check_no_params() {
         assert_eq!(
-            Line::no_params(5, ClearDisplay, [45, 67]),
-            Line::new_code(5, ClearDisplay, [45, 67])
+            Line::no_params(5, ClearDisplay),
+            Line::new_code(5, ClearDisplay)
         )
     }
 
     #[test]
     fn check_x() {
+        let labels = Labels::default();
         assert_eq!(
-            Line::x(0, AddMemReg, 0xF0, 0x1E, "v4"),
-            Ok(Line::new_code(0, AddMemReg, [0xF4, 0x1E]))
+            Line::x(0, |x| AddMemReg { x }, "v4", &labels),
+            Ok(Line::new_code(0, AddMemReg { x: 4 }))
         );
         assert_eq!(
-            Line::x(4, AddMemReg, 0xF0, 0x1E, ""),
+            Line::x(4, |x| AddMemReg { x }, "", &labels),
             Err("Line 4) Reg 1 is invalid".to_string())
         );
         assert_eq!(
-            Line::x(8, AddMemReg, 0xF0, 0x1E, "12"),
+            Line::x(8, |x| AddMemReg { x }, "12", &labels),
             Err("Line 8) Reg 1 is invalid".to_string())
         );
         assert_eq!(
-            Line::x(99, AddMemReg, 0xF0, 0x1E, "vp"),
+            Line::x(99, |x| AddMemReg { x }, "vp", &labels),
             Err("Line 99) Unable to parse reg 1: invalid digit found in string".to_string())
         );
     }
 
     #[test]
     fn check_xnn() {
+        let labels = Labels::default();
         assert_eq!(
-            Line::xnn(53, SkipIfEqualNum, 0x30, "VB , 18"),
-            Ok(Line::new_code(53, SkipIfEqualNum, [0x3B, 0x18]))
+            Line::xnn(53, |x, nn| SkipIfEqualNum { x, nn }, "VB , 18", &labels),
+            Ok(Line::new_code(53, SkipIfEqualNum { x: 0xB, nn: 0x18 }))
         );
         assert_eq!(
-            Line::xnn(54, SkipIfNotEqualNum, 0x40, "VB , 181"),
+            Line::xnn(54, |x, nn| SkipIfNotEqualNum { x, nn }, "VB , 181", &labels),
             Err("Line 54) Number param is too long".to_string())
         );
     }
 
+    #[test]
+    fn check_xnn_expressions() {
+        let labels = Labels::default();
+        assert_eq!(
+            Line::xnn(
+                54,
+                |x, nn| SkipIfNotEqualNum { x, nn },
+                "VB , FF + 1",
+                &labels
+            ),
+            Err("Line 54) Number 256 is out of range (-128 to 255)".to_string())
+        );
+        assert_eq!(
+            Line::xnn(55, |x, nn| SetRegFromNum { x, nn }, "v0, -1", &labels),
+            Ok(Line::new_code(55, SetRegFromNum { x: 0, nn: 0xFF }))
+        );
+        assert_eq!(
+            Line::xnn(56, |x, nn| SetRegFromNum { x, nn }, "v0, 0x18", &labels),
+            Ok(Line::new_code(56, SetRegFromNum { x: 0, nn: 0x18 }))
+        );
+    }
+
     #[test]
     fn check_nnn() {
+        let labels = Labels::default();
         assert_eq!(
-            Line::nnn(3, Jump, 0x10, "1ad"),
-            Ok(Line::new_code(3, Jump, [0x11, 0xAD]))
+            Line::nnn(3, |nnn| Jump { nnn }, "1ad", &labels),
+            Ok(Line::new_code(3, Jump { nnn: 0x1AD }))
         );
         assert_eq!(
-            Line::nnn(6, Call, 0x20, "v1"),
+            Line::nnn(6, |nnn| Call { nnn }, "v1", &labels),
             Err("Line 6) Unable to parse address invalid digit found in string".to_string())
         );
         assert_eq!(
-            Line::nnn(7, SetMemReg, 0xA0, "1234"),
+            Line::nnn(7, |nnn| SetMemReg { nnn }, "1234", &labels),
             Err("Line 7) Address param is too long".to_string())
         );
     }
 
+    #[test]
+    fn check_nnn_expressions() {
+        let labels = Labels::default();
+        assert_eq!(
+            Line::nnn(3, |nnn| Jump { nnn }, "0x1ad", &labels),
+            Ok(Line::new_code(3, Jump { nnn: 0x1AD }))
+        );
+        assert_eq!(
+            Line::nnn(6, |nnn| Call { nnn }, "1g", &labels),
+            Err("Line 6) Invalid number '1g'".to_string())
+        );
+        assert_eq!(
+            Line::nnn(7, |nnn| SetMemReg { nnn }, "0x1234", &labels),
+            Err("Line 7) Address 4660 is out of range (0 to 4095)".to_string())
+        );
+    }
+
     #[test]
     fn check_xy() {
+        let labels = Labels::default();
         assert_eq!(
-            Line::xy(1, AddReg, 0x80, 0x04, "v4, va"),
-            Ok(Line::new_code(1, AddReg, [0x84, 0xA4]))
+            Line::xy(1, |x, y| AddReg { x, y }, "v4, va", &labels),
+            Ok(Line::new_code(1, AddReg { x: 4, y: 0xA }))
         );
         assert_eq!(
-            Line::xy(10, BitwiseOr, 0x80, 0x01, "v1"),
+            Line::xy(10, |x, y| BitwiseOr { x, y }, "v1", &labels),
             Err("Line 10) Two registers required".to_string())
         );
         assert_eq!(
-            Line::xy(12, BitwiseAnd, 0x80, 0x02, ", v2"),
+            Line::xy(12, |x, y| BitwiseAnd { x, y }, ", v2", &labels),
             Err("Line 12) Reg 1 is invalid".to_string())
         );
         assert_eq!(
-            Line::xy(9, BitwiseXor, 0x80, 0x03, "1, 3"),
+            Line::xy(9, |x, y| BitwiseXor { x, y }, "1, 3", &labels),
             Err("Line 9) Reg 1 is invalid".to_string())
         );
     }
 
     #[test]
     fn check_xyn() {
+        let labels = Labels::default();
         assert_eq!(
-            Line::xyn(1, DrawSprite, 0xD0, "v4, va, 6"),
-            Ok(Line::new_code(1, DrawSprite, [0xD4, 0xA6]))
+            Line::xyn(1, |x, y, n| DrawSprite { x, y, n }, "v4, va, 6", &labels),
+            Ok(Line::new_code(1, DrawSprite { x: 4, y: 0xA, n: 6 }))
         );
         assert_eq!(
-            Line::xyn(2, DrawSprite, 0xD0, "v1"),
+            Line::xyn(2, |x, y, n| DrawSprite { x, y, n }, "v1", &labels),
             Err("Line 2) Three params required".to_string())
         );
     }
@@ -432,4 +999,149 @@ mod test {
         assert!(parse_reg("Vp", 1).is_err());
         assert!(parse_reg("V", 1).is_err());
     }
+
+    #[test]
+    fn check_octo() {
+        let source = vec![
+            "# counter",
+            ": main",
+            "  v0 := 0",
+            "  loop",
+            "    v0 += 1",
+            "    if v0 == 10 then v0 := 0",
+            "  again",
+        ];
+        let program = parse_octo(source, Path::new("test.8o"), &Settings::default()).unwrap();
+        assert_eq!(
+            program.into_bytes(),
+            vec![0x60, 0x00, 0x70, 0x01, 0x40, 0x0A, 0x60, 0x00, 0x12, 0x02]
+        );
+
+        let source = vec![": main", "  v1 := 0 v0 := 300"];
+        let err = parse_octo(source, Path::new("test.8o"), &Settings::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error: Number 300 is out of range (-128 to 255)
+ --> test.8o:2:11
+  |
+2 |   v1 := 0 v0 := 300
+  |           ^^^^^^^^^
+
+Unable to assemble due to 1 error"
+        );
+    }
+
+    #[test]
+    fn check_definitions() {
+        let source = vec![
+            "equ SIZE 5",
+            "alias x v1",
+            "macro draw x",
+            "  drw x, v2, SIZE",
+            "endm",
+            "start: set x, SIZE * 2",
+            "add x, x",
+            "sti font + SIZE",
+            "draw v0",
+            "font: db SIZE",
+        ];
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(
+            program.diagnostics[0].message,
+            "Param 'x' of macro 'draw' shadows the alias defined on line 2"
+        );
+        assert_eq!(
+            program.into_bytes(),
+            vec![0x61, 0x0A, 0x81, 0x14, 0xA2, 0x0D, 0xD0, 0x25, 0x05]
+        );
+        assert_eq!(
+            errors(vec!["alias x v1", "set v0, x + 1", "jmp X"]),
+            vec![
+                "2: Register alias 'x' can't be used as a number",
+                "3: Undefined label 'X'"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_defines() {
+        let settings = Settings {
+            defines: vec![Define {
+                name: String::from("SIZE"),
+                value: 3,
+            }],
+            decimal: false,
+        };
+        let source = vec![
+            "equ SIZE 5",
+            "if SIZE < 4",
+            "  set v0, SIZE",
+            "else",
+            "  set v0, 0",
+            "endif",
+        ];
+        let program = parse(source.clone(), Path::new("test.eca"), &settings).unwrap();
+        assert_eq!(program.into_bytes(), vec![0x60, 0x03]);
+        let program = parse(source, Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(program.into_bytes(), vec![0x60, 0x00]);
+
+        let source = vec!["alias SIZE v1", "SIZE: clr"];
+        let messages: Vec<String> = parse(source, Path::new("test.eca"), &settings)
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Alias 'SIZE' has the same name as a command line define",
+                "Label 'SIZE' has the same name as a command line define",
+            ]
+        );
+        assert_eq!(
+            errors(vec!["macro endif", "endm"]),
+            vec![
+                "1: Macro name 'endif' is already used by an instruction",
+                "2: endm without macro"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_numbers() {
+        let source = vec!["set v0, 10", "add v0, FF", "jmp cafe", "cafe: set v1, 0x10"];
+        let program = parse(source.clone(), Path::new("test.eca"), &Settings::default()).unwrap();
+        assert_eq!(
+            program
+                .lints
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["Number '10' is read as hex (0x10), write 0x10 or use --decimal"]
+        );
+        assert_eq!(
+            program.into_bytes(),
+            vec![0x60, 0x10, 0x70, 0xFF, 0x12, 0x06, 0x61, 0x10]
+        );
+
+        let settings = Settings {
+            defines: vec![],
+            decimal: true,
+        };
+        let program = parse(vec!["set v0, 10"], Path::new("test.eca"), &settings).unwrap();
+        assert!(program.lints.is_empty());
+        assert_eq!(program.into_bytes(), vec![0x60, 0x0A]);
+        assert_eq!(
+            Line::nnn(7, |nnn| SetMemReg { nnn }, "1234", &Labels::new(&settings)),
+            Ok(Line::new_code(7, SetMemReg { nnn: 1234 }))
+        );
+        assert_eq!(
+            parse(source, Path::new("test.eca"), &settings)
+                .unwrap_err()
+                .0[0]
+                .message,
+            "Undefined label 'FF'"
+        );
+    }
 }
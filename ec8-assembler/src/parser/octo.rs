@@ -0,0 +1,798 @@
+//! Octo
+//!
+//! Translates [Octo](https://github.com/JohnEarnest/Octo) source into assembly, so it can be
+//! assembled, checked and described the same as any other program
+//!
+//! ```text
+//! : main
+//!     v0 := 0
+//!     loop
+//!         i := hex v0
+//!         sprite v1 v2 5
+//!         v0 += 1
+//!         if v0 == 16 then v0 := 0
+//!     again
+//! ```
+//!
+//! Only the CHIP-8 instructions are supported, along with `:const`, `:alias`, `:org`, `:byte`,
+//! `:call`, `:unpack`, `if .. then`, `if .. begin .. else .. end` and `loop .. while .. again`
+//!
+//! As with Octo, the program starts at `main`, if there's anything before it a jump to it is added
+//! at the start. Names can contain dashes, these are replaced with underscores
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::labels::is_label;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+/// Instructions that aren't part of CHIP-8
+const UNSUPPORTED: [&str; 13] = [
+    "hires",
+    "lores",
+    "scroll-down",
+    "scroll-up",
+    "scroll-left",
+    "scroll-right",
+    "exit",
+    "saveflags",
+    "loadflags",
+    "plane",
+    "audio",
+    "pitch",
+    "native",
+];
+
+/// A line of assembly and the part of the Octo source it was made from
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Statement {
+    /// Line number in the Octo source, starting at 0
+    pub line: usize,
+    /// Columns of the Octo code on the line
+    pub columns: Range<usize>,
+    pub code: String,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    columns: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Block {
+    If { id: usize, has_else: bool },
+    Loop { id: usize },
+}
+
+type Error = (Token, String);
+
+struct Translator {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// First token of the current statement
+    start: usize,
+    output: Vec<Statement>,
+    consts: HashMap<String, String>,
+    aliases: HashMap<String, String>,
+    /// Open blocks and the token that opened them
+    blocks: Vec<(Token, Block)>,
+    ids: usize,
+}
+
+/// Translate Octo `source`, which was read from `path`, into assembly
+///
+/// # Errors
+/// Every unsupported or invalid statement, and unclosed blocks
+pub fn translate(source: &[&str], path: &Path) -> Result<Vec<Statement>, Vec<Diagnostic>> {
+    let mut translator = Translator {
+        tokens: tokenize(source),
+        pos: 0,
+        start: 0,
+        output: vec![],
+        consts: HashMap::new(),
+        aliases: HashMap::new(),
+        blocks: vec![],
+        ids: 0,
+    };
+    let mut errors = translator.run();
+    if errors.is_empty() && !translator.defines("main") {
+        return Err(vec![Diagnostic::error("Program is missing a 'main' label")]);
+    }
+    let starts_with_main = translator
+        .output
+        .first()
+        .is_some_and(|statement| statement.code == "main:");
+    if !starts_with_main {
+        translator.output.insert(
+            0,
+            Statement {
+                line: translator.tokens[0].line,
+                columns: 0..0,
+                code: String::from("jmp main"),
+            },
+        );
+    }
+    for (token, block) in translator.blocks {
+        let message = match block {
+            Block::If { .. } => "'begin' without 'end'",
+            Block::Loop { .. } => "'loop' without 'again'",
+        };
+        errors.push((token, message.to_string()));
+    }
+    if errors.is_empty() {
+        Ok(translator.output)
+    } else {
+        Err(errors
+            .into_iter()
+            .map(|(token, message)| {
+                Diagnostic::error(&message).at(Span {
+                    file: path.display().to_string(),
+                    line: token.line + 1,
+                    columns: token.columns,
+                    text: source[token.line].to_string(),
+                })
+            })
+            .collect())
+    }
+}
+
+/// Split `source` into whitespace separated tokens, skipping comments (`#` to the end of the line)
+fn tokenize(source: &[&str]) -> Vec<Token> {
+    let mut tokens = vec![];
+    for (line, text) in source.iter().enumerate() {
+        let code = &text[..text.find('#').unwrap_or(text.len())];
+        let mut start = None;
+        for (idx, chr) in code.char_indices().chain([(code.len(), ' ')]) {
+            match (start, chr.is_whitespace()) {
+                (None, false) => start = Some(idx),
+                (Some(begin), true) => {
+                    tokens.push(Token {
+                        text: code[begin..idx].to_string(),
+                        line,
+                        columns: begin..idx,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    tokens
+}
+
+impl Translator {
+    /// Translate every statement, returning the errors
+    fn run(&mut self) -> Vec<Error> {
+        let mut errors = vec![];
+        while self.pos < self.tokens.len() {
+            if let Err(err) = self.statement() {
+                errors.push(err);
+            }
+        }
+        errors
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.tokens
+            .windows(2)
+            .any(|pair| pair[0].text == ":" && pair[1].text == name)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, Error> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => {
+                let last = self.tokens[self.pos - 1].clone();
+                Err((last, format!("Expected {expected}")))
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| token.text.as_str())
+    }
+
+    /// Add `code`, for the tokens of the current statement
+    fn emit(&mut self, code: String) {
+        let start = &self.tokens[self.start];
+        let last = &self.tokens[self.pos.max(self.start + 1) - 1];
+        let end = if last.line == start.line {
+            last.columns.end
+        } else {
+            start.columns.end
+        };
+        self.output.push(Statement {
+            line: start.line,
+            columns: start.columns.start..end,
+            code,
+        });
+    }
+
+    fn statement(&mut self) -> Result<(), Error> {
+        self.start = self.pos;
+        let token = self.next("statement")?;
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                self.emit(format!("{name}:"));
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.consts.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.register()?;
+                self.aliases.insert(name, reg);
+            }
+            ":org" => {
+                let addr = self.value()?;
+                self.emit(format!("org {addr}"));
+            }
+            ":byte" => {
+                let value = self.value()?;
+                self.emit(format!("db {value}"));
+            }
+            ":call" => {
+                let addr = self.value()?;
+                self.emit(format!("cal {addr}"));
+            }
+            ":unpack" => {
+                let nibble = self.value()?;
+                let label = self.value()?;
+                self.emit(format!("set v0, ({nibble} << 4) | hi({label})"));
+                self.emit(format!("set v1, lo({label})"));
+            }
+            ":breakpoint" => {
+                self.next("breakpoint name")?;
+            }
+            "clear" => self.emit(String::from("clr")),
+            "return" | ";" => self.emit(String::from("ret")),
+            "jump" => {
+                let addr = self.value()?;
+                self.emit(format!("jmp {addr}"));
+            }
+            "jump0" => {
+                let addr = self.value()?;
+                self.emit(format!("jpo {addr}"));
+            }
+            "bcd" | "save" | "load" => {
+                let reg = self.register()?;
+                let op = match token.text.as_str() {
+                    "bcd" => "bcd",
+                    "save" => "str",
+                    _ => "ldr",
+                };
+                self.emit(format!("{op} {reg}"));
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let height = self.value()?;
+                self.emit(format!("drw {x}, {y}, {height}"));
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let reg = self.register()?;
+                let op = if token.text == "delay" { "sdt" } else { "sst" };
+                self.emit(format!("{op} {reg}"));
+            }
+            "i" => self.index()?,
+            "if" => self.condition(token)?,
+            "else" => match self.blocks.last_mut() {
+                Some((_, Block::If { id, has_else })) if !*has_else => {
+                    *has_else = true;
+                    let id = *id;
+                    self.emit(format!("jmp __if_{id}_end"));
+                    self.emit(format!("__if_{id}_else:"));
+                }
+                _ => return Err((token, String::from("'else' without 'begin'"))),
+            },
+            "end" => match self.blocks.pop() {
+                Some((_, Block::If { id, has_else })) => {
+                    let label = if has_else { "end" } else { "else" };
+                    self.emit(format!("__if_{id}_{label}:"));
+                }
+                Some(block) => {
+                    self.blocks.push(block);
+                    return Err((token, String::from("'end' without 'begin'")));
+                }
+                None => return Err((token, String::from("'end' without 'begin'"))),
+            },
+            "loop" => {
+                self.ids += 1;
+                self.blocks.push((token, Block::Loop { id: self.ids }));
+                self.emit(format!("__loop_{}_start:", self.ids));
+            }
+            "while" => {
+                let Some(id) = self.innermost_loop() else {
+                    return Err((token, String::from("'while' without 'loop'")));
+                };
+                let comparison = self.comparison()?;
+                self.skip(&comparison, true)?;
+                self.emit(format!("jmp __loop_{id}_end"));
+            }
+            "again" => match self.blocks.pop() {
+                Some((_, Block::Loop { id })) => {
+                    self.emit(format!("jmp __loop_{id}_start"));
+                    self.emit(format!("__loop_{id}_end:"));
+                }
+                Some(block) => {
+                    self.blocks.push(block);
+                    return Err((token, String::from("'again' without 'loop'")));
+                }
+                None => return Err((token, String::from("'again' without 'loop'"))),
+            },
+            text if is_number(text) => {
+                let mut bytes = vec![text.to_string()];
+                while let Some(next) = self.tokens.get(self.pos) {
+                    if next.line != token.line || !is_number(&next.text) {
+                        break;
+                    }
+                    bytes.push(next.text.clone());
+                    self.pos += 1;
+                }
+                self.emit(format!("db {}", bytes.join(", ")));
+            }
+            text if self.is_register(text) => self.assign(token)?,
+            text if text.starts_with(':') || UNSUPPORTED.contains(&text) => {
+                let message = format!("'{text}' isn't supported");
+                return Err((token, message));
+            }
+            _ => {
+                let name = self.mangle(&token)?;
+                self.emit(format!("cal {name}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), Error> {
+        let token = self.next(&format!("'{text}'"))?;
+        if token.text == text {
+            Ok(())
+        } else {
+            Err((
+                token.clone(),
+                format!("Expected '{text}' found '{}'", token.text),
+            ))
+        }
+    }
+
+    fn innermost_loop(&self) -> Option<usize> {
+        self.blocks.iter().rev().find_map(|(_, block)| match block {
+            Block::Loop { id } => Some(*id),
+            Block::If { .. } => None,
+        })
+    }
+
+    /// `i := addr`, `i := hex vx` or `i += vx`
+    fn index(&mut self) -> Result<(), Error> {
+        let op = self.next("':=' or '+='")?;
+        match op.text.as_str() {
+            ":=" if self.peek() == Some("hex") => {
+                self.pos += 1;
+                let reg = self.register()?;
+                self.emit(format!("chr {reg}"));
+            }
+            ":=" if matches!(self.peek(), Some("bighex" | "long")) => {
+                let token = self.next("value")?;
+                let message = format!("'{}' isn't supported", token.text);
+                return Err((token, message));
+            }
+            ":=" => {
+                let addr = self.value()?;
+                self.emit(format!("sti {addr}"));
+            }
+            "+=" => {
+                let reg = self.register()?;
+                self.emit(format!("adi {reg}"));
+            }
+            _ => return Err((op.clone(), format!("Unknown operator '{}' for i", op.text))),
+        }
+        Ok(())
+    }
+
+    /// `vx := ..`, `vx += ..` and the other register operators
+    fn assign(&mut self, target: Token) -> Result<(), Error> {
+        let x = self.resolve_register(&target)?;
+        let op = self.next("operator")?;
+        let source = self.next("value")?;
+        let source_reg = self.resolve_register(&source).ok();
+        let code = match (op.text.as_str(), source_reg) {
+            (":=", Some(y)) => format!("set {x}, {y}"),
+            (":=", None) => match source.text.as_str() {
+                "random" => format!("rnd {x}, {}", self.value()?),
+                "key" => format!("key {x}"),
+                "delay" => format!("rdt {x}"),
+                _ => format!("set {x}, {}", self.resolve_value(&source)?),
+            },
+            ("+=", Some(y)) => format!("add {x}, {y}"),
+            ("+=", None) => format!("add {x}, {}", self.resolve_value(&source)?),
+            ("-=", Some(y)) => format!("sub {x}, {y}"),
+            ("-=", None) => format!("add {x}, -({}) & 255", self.resolve_value(&source)?),
+            ("=-", Some(y)) => format!("sbr {x}, {y}"),
+            ("|=", Some(y)) => format!("or {x}, {y}"),
+            ("&=", Some(y)) => format!("and {x}, {y}"),
+            ("^=", Some(y)) => format!("xor {x}, {y}"),
+            (">>=", Some(y)) => format!("shr {x}, {y}"),
+            ("<<=", Some(y)) => format!("shl {x}, {y}"),
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                let message = format!("Expected a register after '{}'", op.text);
+                return Err((source, message));
+            }
+            _ => return Err((op.clone(), format!("Unknown operator '{}'", op.text))),
+        };
+        self.emit(code);
+        Ok(())
+    }
+
+    /// `if cond then statement` or `if cond begin`
+    fn condition(&mut self, token: Token) -> Result<(), Error> {
+        let comparison = self.comparison()?;
+        let keyword = self.next("'then' or 'begin'")?;
+        match keyword.text.as_str() {
+            "then" => {
+                self.skip(&comparison, false)?;
+                let len = self.output.len();
+                let outer = self.start;
+                self.statement()?;
+                self.start = outer;
+                let added = self.output[len..]
+                    .iter()
+                    .filter(|statement| !statement.code.ends_with(':'))
+                    .count();
+                if added != 1 {
+                    let message = String::from("Only one instruction can follow 'then'");
+                    return Err((token, message));
+                }
+            }
+            "begin" => {
+                self.skip(&comparison, true)?;
+                self.ids += 1;
+                self.emit(format!("jmp __if_{}_else", self.ids));
+                let block = Block::If {
+                    id: self.ids,
+                    has_else: false,
+                };
+                self.blocks.push((token, block));
+            }
+            _ => {
+                let message = format!("Expected 'then' or 'begin' found '{}'", keyword.text);
+                return Err((keyword, message));
+            }
+        }
+        Ok(())
+    }
+
+    /// Register, operator and value (except for `key` and `-key`) of a condition, such as
+    /// `v0 == 3` or `v1 -key`
+    fn comparison(&mut self) -> Result<(String, Token, Option<String>), Error> {
+        let x = self.register()?;
+        let op = self.next("comparison")?;
+        let value = match op.text.as_str() {
+            "key" | "-key" => None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => {
+                let value = self.next("value")?;
+                match self.resolve_register(&value) {
+                    Ok(reg) => Some(reg),
+                    Err(_) => Some(self.resolve_value(&value)?),
+                }
+            }
+            _ => return Err((op.clone(), format!("Unknown comparison '{}'", op.text))),
+        };
+        Ok((x, op, value))
+    }
+
+    /// Add the instructions that skip the next instruction unless `comparison` is true (or false
+    /// if `negated`), comparisons other than `==` and `!=` use VF
+    fn skip(
+        &mut self,
+        (x, token, value): &(String, Token, Option<String>),
+        negated: bool,
+    ) -> Result<(), Error> {
+        let op = match negated {
+            true => negate(&token.text),
+            false => token.text.as_str(),
+        };
+        let y = value.clone().unwrap_or_default();
+        let codes = match op {
+            "key" => vec![format!("skr {x}")],
+            "-key" => vec![format!("skp {x}")],
+            "==" => vec![format!("skn {x}, {y}")],
+            "!=" => vec![format!("ske {x}, {y}")],
+            _ => compare(x, negate(op), &y).ok_or_else(|| {
+                let message = format!("VF can't be compared with '{}'", token.text);
+                (token.clone(), message)
+            })?,
+        };
+        for code in codes {
+            self.emit(code);
+        }
+        Ok(())
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        self.aliases.contains_key(&text.replace('-', "_")) || register(text).is_some()
+    }
+
+    fn register(&mut self) -> Result<String, Error> {
+        let token = self.next("register")?;
+        self.resolve_register(&token)
+    }
+
+    fn resolve_register(&self, token: &Token) -> Result<String, Error> {
+        self.aliases
+            .get(&token.text.replace('-', "_"))
+            .cloned()
+            .or_else(|| register(&token.text))
+            .ok_or_else(|| {
+                let message = format!("Expected a register found '{}'", token.text);
+                (token.clone(), message)
+            })
+    }
+
+    /// Number, constant or label
+    fn value(&mut self) -> Result<String, Error> {
+        let token = self.next("value")?;
+        self.resolve_value(&token)
+    }
+
+    fn resolve_value(&self, token: &Token) -> Result<String, Error> {
+        if is_number(&token.text) {
+            return Ok(token.text.clone());
+        }
+        if self.is_register(&token.text) {
+            let message = format!("Expected a number or label found '{}'", token.text);
+            return Err((token.clone(), message));
+        }
+        let name = self.mangle(token)?;
+        Ok(self.consts.get(&name).cloned().unwrap_or(name))
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let token = self.next("name")?;
+        self.mangle(&token)
+    }
+
+    /// `token` as a label name, with dashes replaced
+    fn mangle(&self, token: &Token) -> Result<String, Error> {
+        let name = token.text.replace('-', "_");
+        if is_label(&name) {
+            Ok(name)
+        } else {
+            Err((token.clone(), format!("Invalid name '{}'", token.text)))
+        }
+    }
+}
+
+/// `vX` in lowercase, if `text` is a register
+fn register(text: &str) -> Option<String> {
+    let lower = text.to_lowercase();
+    let digit = lower.strip_prefix('v')?;
+    (digit.len() == 1 && digit.chars().all(|chr| chr.is_ascii_hexdigit())).then_some(lower)
+}
+
+fn is_number(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    digits.starts_with(|chr: char| chr.is_ascii_digit())
+}
+
+/// The comparison that's true when `op` is false
+fn negate(op: &str) -> &str {
+    match op {
+        "==" => "!=",
+        "!=" => "==",
+        "key" => "-key",
+        "-key" => "key",
+        "<" => ">=",
+        ">=" => "<",
+        ">" => "<=",
+        "<=" => ">",
+        _ => op,
+    }
+}
+
+/// Instructions that skip the next instruction if `x op y` is true, for `<`, `>`, `<=` and `>=`
+///
+/// 8xy5 writes the difference after the flag, so the flag is lost if the difference goes in VF.
+/// Instead the subtraction is done in a register and undone, leaving the result in VF
+/// - `sub vx, vy` then `add vx, vy` sets VF to the carry from undoing it, 1 if `vx < vy`
+/// - `sub vx, vf` then `add vx, n` (which doesn't change VF) leaves VF as 1 if `vx > n`
+///
+/// None if either side is VF, as it holds the result
+fn compare(x: &str, op: &str, y: &str) -> Option<Vec<String>> {
+    if x == "vf" || y == "vf" {
+        return None;
+    }
+    //skipping always, or never
+    let always = |result: bool| match result {
+        true => vec![format!("ske {x}, {x}")],
+        false => vec![],
+    };
+    let (mut codes, flag) = if register(y).is_some() {
+        if x == y {
+            return Some(always(op.ends_with('=')));
+        }
+        let (lhs, rhs) = match op {
+            "<" | ">=" => (x, y),
+            _ => (y, x),
+        };
+        let codes = vec![format!("sub {lhs}, {rhs}"), format!("add {lhs}, {rhs}")];
+        (codes, if op.len() == 1 { 1 } else { 0 })
+    } else {
+        //x < n is x <= n - 1 and x >= n is x > n - 1, when n isn't 0
+        let n = match op {
+            "<" | ">=" if is_zero(y) => return Some(always(op == ">=")),
+            "<" | ">=" => format!("{y} - 1"),
+            _ => y.to_string(),
+        };
+        let codes = vec![
+            format!("set vf, {n}"),
+            format!("sub {x}, vf"),
+            format!("add {x}, {n}"),
+        ];
+        (codes, if op.starts_with('>') { 1 } else { 0 })
+    };
+    codes.push(format!("ske vf, {flag}"));
+    Some(codes)
+}
+
+/// If `text` is a number that's 0, such as `0` or `0x00`
+fn is_zero(text: &str) -> bool {
+    is_number(text) && text.chars().all(|chr| "0xXbB-".contains(chr))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::octo::translate;
+    use std::path::Path;
+
+    fn codes(source: &[&str]) -> Vec<String> {
+        translate(source, Path::new("test.8o"))
+            .unwrap()
+            .into_iter()
+            .map(|statement| statement.code)
+            .collect()
+    }
+
+    fn errors(source: &[&str]) -> Vec<String> {
+        translate(source, Path::new("test.8o"))
+            .unwrap_err()
+            .into_iter()
+            .map(|diagnostic| {
+                let line = diagnostic.span.map(|span| span.line).unwrap_or_default();
+                format!("{line}: {}", diagnostic.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_translate() {
+        let source = [
+            ":alias x v1 :const SIZE 5",
+            ": main",
+            "  i := hex x # digit",
+            "  sprite v0 x SIZE",
+            "  x += 1 x -= 2 x =- v2 delay := x",
+            "  if x != 3 then draw-it",
+            "  jump main",
+            ": draw-it ;",
+            ": data 1 2 3",
+        ];
+        assert_eq!(
+            codes(&source),
+            vec![
+                "main:",
+                "chr v1",
+                "drw v0, v1, 5",
+                "add v1, 1",
+                "add v1, -(2) & 255",
+                "sbr v1, v2",
+                "sdt v1",
+                "ske v1, 3",
+                "cal draw_it",
+                "jmp main",
+                "draw_it:",
+                "ret",
+                "data:",
+                "db 1, 2, 3",
+            ]
+        );
+        let statements = translate(&source, Path::new("test.8o")).unwrap();
+        assert_eq!(statements[4].line, 4);
+        assert_eq!(statements[4].columns, 9..15);
+    }
+
+    #[test]
+    fn check_blocks() {
+        let source = [
+            ": sub return",
+            ": main",
+            "loop",
+            "  if v0 key begin v1 := 1 else v1 := 2 end",
+            "  while v0 < 4",
+            "again",
+        ];
+        assert_eq!(
+            codes(&source),
+            vec![
+                "jmp main",
+                "sub:",
+                "ret",
+                "main:",
+                "__loop_1_start:",
+                "skp v0",
+                "jmp __if_2_else",
+                "set v1, 1",
+                "jmp __if_2_end",
+                "__if_2_else:",
+                "set v1, 2",
+                "__if_2_end:",
+                "set vf, 4 - 1",
+                "sub v0, vf",
+                "add v0, 4 - 1",
+                "ske vf, 0",
+                "jmp __loop_1_end",
+                "jmp __loop_1_start",
+                "__loop_1_end:",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_comparisons() {
+        let source = [
+            ": main",
+            "  if v0 > v1 then v2 := 1",
+            "  if v0 <= 5 then v2 := 2",
+            "  if v0 < 0 then v2 := 3",
+            "  if v0 >= v0 then v2 := 4",
+        ];
+        assert_eq!(
+            codes(&source),
+            vec![
+                "main:",
+                "sub v1, v0",
+                "add v1, v0",
+                "ske vf, 0",
+                "set v2, 1",
+                "set vf, 5",
+                "sub v0, vf",
+                "add v0, 5",
+                "ske vf, 1",
+                "set v2, 2",
+                "ske v0, v0",
+                "set v2, 3",
+                "set v2, 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        assert_eq!(
+            errors(&[": main", "hires", "v0 *= 2", "else", "if v0 == 1 begin"]),
+            vec![
+                "2: 'hires' isn't supported",
+                "3: Unknown operator '*='",
+                "4: 'else' without 'begin'",
+                "5: 'begin' without 'end'",
+            ]
+        );
+        assert_eq!(
+            errors(&[": main", "if vf < v1 then clear"]),
+            vec!["2: VF can't be compared with '<'"]
+        );
+        assert_eq!(
+            errors(&["clear"]),
+            vec!["0: Program is missing a 'main' label"]
+        );
+    }
+}
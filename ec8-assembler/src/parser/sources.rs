@@ -0,0 +1,782 @@
+//! Sources
+//!
+//! Expands `include "path"` by inserting the lines of the file, paths are relative to the file
+//! containing the include, and expands [macros](crate::parser::macros)
+//!
+//! Lines between `if`, `elif`, `else` and `endif` are removed unless their condition is true (not
+//! zero), conditions can only use constants defined before them and command line defines. Blocks
+//! must end in the same file or macro they start in
+//!
+//! Lines are numbered in the order they appear after expanding, [Sources::diagnostic] converts
+//! these back to the file and line number along with the chain of includes and macro calls
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::directives::parse_string;
+use crate::parser::expr::eval;
+use crate::parser::labels::{definition, split_label, Labels};
+use crate::parser::macros::{Macro, MAX_DEPTH};
+use crate::parser::octo::Statement;
+use crate::parser::{comment_start, unquoted, Settings};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    path: PathBuf,
+    /// Index of the include line, None for the main file
+    included_at: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Origin {
+    /// Index in [Sources::files]
+    file: usize,
+    /// Line number in the file
+    line: usize,
+    /// Index of the macro call, if this line is from a macro
+    expanded_at: Option<usize>,
+    /// Index of the line as it's written in the file, differs for lines from macros
+    written_at: usize,
+    /// Columns of the code in the line as written, for lines translated from another syntax
+    columns: Option<Range<usize>>,
+}
+
+/// An `if` block
+#[derive(Debug, Clone)]
+struct Condition {
+    /// Index of the `if` line
+    line: usize,
+    /// If the lines in the current branch are used
+    active: bool,
+    /// If any branch has been used
+    taken: bool,
+    has_else: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sources {
+    pub lines: Vec<String>,
+    /// Text of each line before includes and macro calls were removed
+    pub original: Vec<String>,
+    origins: Vec<Origin>,
+    files: Vec<SourceFile>,
+    /// Macros by name, with the index of the `macro` line
+    macros: HashMap<String, (usize, Macro)>,
+    /// Macro being defined and the index of the `macro` line
+    recording: Option<(usize, Macro)>,
+    expansions: usize,
+    errors: Vec<String>,
+    settings: Settings,
+    /// Constants defined so far, for conditions
+    constants: Labels,
+    /// Open `if` blocks
+    conditions: Vec<Condition>,
+}
+
+impl Sources {
+    /// Expand every include, macro and condition in `source`, which was read from `path`
+    ///
+    /// # Errors
+    /// Every included file that can't be read or includes itself (directly or indirectly), every
+    /// invalid macro and every invalid condition
+    pub fn load(
+        source: Vec<&str>,
+        path: &Path,
+        settings: &Settings,
+    ) -> Result<Sources, Vec<Diagnostic>> {
+        let mut sources = Sources::new(path, settings);
+        let source: Vec<String> = source.into_iter().map(String::from).collect();
+        sources.add(0, source);
+        if let Some((i, mac)) = &sources.recording {
+            let err = format!("Line {i}) Macro '{}' is missing endm", mac.name);
+            sources.errors.push(err);
+        }
+        if sources.errors.is_empty() {
+            Ok(sources)
+        } else {
+            Err(sources.diagnostics(&sources.errors))
+        }
+    }
+
+    /// Lines translated from `source` (Octo), which was read from `path`
+    ///
+    /// Each line of `source` is kept without any code, followed by the lines made from it
+    pub fn translated(
+        source: Vec<&str>,
+        path: &Path,
+        settings: &Settings,
+        statements: Vec<Statement>,
+    ) -> Sources {
+        let mut sources = Sources::new(path, settings);
+        let mut written = vec![];
+        let mut add_until = |sources: &mut Sources, line: usize| {
+            while written.len() <= line {
+                let origin = Origin {
+                    file: 0,
+                    line: written.len(),
+                    expanded_at: None,
+                    written_at: sources.lines.len(),
+                    columns: None,
+                };
+                written.push(sources.push(origin, source[written.len()]));
+            }
+            written[line]
+        };
+        for statement in statements {
+            let written_at = add_until(&mut sources, statement.line);
+            let i = sources.push(
+                Origin {
+                    file: 0,
+                    line: statement.line,
+                    expanded_at: None,
+                    written_at,
+                    columns: Some(statement.columns),
+                },
+                &statement.code,
+            );
+            sources.lines[i] = statement.code;
+        }
+        if !source.is_empty() {
+            add_until(&mut sources, source.len() - 1);
+        }
+        sources
+    }
+
+    fn new(path: &Path, settings: &Settings) -> Sources {
+        Sources {
+            lines: vec![],
+            original: vec![],
+            origins: vec![],
+            files: vec![SourceFile {
+                path: path.to_path_buf(),
+                included_at: None,
+            }],
+            macros: HashMap::new(),
+            recording: None,
+            expansions: 0,
+            errors: vec![],
+            settings: settings.clone(),
+            constants: Labels::new(settings),
+            conditions: vec![],
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    fn add(&mut self, file: usize, source: Vec<String>) {
+        let open = self.conditions.len();
+        for (line, text) in source.into_iter().enumerate() {
+            let i = self.push(
+                Origin {
+                    file,
+                    line,
+                    expanded_at: None,
+                    written_at: self.lines.len(),
+                    columns: None,
+                },
+                &text,
+            );
+            if let Err(err) = self.process(i, text, 0) {
+                self.errors.push(err);
+            }
+        }
+        self.close_conditions(open);
+    }
+
+    /// Error for each `if` opened after the first `open` that's still open
+    fn close_conditions(&mut self, open: usize) {
+        for condition in self.conditions.split_off(open) {
+            let line = condition.line;
+            self.errors.push(format!("Line {line}) if without endif"));
+        }
+    }
+
+    /// Add an empty line, returning its index
+    fn push(&mut self, origin: Origin, text: &str) -> usize {
+        self.lines.push(String::new());
+        self.original.push(text.to_string());
+        self.origins.push(origin);
+        self.lines.len() - 1
+    }
+
+    /// Set line `i` to `text`, unless it's part of a macro definition, an include or a macro call
+    fn process(&mut self, i: usize, text: String, depth: usize) -> Result<(), String> {
+        let code = match comment_start(&text) {
+            Some(idx) => text[..idx].trim(),
+            None => text.trim(),
+        };
+        let (word, params) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+        let word = word.to_lowercase();
+        if let Some((_, mac)) = &mut self.recording {
+            match word.as_str() {
+                "endm" => {
+                    let (defined, mac) = self.recording.take().expect("Recording macro");
+                    self.macros.insert(mac.name.clone(), (defined, mac));
+                }
+                "macro" => {
+                    return Err(format!(
+                        "Line {i}) Macros can't be defined inside other macros"
+                    ))
+                }
+                _ => mac.body.push((i, text)),
+            }
+            return Ok(());
+        }
+        match word.as_str() {
+            "if" | "elif" | "else" | "endif" => return self.condition(i, &word, params),
+            _ if !self.is_active() => return Ok(()),
+            _ => {}
+        }
+        match word.as_str() {
+            "macro" => {
+                let mac = Macro::new(params).map_err(|err| format!("Line {i}) {err}"))?;
+                if let Some((defined, _)) = self.macros.get(&mac.name) {
+                    return Err(format!(
+                        "Line {i}) Macro '{}' is already defined on line {defined}",
+                        mac.name
+                    ));
+                }
+                self.recording = Some((i, mac));
+            }
+            "endm" => return Err(format!("Line {i}) endm without macro")),
+            "include" if depth > 0 => return Err(format!("Line {i}) Macros can't include files")),
+            "include" => self.include(i, params)?,
+            _ => match split_label(i, code) {
+                Ok((label, call)) if self.is_macro(call) => {
+                    let mut line = label.map(|label| format!("{label}:")).unwrap_or_default();
+                    line.push_str(&format!(" ;{call}"));
+                    self.lines[i] = line;
+                    self.expand(i, call, depth)?;
+                }
+                _ => {
+                    if definition(code).is_some() {
+                        // errors are reported when the labels are collected
+                        let _ = self.constants.define(i, code);
+                    }
+                    self.lines[i] = text
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// If lines are currently used, i.e. every open `if` is in a true branch
+    fn is_active(&self) -> bool {
+        self.conditions.iter().all(|condition| condition.active)
+    }
+
+    /// Handle an `if`, `elif`, `else` or `endif` on line `i`
+    fn condition(&mut self, i: usize, word: &str, params: &str) -> Result<(), String> {
+        if word == "if" {
+            let result = match self.is_active() {
+                true => self.evaluate(i, params),
+                false => Ok(false),
+            };
+            let active = result == Ok(true);
+            self.conditions.push(Condition {
+                line: i,
+                active,
+                taken: active || result.is_err(),
+                has_else: false,
+            });
+            return result.map(|_| ());
+        }
+        let Some(condition) = self.conditions.pop() else {
+            return Err(format!("Line {i}) {word} without if"));
+        };
+        let outer = self.is_active();
+        let Condition {
+            line,
+            taken,
+            has_else,
+            ..
+        } = condition;
+        let result = match word {
+            _ if has_else && word != "endif" => {
+                self.conditions.push(condition);
+                return Err(format!("Line {i}) {word} after else (if on line {line})"));
+            }
+            "endif" => return Ok(()),
+            "else" => Ok(outer && !taken),
+            _ if outer && !taken => self.evaluate(i, params),
+            _ => Ok(false),
+        };
+        let active = result == Ok(true);
+        self.conditions.push(Condition {
+            line,
+            active,
+            taken: taken || active || result.is_err(),
+            has_else: word == "else",
+        });
+        result.map(|_| ())
+    }
+
+    /// If the condition `params` on line `i` is true (not zero)
+    fn evaluate(&self, i: usize, params: &str) -> Result<bool, String> {
+        if params.trim().is_empty() {
+            return Err(format!("Line {i}) Missing condition"));
+        }
+        eval(params, &self.constants)
+            .map(|value| value != 0)
+            .map_err(|err| format!("Line {i}) {err}"))
+    }
+
+    fn is_macro(&self, code: &str) -> bool {
+        let name = code.split_whitespace().next().unwrap_or_default();
+        self.macros.contains_key(name)
+    }
+
+    /// Add the lines of the macro called on line `i`
+    fn expand(&mut self, i: usize, call: &str, depth: usize) -> Result<(), String> {
+        if depth >= MAX_DEPTH {
+            return Err(format!(
+                "Line {i}) Macros are nested more than {MAX_DEPTH} deep"
+            ));
+        }
+        let (name, args) = call.split_once(char::is_whitespace).unwrap_or((call, ""));
+        self.expansions += 1;
+        let lines = self.macros[name]
+            .1
+            .expand(args, self.expansions)
+            .map_err(|err| format!("Line {i}) {err}"))?;
+        let open = self.conditions.len();
+        for (def, text) in lines {
+            let j = self.push(
+                Origin {
+                    expanded_at: Some(i),
+                    ..self.origins[def].clone()
+                },
+                &text,
+            );
+            if let Err(err) = self.process(j, text, depth + 1) {
+                self.errors.push(err);
+            }
+        }
+        self.close_conditions(open);
+        Ok(())
+    }
+
+    /// Add the lines of the file included on line `i`
+    fn include(&mut self, i: usize, params: &str) -> Result<(), String> {
+        let path = include_path(params).map_err(|err| format!("Line {i}) {err}"))?;
+        let path = self.dir(i).join(path);
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("Line {i}) Unable to read '{}': {err}", path.display()))?;
+        if let Some(cycle) = self.cycle(self.origins[i].file, &path) {
+            return Err(format!("Line {i}) Include cycle: {cycle}"));
+        }
+        self.files.push(SourceFile {
+            path,
+            included_at: Some(i),
+        });
+        let source = text.lines().map(String::from).collect();
+        self.add(self.files.len() - 1, source);
+        Ok(())
+    }
+
+    /// If including `path` from `file` would make a cycle, the files in the cycle
+    fn cycle(&self, file: usize, path: &Path) -> Option<String> {
+        let canonical = |path: &Path| path.canonicalize().unwrap_or(path.to_path_buf());
+        let target = canonical(path);
+        let mut chain = vec![path.display().to_string()];
+        let mut current = Some(file);
+        while let Some(idx) = current {
+            let file = &self.files[idx];
+            chain.push(file.path.display().to_string());
+            if canonical(&file.path) == target {
+                chain.reverse();
+                return Some(chain.join(" -> "));
+            }
+            current = file.included_at.map(|i| self.origins[i].file);
+        }
+        None
+    }
+
+    /// Every macro and the index of its `macro` line
+    pub fn macros(&self) -> impl Iterator<Item = (usize, &Macro)> {
+        self.macros.values().map(|(i, mac)| (*i, mac))
+    }
+
+    /// Directory of the file containing line `i`
+    pub fn dir(&self, i: usize) -> &Path {
+        let file = &self.files[self.origins[i].file];
+        file.path.parent().unwrap_or(Path::new(""))
+    }
+
+    /// `line 4` for the main file, `lib.eca line 4` for included files
+    fn location(&self, i: usize) -> String {
+        match self.origins.get(i) {
+            Some(Origin { file: 0, line, .. }) => format!("line {}", line + 1),
+            Some(Origin { file, line, .. }) => {
+                format!("{} line {}", self.files[*file].path.display(), line + 1)
+            }
+            None => format!("line {}", i + 1),
+        }
+    }
+
+    /// `main.eca:4`, the path and line number of line `i` for any file
+    fn file_line(&self, i: usize) -> String {
+        let origin = &self.origins[i];
+        format!(
+            "{}:{}",
+            self.files[origin.file].path.display(),
+            origin.line + 1
+        )
+    }
+
+    /// `4` for the main file, `lib.eca:4` for included files
+    pub fn short_location(&self, i: usize) -> String {
+        let origin = &self.origins[i];
+        match origin.file {
+            0 => (origin.line + 1).to_string(),
+            file => {
+                let path = &self.files[file].path;
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                format!("{}:{}", name.to_string_lossy(), origin.line + 1)
+            }
+        }
+    }
+
+    /// Path of every file, in the order they were included
+    pub fn files(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .map(|file| file.path.display().to_string())
+            .collect()
+    }
+
+    /// Index in [Sources::files] and line number in the file for line `i`
+    pub fn position(&self, i: usize) -> (usize, usize) {
+        (self.origins[i].file, self.origins[i].line)
+    }
+
+    /// Column of the first character after the label in line `i`, as written in the file
+    pub fn column(&self, i: usize) -> usize {
+        self.code_columns(i).start
+    }
+
+    /// Columns of the code after the label in line `i`, or the whole line if it only has a label
+    fn code_columns(&self, i: usize) -> Range<usize> {
+        if let Some(columns) = &self.origins[i].columns {
+            return columns.clone();
+        }
+        let text = &self.original[self.origins[i].written_at];
+        let code = text[..comment_start(text).unwrap_or(text.len())].trim_end();
+        let start = unquoted(code)
+            .into_iter()
+            .find(|(_, chr)| *chr == ':')
+            .map(|(idx, _)| idx + 1)
+            .unwrap_or(0);
+        let start = start + code[start..].len() - code[start..].trim_start().len();
+        if start == code.len() {
+            code.len() - code.trim_start().len()..code.len()
+        } else {
+            start..code.len()
+        }
+    }
+
+    /// Line `i` as written in its file, with `message` used to find the part to underline
+    ///
+    /// The first quoted name in `message` is underlined if it's in the line, otherwise all of the
+    /// code is
+    pub fn span(&self, i: usize, message: &str) -> Span {
+        let origin = &self.origins[i];
+        let text = &self.original[origin.written_at];
+        let code = self.code_columns(i);
+        let quoted = message
+            .split('\'')
+            .nth(1)
+            .filter(|name| !name.is_empty() && message.matches('\'').count() >= 2);
+        let columns = quoted
+            .and_then(|name| find_word(&text[..code.end], name))
+            .map(|idx| idx..idx + quoted.unwrap_or_default().len())
+            .unwrap_or(code);
+        Span {
+            file: self.files[origin.file].path.display().to_string(),
+            line: origin.line + 1,
+            columns,
+            text: text.clone(),
+        }
+    }
+
+    /// Convert `errors` (`Line 4) ...`) to diagnostics
+    pub fn diagnostics(&self, errors: &[String]) -> Vec<Diagnostic> {
+        errors.iter().map(|err| self.diagnostic(err)).collect()
+    }
+
+    /// Number of macro calls line `i` is nested in
+    pub fn depth(&self, i: usize) -> usize {
+        let mut depth = 0;
+        let mut current = self.origins[i].expanded_at;
+        while let Some(i) = current {
+            depth += 1;
+            current = self.origins[i].expanded_at;
+        }
+        depth
+    }
+
+    /// Convert `err` (`Line 4) ...`) to a diagnostic for the file and line, with notes for the
+    /// chain of includes and macro calls. Other line numbers (`line 4`) are converted to the
+    /// file and line number
+    pub fn diagnostic(&self, err: &str) -> Diagnostic {
+        let Some((num, rest)) = err
+            .strip_prefix("Line ")
+            .and_then(|text| text.split_once(") "))
+            .and_then(|(num, rest)| num.parse::<usize>().ok().map(|num| (num, rest)))
+            .filter(|(num, _)| *num < self.origins.len())
+        else {
+            return Diagnostic::error(err);
+        };
+        let mut diagnostic = Diagnostic::error(&self.replace_lines(rest)).at(self.span(num, rest));
+        let mut current = self.origins.get(num);
+        while let Some(origin) = current {
+            let (kind, i) = match (origin.expanded_at, self.files[origin.file].included_at) {
+                (Some(i), _) => ("expanded", i),
+                (None, Some(i)) => ("included", i),
+                (None, None) => break,
+            };
+            diagnostic
+                .notes
+                .push(format!("{kind} from {}", self.file_line(i)));
+            current = self.origins.get(i);
+        }
+        diagnostic
+    }
+
+    fn replace_lines(&self, text: &str) -> String {
+        let mut output = String::new();
+        let mut rest = text;
+        while let Some(idx) = rest.find("line ") {
+            output.push_str(&rest[..idx]);
+            rest = &rest[idx + 5..];
+            let len = rest.chars().take_while(char::is_ascii_digit).count();
+            match rest[..len].parse::<usize>() {
+                Ok(num) => output.push_str(&self.location(num)),
+                Err(_) => output.push_str("line "),
+            }
+            rest = &rest[len..];
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+/// Byte index of `word` in `text`, where it's not part of a longer name
+fn find_word(text: &str, word: &str) -> Option<usize> {
+    let is_name = |chr: char| chr.is_ascii_alphanumeric() || chr == '_';
+    text.match_indices(word).map(|(idx, _)| idx).find(|idx| {
+        let before = text[..*idx].chars().next_back();
+        let after = text[idx + word.len()..].chars().next();
+        let joined_before = word.starts_with(is_name) && before.is_some_and(is_name);
+        let joined_after = word.ends_with(is_name) && after.is_some_and(is_name);
+        !joined_before && !joined_after
+    })
+}
+
+/// Path from the params of an include
+fn include_path(params: &str) -> Result<String, String> {
+    parse_string(0, params.trim())
+        .map_err(|_| String::from("Expected 'include \"path\"'"))
+        .and_then(|bytes| String::from_utf8(bytes).map_err(|_| String::from("Invalid file name")))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Span;
+    use crate::parser::sources::Sources;
+    use crate::parser::Settings;
+    use clap_common::Define;
+    use std::fs;
+    use std::path::Path;
+
+    fn setup(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("ec8-assembler-{name}"));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        for (file, text) in files {
+            fs::write(dir.join(file), text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn check_include() {
+        let dir = setup(
+            "include",
+            &[
+                ("lib/sprites.eca", "db 1\ninclude \"font.eca\" ;font"),
+                ("lib/font.eca", "db 2\ndb 3 +"),
+            ],
+        );
+        let main = dir.join("main.eca");
+        let sources = Sources::load(
+            vec!["clr", "include \"lib/sprites.eca\"", "ret"],
+            &main,
+            &Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            sources.lines,
+            vec!["clr", "", "db 1", "", "db 2", "db 3 +", "ret"]
+        );
+        assert_eq!(sources.dir(4), dir.join("lib"));
+        assert_eq!(sources.original[1], "include \"lib/sprites.eca\"");
+        assert_eq!(sources.short_location(4), "font.eca:1");
+        assert_eq!(sources.short_location(6), "3");
+        assert_eq!(sources.position(5), (2, 1));
+        assert_eq!(sources.column(1), 0);
+        let diagnostic = sources.diagnostic("Line 6) Invalid");
+        assert_eq!(diagnostic.message, "Invalid");
+        assert_eq!(diagnostic.span.map(|span| span.line), Some(3));
+        assert!(diagnostic.notes.is_empty());
+
+        let font = dir.join("lib/font.eca");
+        let sprites = dir.join("lib/sprites.eca");
+        let diagnostic = sources.diagnostic("Line 5) Missing value, see line 0");
+        assert_eq!(diagnostic.message, "Missing value, see line 1");
+        assert_eq!(
+            diagnostic.span,
+            Some(Span {
+                file: font.display().to_string(),
+                line: 2,
+                columns: 0..6,
+                text: String::from("db 3 +"),
+            })
+        );
+        assert_eq!(
+            diagnostic.notes,
+            vec![
+                format!("included from {}:2", sprites.display()),
+                format!("included from {}:2", main.display())
+            ]
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        let dir = setup(
+            "cycle",
+            &[
+                ("a.eca", "include \"b.eca\""),
+                ("b.eca", "clr\ninclude \"a.eca\""),
+            ],
+        );
+        let main = dir.join("main.eca");
+        let err =
+            Sources::load(vec!["include \"a.eca\""], &main, &Settings::default()).unwrap_err();
+        let a = dir.join("a.eca").display().to_string();
+        let b = dir.join("b.eca").display().to_string();
+        assert_eq!(err[0].message, format!("Include cycle: {a} -> {b} -> {a}"));
+        assert_eq!(
+            err[0]
+                .span
+                .as_ref()
+                .map(|span| (span.file.as_str(), span.line)),
+            Some((b.as_str(), 2))
+        );
+        assert_eq!(
+            err[0].notes,
+            vec![
+                format!("included from {a}:1"),
+                format!("included from {}:1", main.display())
+            ]
+        );
+
+        let err = Sources::load(
+            vec!["clr", "include \"missing.eca\""],
+            &main,
+            &Settings::default(),
+        )
+        .unwrap_err();
+        assert!(err[0].message.starts_with("Unable to read"), "{err:?}");
+        let source = vec!["include missing.eca", "endm", "macro add", "clr"];
+        let messages: Vec<String> = Sources::load(source, &main, &Settings::default())
+            .unwrap_err()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Expected 'include \"path\"'",
+                "endm without macro",
+                "Macro name 'add' is already used by an instruction"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_conditions() {
+        let main = Path::new("test.eca");
+        let source = vec![
+            "equ SPEED 2",
+            "equ DEBUG 0",
+            "if SPEED > 1",
+            "db 1",
+            "if DEBUG",
+            "db 2",
+            "else",
+            "db 3",
+            "endif",
+            "elif SPEED",
+            "db 4",
+            "else",
+            "db 5",
+            "endif",
+        ];
+        let code = |defines: &[Define]| {
+            let settings = Settings {
+                defines: defines.to_vec(),
+                decimal: false,
+            };
+            let sources = Sources::load(source.clone(), main, &settings).unwrap();
+            let lines = sources.lines.into_iter();
+            lines
+                .filter(|line| line.starts_with("db"))
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(code(&[]), vec!["db 1", "db 3"]);
+        let speed = Define {
+            name: String::from("SPEED"),
+            value: 1,
+        };
+        assert_eq!(code(std::slice::from_ref(&speed)), vec!["db 4"]);
+        let debug = Define {
+            name: String::from("DEBUG"),
+            value: 1,
+        };
+        assert_eq!(code(&[debug]), vec!["db 1", "db 2"]);
+        assert_eq!(code(&[Define { value: 0, ..speed }]), vec!["db 5"]);
+
+        let source = vec![
+            "if",
+            "endif",
+            "else",
+            "if 1",
+            "else",
+            "elif 1",
+            "endif",
+            "if UNKNOWN",
+            "endif",
+            "if 1",
+        ];
+        let messages: Vec<String> = Sources::load(source, main, &Settings::default())
+            .unwrap_err()
+            .into_iter()
+            .map(|diagnostic| format!("{}: {}", diagnostic.span.unwrap().line, diagnostic.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "1: Missing condition",
+                "3: else without if",
+                "6: elif after else (if on line 4)",
+                "8: Undefined label 'UNKNOWN'",
+                "10: if without endif",
+            ]
+        );
+    }
+}
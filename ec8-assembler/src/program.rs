@@ -1,105 +1,55 @@
+use crate::diagnostic::{Diagnostic, Span};
 use crate::program::Line::*;
-use ec8_common::OpCodes;
-use ec8_common::OpCodes::SysCall;
-use std::collections::HashMap;
+use ec8_common::debug_info::{DebugInfo, LineInfo, RegionKind};
+use ec8_common::instruction::Instruction;
+use ec8_common::symbols::{SymbolKind, Symbols};
+use ec8_common::{MAX_ADDRESS, PROG_START_ADDRESS};
 use std::fmt::{Display, Formatter};
 
+#[derive(Debug)]
 pub struct Program {
     pub lines: Vec<Line>,
+    /// Every line of source, after expanding includes and macros
+    pub source: Vec<SourceLine>,
+    pub labels: Vec<Label>,
+    /// Path of every source file
+    pub files: Vec<String>,
+    /// Warnings found while assembling
+    pub diagnostics: Vec<Diagnostic>,
+    /// Lint warnings found while assembling, reported at the lint level
+    pub lints: Vec<Diagnostic>,
 }
 
-impl Program {
-    pub fn warnings(&self, ignore_ec8: bool) -> Option<String> {
-        let mut output = String::new();
-        if let Some(text) = self.sys_call_warnings() {
-            output.push_str(&text);
-        }
-        if let (false, Some(text)) = (ignore_ec8, self.ec8_warnings()) {
-            if !output.is_empty() {
-                output.push('\n');
-            }
-            output.push_str(&text);
-        }
-        if output.is_empty() {
-            None
-        } else {
-            Some(output)
-        }
-    }
-
-    fn ec8_warnings(&self) -> Option<String> {
-        let mut ec8_calls: HashMap<OpCodes, Vec<usize>> = HashMap::new();
-        for line in &self.lines {
-            if let Code {
-                idx,
-                opcode,
-                bytes: _,
-                comment: _,
-            } = line
-            {
-                if opcode.is_ec8_only() {
-                    ec8_calls.entry(*opcode).or_default().push(*idx);
-                }
-            }
-        }
-        if !ec8_calls.is_empty() {
-            let text = ec8_calls
-                .iter()
-                .map(|(op, lines)| {
-                    format!(
-                        "\n  {:?}: {}",
-                        op,
-                        lines
-                            .iter()
-                            .map(|num| num.to_string())
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                    )
-                })
-                .collect::<Vec<String>>()
-                .join("\n");
-            Some(format!("Contains EC8 only calls:{text}"))
-        } else {
-            None
-        }
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    /// Line number, with the file name for included files (i.e. `lib.eca:4`)
+    pub location: String,
+    /// Index in [Program::files]
+    pub file: usize,
+    pub line: usize,
+    pub column: usize,
+    /// The line as written, for diagnostics
+    pub span: Span,
+    pub text: String,
+    /// Number of macro calls this line is nested in
+    pub depth: usize,
+}
 
-    fn sys_call_warnings(&self) -> Option<String> {
-        let mut sys_calls = vec![];
-        for line in &self.lines {
-            if let Code {
-                idx,
-                opcode,
-                bytes: _,
-                comment: _,
-            } = line
-            {
-                if opcode == &SysCall {
-                    sys_calls.push(idx);
-                }
-            }
-        }
-        if !sys_calls.is_empty() {
-            Some(format!(
-                "Contains system calls (0x0nnn) on lines {}",
-                sys_calls
-                    .iter()
-                    .map(|num| num.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            ))
-        } else {
-            None
-        }
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub name: String,
+    pub addr: u16,
+    /// Index of the line defining the label
+    pub defined: usize,
+    /// Index of each line using the label
+    pub references: Vec<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Line {
     Code {
         idx: usize,
-        opcode: OpCodes,
-        bytes: [u8; 2],
+        instruction: Instruction,
         comment: String,
     },
     Comment {
@@ -111,14 +61,18 @@ pub enum Line {
         bytes: Vec<u8>,
         comment: String,
     },
+    /// Following lines start at `addr`
+    Org {
+        idx: usize,
+        addr: u16,
+    },
 }
 
 impl Line {
-    pub fn new_code(idx: usize, opcode: OpCodes, bytes: [u8; 2]) -> Line {
+    pub fn new_code(idx: usize, instruction: Instruction) -> Line {
         Code {
             idx,
-            opcode,
-            bytes,
+            instruction,
             comment: String::new(),
         }
     }
@@ -131,6 +85,20 @@ impl Line {
         }
     }
 
+    pub fn new_org(idx: usize, addr: u16) -> Line {
+        Org { idx, addr }
+    }
+
+    /// Address after this line, if it starts at `addr`
+    pub fn next_addr(&self, addr: usize) -> usize {
+        match self {
+            Code { .. } => addr + 2,
+            Comment { .. } => addr,
+            Data { bytes, .. } => addr + bytes.len(),
+            Org { addr, .. } => *addr as usize,
+        }
+    }
+
     pub fn new_comment(idx: usize, comment: &str) -> Line {
         Comment {
             idx,
@@ -142,24 +110,22 @@ impl Line {
         match self {
             Code {
                 idx,
-                opcode,
-                bytes,
+                instruction,
                 comment: _,
             } => Code {
                 idx,
-                opcode,
-                bytes,
+                instruction,
                 comment: text.to_string(),
             },
-            Comment { .. } => self,
+            Comment { .. } | Org { .. } => self,
             Data {
                 idx,
                 bytes,
-                comment,
+                comment: _,
             } => Data {
                 idx,
                 bytes,
-                comment,
+                comment: text.to_string(),
             },
         }
     }
@@ -167,20 +133,26 @@ impl Line {
 
 impl Display for Line {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.describe(&Symbols::default()))
+    }
+}
+
+impl Line {
+    /// Line for a describe file, with addresses named using `symbols`
+    pub fn describe(&self, symbols: &Symbols) -> String {
         match self {
             Code {
                 idx: _,
-                opcode,
-                bytes,
+                instruction,
                 comment,
             } => {
-                let desc = opcode.simple_describe(*bytes);
+                let desc = instruction.simple_describe_with(symbols);
                 let comment = if !comment.is_empty() {
                     format!(";{}", comment)
                 } else {
                     String::new()
                 };
-                write!(f, "{:02X}{:02X} {} {}", bytes[0], bytes[1], desc, comment)
+                format!("{:04X} {} {}", instruction.encode(), desc, comment)
             }
             Data {
                 idx: _,
@@ -196,51 +168,312 @@ impl Display for Line {
                 for byte in bytes {
                     byte_str.push_str(&format!("{:02X}", byte));
                 }
-                write!(f, "DATA {byte_str}{comment}")
+                format!("DATA {byte_str}{comment}")
             }
-            Comment { idx: _, text } => write!(f, ";{text}"),
+            Comment { idx: _, text } => format!(";{text}"),
+            Org { idx: _, addr } => format!("ORG {addr:03X}"),
+        }
+    }
+
+    /// Explanation for a listing file, with addresses named using `symbols`
+    pub fn explain(&self, symbols: &Symbols) -> String {
+        match self {
+            Code { instruction, .. } => instruction.simple_describe_with(symbols),
+            Data { bytes, .. } if bytes.len() == 1 => String::from("1 byte"),
+            Data { bytes, .. } => format!("{} bytes", bytes.len()),
+            Comment { .. } => String::new(),
+            Org { addr, .. } => format!("Move to 0x{addr:03X}"),
+        }
+    }
+
+    fn idx(&self) -> usize {
+        match self {
+            Code { idx, .. } | Comment { idx, .. } | Data { idx, .. } | Org { idx, .. } => *idx,
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Code { instruction, .. } => instruction.to_bytes().to_vec(),
+            Data { bytes, .. } => bytes.clone(),
+            Comment { .. } | Org { .. } => vec![],
         }
     }
 }
 
 impl Program {
-    pub fn new(lines: Vec<Line>) -> Self {
-        Self { lines }
+    pub fn new(
+        lines: Vec<Line>,
+        source: Vec<SourceLine>,
+        labels: Vec<Label>,
+        files: Vec<String>,
+    ) -> Self {
+        Self {
+            lines,
+            source,
+            labels,
+            files,
+            diagnostics: vec![],
+            lints: vec![],
+        }
     }
 }
 
 impl Program {
-    pub fn describe(&self) -> String {
+    /// Describe file with addresses named using `symbols`
+    pub fn describe(&self, symbols: &Symbols) -> String {
         let mut output = String::new();
         for line in &self.lines {
-            output.push_str(&line.to_string());
+            output.push_str(&line.describe(symbols));
             output.push('\n');
         }
         output
     }
 
-    pub fn into_bytes(self) -> Vec<u8> {
-        let mut output = vec![];
-        for line in self.lines {
-            match line {
-                Code {
-                    idx: _,
-                    opcode: _,
-                    bytes,
-                    comment: _,
-                } => {
-                    output.extend_from_slice(&bytes);
-                }
-                Comment { .. } => {}
-                Data {
-                    idx: _,
-                    bytes,
-                    comment: _,
-                } => {
-                    output.extend_from_slice(&bytes);
+    /// Listing file, each line of source with its address, bytes and explanation followed by
+    /// the address of every label and the lines using it
+    pub fn listing(&self) -> String {
+        let mut symbols = Symbols::default();
+        let placed = self.placed();
+        for label in &self.labels {
+            let is_data = placed
+                .iter()
+                .any(|(addr, line)| *addr == label.addr as usize && matches!(line, Data { .. }));
+            let kind = if is_data {
+                SymbolKind::Data
+            } else {
+                SymbolKind::Code
+            };
+            symbols.insert(label.addr, &label.name, kind);
+        }
+        let locations: Vec<String> = self
+            .source
+            .iter()
+            .map(|line| format!("{}{}", line.location, "+".repeat(line.depth)))
+            .collect();
+        let loc_width = locations.iter().map(String::len).max().unwrap_or(0).max(4);
+        let text_width = self
+            .source
+            .iter()
+            .map(|line| line.text.trim_end().len())
+            .max()
+            .unwrap_or(0)
+            .max(6);
+
+        let mut rows = vec![(
+            String::from("LINE"),
+            String::from("ADDR"),
+            String::from("BYTES"),
+            "SOURCE",
+            String::from("EXPLANATION"),
+        )];
+        for (i, source) in self.source.iter().enumerate() {
+            let text = source.text.trim_end();
+            let label = self.labels.iter().find(|label| label.defined == i);
+            let mut lines = placed
+                .iter()
+                .filter(|(_, line)| line.idx() == i && !matches!(line, Comment { .. }))
+                .peekable();
+            if lines.peek().is_none() {
+                let addr = label
+                    .map(|label| format!("{:03X}", label.addr))
+                    .unwrap_or_default();
+                rows.push((
+                    locations[i].clone(),
+                    addr,
+                    String::new(),
+                    text,
+                    String::new(),
+                ));
+            }
+            for (addr, line) in lines {
+                let addr = match line {
+                    Org { addr, .. } => *addr as usize,
+                    _ => *addr,
+                };
+                let bytes = line.bytes();
+                let mut chunks = bytes.chunks(4);
+                let first = chunks.next().map(hex).unwrap_or_default();
+                rows.push((
+                    locations[i].clone(),
+                    format!("{addr:03X}"),
+                    first,
+                    text,
+                    line.explain(&symbols),
+                ));
+                for (n, chunk) in chunks.enumerate() {
+                    let addr = addr + (n + 1) * 4;
+                    rows.push((
+                        String::new(),
+                        format!("{addr:03X}"),
+                        hex(chunk),
+                        "",
+                        String::new(),
+                    ));
                 }
             }
         }
+
+        let mut output = String::new();
+        for (location, addr, bytes, text, explain) in rows {
+            let row = format!(
+                "{location:<loc_width$}  {addr:<4}  {bytes:<11}  {text:<text_width$}  {explain}"
+            );
+            output.push_str(row.trim_end());
+            output.push('\n');
+        }
+
+        if !self.labels.is_empty() {
+            let name_width = self
+                .labels
+                .iter()
+                .map(|label| label.name.len())
+                .max()
+                .unwrap_or(0)
+                .max(6);
+            output.push_str(&format!(
+                "\n{:<name_width$}  ADDR  DEFINED  REFERENCES\n",
+                "SYMBOL"
+            ));
+            for label in &self.labels {
+                let references = label
+                    .references
+                    .iter()
+                    .map(|i| locations[*i].as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+                let row = format!(
+                    "{:<name_width$}  {:03X}   {:<7}  {references}",
+                    label.name, label.addr, locations[label.defined]
+                );
+                output.push_str(row.trim_end());
+                output.push('\n');
+            }
+        }
         output
     }
+
+    /// Source file, line and column of every address, labels and data blocks
+    pub fn debug_info(&self) -> DebugInfo {
+        let mut info = DebugInfo::new(self.files.clone());
+        let placed = self.placed();
+        for (addr, line) in &placed {
+            let kind = match line {
+                Code { .. } => RegionKind::Code,
+                Data { .. } => RegionKind::Data,
+                Comment { .. } | Org { .. } => continue,
+            };
+            let source = &self.source[line.idx()];
+            info.add_line(LineInfo {
+                addr: *addr as u16,
+                len: (line.next_addr(*addr) - addr) as u16,
+                kind,
+                file: source.file,
+                line: source.line + 1,
+                column: source.column + 1,
+            });
+        }
+        for label in &self.labels {
+            info.add_label(&label.name, label.addr);
+            let data = placed
+                .iter()
+                .find(|(addr, line)| *addr == label.addr as usize && matches!(line, Data { .. }));
+            if let Some((addr, line)) = data {
+                info.add_data(
+                    &label.name,
+                    label.addr,
+                    (line.next_addr(*addr) - addr) as u16,
+                );
+            }
+        }
+        info
+    }
+
+    /// Start address of every line
+    pub fn placed(&self) -> Vec<(usize, &Line)> {
+        let mut placed = vec![];
+        let mut addr = PROG_START_ADDRESS as usize;
+        for line in &self.lines {
+            placed.push((addr, line));
+            addr = line.next_addr(addr);
+        }
+        placed
+    }
+
+    /// Start address, end address (exclusive) and line number of every line with bytes
+    fn regions(&self) -> Vec<(usize, usize, usize)> {
+        self.placed()
+            .into_iter()
+            .filter_map(|(addr, line)| match line {
+                Code { idx, .. } | Data { idx, .. } => Some((addr, line.next_addr(addr), *idx)),
+                Comment { .. } | Org { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Check every line fits in memory and no lines overlap
+    pub fn check_layout(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        let mut regions = self.regions();
+        regions.sort();
+        for (start, end, idx) in &regions {
+            if *end > MAX_ADDRESS as usize + 1 {
+                errors.push(format!(
+                    "Line {idx}) 0x{start:03X} to 0x{:03X} is past the end of memory",
+                    end - 1
+                ));
+            }
+        }
+        for pair in regions.windows(2) {
+            if pair[1].0 < pair[0].1 {
+                //report the later line
+                let (first, (start, end, idx)) = match pair[0].2 < pair[1].2 {
+                    true => (pair[0], pair[1]),
+                    false => (pair[1], pair[0]),
+                };
+                errors.push(format!(
+                    "Line {idx}) 0x{start:03X} to 0x{:03X} overlaps line {} (0x{:03X} to 0x{:03X})",
+                    end - 1,
+                    first.2,
+                    first.0,
+                    first.1 - 1
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Program bytes, starting at [PROG_START_ADDRESS] with gaps filled with zeros
+    pub fn into_bytes(self) -> Vec<u8> {
+        let start = PROG_START_ADDRESS as usize;
+        let regions = self.regions();
+        let end = regions
+            .iter()
+            .map(|(_, end, _)| *end)
+            .max()
+            .unwrap_or(start);
+        let mut output = vec![0; end - start];
+        let lines = self
+            .lines
+            .iter()
+            .filter(|line| matches!(line, Code { .. } | Data { .. }));
+        for ((addr, _, _), line) in regions.into_iter().zip(lines) {
+            let bytes = line.bytes();
+            output[addr - start..addr - start + bytes.len()].copy_from_slice(&bytes);
+        }
+        output
+    }
+}
+
+/// Bytes as hex, separated by spaces
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<String>>()
+        .join(" ")
 }
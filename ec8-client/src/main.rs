@@ -1,13 +1,19 @@
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 use buffer_graphics_lib::color::{BLACK, WHITE};
 use buffer_graphics_lib::Graphics;
 use clap::{arg, command, value_parser};
 use color_eyre::eyre::eyre;
 use color_eyre::Result;
+use ec8_common::debug_info::DebugInfo;
+use ec8_common::symbols::Symbols;
 use ec8_common::{MAX_X, MAX_Y};
+use ec8_core::cheats::CheatSet;
 use ec8_core::input::Key;
-use ec8_core::EmmaChip8;
+use ec8_core::sanitizer::ProgramInfo;
+use ec8_core::triggers::{TriggerEngine, TriggerSet};
+use ec8_core::{program_hash, EmmaChip8};
 use env_logger::Builder;
 use log::{LevelFilter, warn};
 use pixels_graphics_lib::{run, System, WindowScaling};
@@ -22,6 +28,9 @@ const TIMER_UPDATE_RATE: f32 = 1.0 / 60.0;
 
 struct EC8Hardware {
     ec8: EmmaChip8,
+    triggers: Option<TriggerEngine>,
+    cheats: Option<CheatSet>,
+    cheats_enabled: bool,
     next_run: f32,
     next_timer: f32,
 }
@@ -30,6 +39,9 @@ impl EC8Hardware {
     pub fn new() -> Self {
         Self {
             ec8: EmmaChip8::new(),
+            triggers: None,
+            cheats: None,
+            cheats_enabled: true,
             next_run: 0.0,
             next_timer: 0.0,
         }
@@ -47,6 +59,32 @@ fn main() -> Result<()> {
                 .value_hint(FilePath)
                 .value_parser(value_parser!(PathBuf)),
         )
+        .arg(arg!(-s --sanitize "Report reads of uninitialized values and suspicious control flow"))
+        .arg(
+            arg!(-r --randomize "Fill registers and unused memory with random values on start")
+                .requires("sanitize"),
+        )
+        .arg(
+            arg!(-g --debug <DEBUG_FILE> "Debug info file (from ec8-assembler or ec8-ll-compiler), marks data and names addresses for the sanitizer")
+                .requires("sanitize")
+                .value_hint(FilePath)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--symbols <FILE> "Symbols file (from ec8-ll-compiler), names addresses in logs and sanitizer reports")
+                .value_hint(FilePath)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(-t --triggers <FILE> "Triggers file, unlocked triggers are printed")
+                .value_hint(FilePath)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(-c --cheats <FILE> "Cheats file, toggle with F12")
+                .value_hint(FilePath)
+                .value_parser(value_parser!(PathBuf)),
+        )
         .get_matches();
 
     let input_file = matches
@@ -62,7 +100,63 @@ fn main() -> Result<()> {
 
     let bytes = fs::read(input_file)?;
 
+    let symbols = match matches.get_one::<PathBuf>("symbols") {
+        Some(file) => fs::read_to_string(file)?
+            .parse::<Symbols>()
+            .map_err(|err| eyre!("Invalid symbols file: {err}"))?,
+        None => Symbols::default(),
+    };
+
     let mut system = Box::new(EC8Hardware::new());
+    system.ec8.symbols = Some(Arc::new(symbols.clone()));
+    if matches.get_flag("sanitize") {
+        system.ec8.enable_sanitizer(matches.get_flag("randomize"));
+        if let Some(sanitizer) = &mut system.ec8.sanitizer {
+            let mut info = match matches.get_one::<PathBuf>("debug") {
+                Some(file) => {
+                    let debug = fs::read_to_string(file)?
+                        .parse::<DebugInfo>()
+                        .map_err(|err| eyre!(err))?;
+                    ProgramInfo::from_debug_info(&debug)
+                }
+                None => ProgramInfo::default(),
+            };
+            if !symbols.is_empty() {
+                info.symbols = symbols;
+            }
+            sanitizer.set_program_info(info);
+        }
+    }
+    let triggers = match matches.get_one::<PathBuf>("triggers") {
+        Some(file) => Some(
+            fs::read_to_string(file)?
+                .parse::<TriggerSet>()
+                .map_err(|err| eyre!("Invalid triggers file: {err}"))?,
+        ),
+        None => None,
+    };
+    let cheats = match matches.get_one::<PathBuf>("cheats") {
+        Some(file) => Some(
+            fs::read_to_string(file)?
+                .parse::<CheatSet>()
+                .map_err(|err| eyre!("Invalid cheats file: {err}"))?,
+        ),
+        None => None,
+    };
+    let for_rom = [
+        ("Triggers", triggers.as_ref().map(|set| set.is_for(&bytes))),
+        ("Cheats", cheats.as_ref().map(|set| set.is_for(&bytes))),
+    ];
+    for (name, is_for) in for_rom {
+        if is_for == Some(false) {
+            return Err(eyre!(
+                "{name} are for a different ROM, this ROM is {:016x}",
+                program_hash(&bytes)
+            ));
+        }
+    }
+    system.triggers = triggers.map(TriggerEngine::new);
+    system.cheats = cheats;
     system.ec8.load_program(&bytes)?;
     run(
         MAX_X,
@@ -90,6 +184,11 @@ impl System for EC8Hardware {
         if self.ec8.state == Running {
             if self.next_run <= 0.0 {
                 self.ec8.run();
+                if let Some(sanitizer) = &mut self.ec8.sanitizer {
+                    for report in sanitizer.take_reports() {
+                        warn!("{}", sanitizer.describe(&report));
+                    }
+                }
                 if self.ec8.state != Running {
                     warn!("{:?}", self.ec8.state);
                 }
@@ -98,8 +197,15 @@ impl System for EC8Hardware {
                 self.next_run -= delta;
             }
             if self.next_timer <= 0.0 {
-                self.ec8.delay = self.ec8.delay.saturating_sub(1);
-                self.ec8.sound = self.ec8.sound.saturating_sub(1);
+                if let Some(cheats) = &mut self.cheats {
+                    cheats.apply(&mut self.ec8);
+                }
+                self.ec8.tick_timers();
+                if let Some(triggers) = &mut self.triggers {
+                    for trigger in triggers.update(&self.ec8) {
+                        warn!("Unlocked: {}", trigger.name);
+                    }
+                }
                 self.next_timer = TIMER_UPDATE_RATE;
             } else {
                 self.next_timer -= delta;
@@ -119,11 +225,19 @@ impl System for EC8Hardware {
     }
 
     fn action_keys(&self) -> Vec<VirtualKeyCode> {
-        vec![Key1, Key2, Key3, Key4, Q, W, E, R, A, S, D, F, Z, X, C, V]
+        vec![Key1, Key2, Key3, Key4, Q, W, E, R, A, S, D, F, Z, X, C, V, F12]
     }
 
     fn on_key_down(&mut self, keys: Vec<VirtualKeyCode>) {
         for key in keys {
+            if key == F12 {
+                if let Some(cheats) = &mut self.cheats {
+                    self.cheats_enabled = !self.cheats_enabled;
+                    cheats.set_enabled(self.cheats_enabled);
+                    warn!("Cheats enabled: {}", self.cheats_enabled);
+                }
+                continue;
+            }
             if let Some(key) = Key::from_lefthand_layout(key_to_chr(key)) {
                 self.ec8.on_key_pressed(key);
             }
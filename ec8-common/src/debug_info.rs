@@ -0,0 +1,205 @@
+//! Debug info
+//!
+//! Source map made by ec8-assembler and ec8-ll-compiler, saved as JSON
+//!
+//! ```json
+//! {
+//!   "files": ["game.eca", "lib/font.eca"],
+//!   "lines": [{ "addr": 512, "len": 2, "kind": "code", "file": 0, "line": 4, "column": 7 }],
+//!   "labels": [{ "name": "start", "addr": 512 }],
+//!   "data": [{ "name": "font", "addr": 530, "len": 5 }],
+//!   "regions": [{ "start": 512, "end": 530, "kind": "code" }]
+//! }
+//! ```
+//! Lines and columns start at 1, region ends are exclusive
+
+use crate::symbols::{SymbolKind, Symbols};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionKind {
+    Code,
+    Data,
+}
+
+/// Source of the bytes starting at `addr`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LineInfo {
+    pub addr: u16,
+    pub len: u16,
+    pub kind: RegionKind,
+    /// Index in [DebugInfo::files]
+    pub file: usize,
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column of the code, starting at 1
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LabelInfo {
+    pub name: String,
+    pub addr: u16,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DataInfo {
+    pub name: String,
+    pub addr: u16,
+    pub len: u16,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    pub start: u16,
+    pub end: u16,
+    pub kind: RegionKind,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DebugInfo {
+    pub files: Vec<String>,
+    pub lines: Vec<LineInfo>,
+    pub labels: Vec<LabelInfo>,
+    pub data: Vec<DataInfo>,
+    pub regions: Vec<Region>,
+}
+
+impl DebugInfo {
+    pub fn new(files: Vec<String>) -> Self {
+        Self {
+            files,
+            ..Self::default()
+        }
+    }
+
+    /// Add a line, extending the last region if it's the same kind and ends at `line.addr`
+    pub fn add_line(&mut self, line: LineInfo) {
+        let end = line.addr + line.len;
+        match self.regions.last_mut() {
+            Some(region) if region.kind == line.kind && region.end == line.addr => region.end = end,
+            _ => self.regions.push(Region {
+                start: line.addr,
+                end,
+                kind: line.kind,
+            }),
+        }
+        self.lines.push(line);
+    }
+
+    pub fn add_label(&mut self, name: &str, addr: u16) {
+        self.labels.push(LabelInfo {
+            name: name.to_string(),
+            addr,
+        });
+    }
+
+    pub fn add_data(&mut self, name: &str, addr: u16, len: u16) {
+        self.data.push(DataInfo {
+            name: name.to_string(),
+            addr,
+            len,
+        });
+    }
+
+    /// Line containing `addr`
+    pub fn line_at(&self, addr: u16) -> Option<&LineInfo> {
+        self.lines
+            .iter()
+            .find(|line| (line.addr..line.addr + line.len).contains(&addr))
+    }
+
+    /// `file:line:column` for `addr`
+    pub fn location(&self, addr: u16) -> Option<String> {
+        self.line_at(addr).map(|line| {
+            let file = self.files.get(line.file).map(String::as_str).unwrap_or("?");
+            format!("{file}:{}:{}", line.line, line.column)
+        })
+    }
+
+    /// Labels as code symbols and data blocks as data symbols
+    pub fn symbols(&self) -> Symbols {
+        let mut symbols = Symbols::default();
+        for label in &self.labels {
+            symbols.insert(label.addr, &label.name, SymbolKind::Code);
+        }
+        for data in &self.data {
+            symbols.insert_sized(data.addr, &data.name, SymbolKind::Data, data.len);
+        }
+        symbols
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Debug info is always valid JSON")
+    }
+}
+
+impl FromStr for DebugInfo {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(text).map_err(|err| format!("Invalid debug info: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::debug_info::{DebugInfo, LineInfo, Region, RegionKind};
+    use crate::symbols::SymbolKind;
+
+    fn line(addr: u16, len: u16, kind: RegionKind, line: usize) -> LineInfo {
+        LineInfo {
+            addr,
+            len,
+            kind,
+            file: 0,
+            line,
+            column: 2,
+        }
+    }
+
+    #[test]
+    fn check_debug_info() {
+        let mut info = DebugInfo::new(vec![String::from("main.eca")]);
+        info.add_line(line(0x200, 2, RegionKind::Code, 1));
+        info.add_line(line(0x202, 2, RegionKind::Code, 2));
+        info.add_line(line(0x204, 3, RegionKind::Data, 3));
+        info.add_line(line(0x300, 2, RegionKind::Code, 5));
+        info.add_label("start", 0x200);
+        info.add_data("text", 0x204, 3);
+
+        assert_eq!(
+            info.regions,
+            vec![
+                Region {
+                    start: 0x200,
+                    end: 0x204,
+                    kind: RegionKind::Code
+                },
+                Region {
+                    start: 0x204,
+                    end: 0x207,
+                    kind: RegionKind::Data
+                },
+                Region {
+                    start: 0x300,
+                    end: 0x302,
+                    kind: RegionKind::Code
+                },
+            ]
+        );
+        assert_eq!(info.location(0x206), Some(String::from("main.eca:3:2")));
+        assert_eq!(info.location(0x207), None);
+        assert_eq!(
+            info.symbols().get(0x204).map(|symbol| symbol.kind),
+            Some(SymbolKind::Data)
+        );
+
+        let json = info.to_json();
+        assert!(json.contains("\"kind\": \"data\""), "{json}");
+        assert_eq!(json.parse::<DebugInfo>(), Ok(info));
+        assert!("{}".parse::<DebugInfo>().is_err());
+    }
+}
@@ -0,0 +1,590 @@
+//! Opcode table
+//!
+//! Every opcode is defined once in [DEFINITIONS], decoding, encoding, assembling and describing
+//! all use this table
+//!
+//! Description templates can contain `{x}`, `{y}`, `{n}`, `{nn}` and `{nnn}`
+
+use crate::opcodes::{cast_4bit_num, cast_8bit_num, cast_first_reg, cast_second_reg};
+use crate::OpCodes;
+use crate::OpCodes::*;
+
+/// Operands an opcode takes, in the order they are written in assembly
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OperandKind {
+    Empty,
+    /// nnn
+    Address,
+    /// x
+    Reg,
+    /// x, nn
+    RegByte,
+    /// x, y
+    RegReg,
+    /// x, y, n
+    RegRegNibble,
+}
+
+/// Which machines support an opcode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Platform {
+    Chip8,
+    /// EmmaChip-8 only
+    Ec8,
+}
+
+/// Operand values, only the fields used by the [OperandKind] are read or written
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Operands {
+    pub x: u8,
+    pub y: u8,
+    pub n: u8,
+    pub nn: u8,
+    pub nnn: u16,
+}
+
+impl Operands {
+    pub fn address(nnn: u16) -> Self {
+        Self {
+            nnn,
+            ..Self::default()
+        }
+    }
+
+    pub fn reg(x: u8) -> Self {
+        Self {
+            x,
+            ..Self::default()
+        }
+    }
+
+    pub fn reg_byte(x: u8, nn: u8) -> Self {
+        Self {
+            x,
+            nn,
+            ..Self::default()
+        }
+    }
+
+    pub fn reg_reg(x: u8, y: u8) -> Self {
+        Self {
+            x,
+            y,
+            ..Self::default()
+        }
+    }
+
+    pub fn reg_reg_nibble(x: u8, y: u8, n: u8) -> Self {
+        Self {
+            x,
+            y,
+            n,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Definition {
+    pub opcode: OpCodes,
+    /// Fixed bits of the word
+    pub pattern: u16,
+    /// Bits of the word that must match `pattern`
+    pub mask: u16,
+    /// Assembly mnemonic, empty if the assembler doesn't support this opcode
+    pub mnemonic: &'static str,
+    pub operands: OperandKind,
+    pub description: &'static str,
+    pub platform: Platform,
+}
+
+const fn def(
+    opcode: OpCodes,
+    pattern: u16,
+    mask: u16,
+    mnemonic: &'static str,
+    operands: OperandKind,
+    description: &'static str,
+    platform: Platform,
+) -> Definition {
+    Definition {
+        opcode,
+        pattern,
+        mask,
+        mnemonic,
+        operands,
+        description,
+        platform,
+    }
+}
+
+use OperandKind::*;
+use Platform::*;
+
+/// All opcodes, more specific patterns are listed before less specific ones (00E0 before 0nnn)
+pub const DEFINITIONS: [Definition; 36] = [
+    def(
+        ClearDisplay,
+        0x00E0,
+        0xFFFF,
+        "clr",
+        Empty,
+        "Clear the display",
+        Chip8,
+    ),
+    def(
+        Return,
+        0x00EE,
+        0xFFFF,
+        "ret",
+        Empty,
+        "Return from subroutine",
+        Chip8,
+    ),
+    def(
+        SysCall,
+        0x0000,
+        0xF000,
+        "",
+        Address,
+        "SysCall to {nnn} (Unsupported)",
+        Chip8,
+    ),
+    def(Jump, 0x1000, 0xF000, "jmp", Address, "Jump to {nnn}", Chip8),
+    def(
+        Call,
+        0x2000,
+        0xF000,
+        "cal",
+        Address,
+        "Call subroutine at {nnn}",
+        Chip8,
+    ),
+    def(
+        SkipIfEqualNum,
+        0x3000,
+        0xF000,
+        "ske",
+        RegByte,
+        "Skip if {x} == {nn}",
+        Chip8,
+    ),
+    def(
+        SkipIfNotEqualNum,
+        0x4000,
+        0xF000,
+        "skn",
+        RegByte,
+        "Skip if {x} != {nn}",
+        Chip8,
+    ),
+    def(
+        SkipIfEqualReg,
+        0x5000,
+        0xF00F,
+        "ske",
+        RegReg,
+        "Skip if {x} == {y}",
+        Chip8,
+    ),
+    def(
+        SetRegFromNum,
+        0x6000,
+        0xF000,
+        "set",
+        RegByte,
+        "Set {x} to {nn}",
+        Chip8,
+    ),
+    def(
+        AddNumToReg,
+        0x7000,
+        0xF000,
+        "add",
+        RegByte,
+        "Set {x} to {x} + {nn}",
+        Chip8,
+    ),
+    def(
+        SetRegFromReg,
+        0x8000,
+        0xF00F,
+        "set",
+        RegReg,
+        "Set {x} to {y}",
+        Chip8,
+    ),
+    def(
+        BitwiseOr,
+        0x8001,
+        0xF00F,
+        "or",
+        RegReg,
+        "Set {x} to {x} | {y}",
+        Chip8,
+    ),
+    def(
+        BitwiseAnd,
+        0x8002,
+        0xF00F,
+        "and",
+        RegReg,
+        "Set {x} to {x} & {y}",
+        Chip8,
+    ),
+    def(
+        BitwiseXor,
+        0x8003,
+        0xF00F,
+        "xor",
+        RegReg,
+        "Set {x} to {x} ^ {y}",
+        Chip8,
+    ),
+    def(
+        AddReg,
+        0x8004,
+        0xF00F,
+        "add",
+        RegReg,
+        "Set {x} to {x} + {y}",
+        Chip8,
+    ),
+    def(
+        SubRightReg,
+        0x8005,
+        0xF00F,
+        "sub",
+        RegReg,
+        "Set {x} to {x} - {y}",
+        Chip8,
+    ),
+    def(
+        ShiftRight,
+        0x8006,
+        0xF00F,
+        "shr",
+        RegReg,
+        "Set VF to first bit of {x}, set {x} to {x} >> 1",
+        Chip8,
+    ),
+    def(
+        SubLeftReg,
+        0x8007,
+        0xF00F,
+        "sbr",
+        RegReg,
+        "Set {x} to {y} - {x}",
+        Chip8,
+    ),
+    def(
+        ShiftLeft,
+        0x800E,
+        0xF00F,
+        "shl",
+        RegReg,
+        "Set VF to last bit of {x}, set {x} to {x} << 1",
+        Chip8,
+    ),
+    def(
+        SkipIfNotEqualReg,
+        0x9000,
+        0xF00F,
+        "skn",
+        RegReg,
+        "Skip if {x} != {y}",
+        Chip8,
+    ),
+    def(
+        SetMemReg,
+        0xA000,
+        0xF000,
+        "sti",
+        Address,
+        "Set I to {nnn}",
+        Chip8,
+    ),
+    def(
+        JumpOffset,
+        0xB000,
+        0xF000,
+        "jpo",
+        Address,
+        "Jump to {nnn} + V0",
+        Chip8,
+    ),
+    def(
+        SetRegRand,
+        0xC000,
+        0xF000,
+        "rnd",
+        RegByte,
+        "Set {x} to rand(0..=255) & {nn}",
+        Chip8,
+    ),
+    def(
+        DrawSprite,
+        0xD000,
+        0xF000,
+        "drw",
+        RegRegNibble,
+        "Draw sprite at {x},{y} with {n} rows from I",
+        Chip8,
+    ),
+    def(
+        SkipIfKeyPressed,
+        0xE09E,
+        0xF0FF,
+        "skp",
+        Reg,
+        "Skipping if key in {x} is pressed",
+        Chip8,
+    ),
+    def(
+        SkipIfKeyNotPressed,
+        0xE0A1,
+        0xF0FF,
+        "skr",
+        Reg,
+        "Skipping if key in {x} is not pressed",
+        Chip8,
+    ),
+    def(
+        SetRegFromTimer,
+        0xF007,
+        0xF0FF,
+        "rdt",
+        Reg,
+        "Set {x} to delay timer",
+        Chip8,
+    ),
+    def(
+        WaitForKey,
+        0xF00A,
+        0xF0FF,
+        "key",
+        Reg,
+        "Wait for key press, and store it in {x}",
+        Chip8,
+    ),
+    def(
+        SetDelayTimer,
+        0xF015,
+        0xF0FF,
+        "sdt",
+        Reg,
+        "Set delay timer to {x}",
+        Chip8,
+    ),
+    def(
+        SetSoundTimer,
+        0xF018,
+        0xF0FF,
+        "sst",
+        Reg,
+        "Set sound timer to {x}",
+        Chip8,
+    ),
+    def(
+        AddMemReg,
+        0xF01E,
+        0xF0FF,
+        "adi",
+        Reg,
+        "Set I to I + {x}",
+        Chip8,
+    ),
+    def(
+        SetMemRegToDigitSprite,
+        0xF029,
+        0xF0FF,
+        "chr",
+        Reg,
+        "Set I to addr of digit in {x}",
+        Chip8,
+    ),
+    def(
+        SetMemRegToAsciiSprite,
+        0xF030,
+        0xF0FF,
+        "asc",
+        Reg,
+        "Set I to addr of ASCII in {x}",
+        Ec8,
+    ),
+    def(
+        StoreBcd,
+        0xF033,
+        0xF0FF,
+        "bcd",
+        Reg,
+        "Store {x} as BCD starting at I",
+        Chip8,
+    ),
+    def(
+        StoreRegs,
+        0xF055,
+        0xF0FF,
+        "str",
+        Reg,
+        "Store regs from V0 to {x} in memory starting at I",
+        Chip8,
+    ),
+    def(
+        LoadRegs,
+        0xF065,
+        0xF0FF,
+        "ldr",
+        Reg,
+        "Load regs from V0 to {x} from memory starting at I",
+        Chip8,
+    ),
+];
+
+/// Definition matching `word`, if any
+pub fn lookup(word: u16) -> Option<&'static Definition> {
+    DEFINITIONS
+        .iter()
+        .find(|def| word & def.mask == def.pattern)
+}
+
+/// Definitions with this mnemonic (case insensitive)
+pub fn by_mnemonic(mnemonic: &str) -> Vec<&'static Definition> {
+    let mnemonic = mnemonic.to_lowercase();
+    DEFINITIONS
+        .iter()
+        .filter(|def| !def.mnemonic.is_empty() && def.mnemonic == mnemonic)
+        .collect()
+}
+
+impl OpCodes {
+    pub fn definition(&self) -> &'static Definition {
+        DEFINITIONS
+            .iter()
+            .find(|def| def.opcode == *self)
+            .expect("Every opcode must be in DEFINITIONS, please raise an issue")
+    }
+}
+
+impl OperandKind {
+    /// Read operands from `word`
+    pub fn extract(&self, word: u16) -> Operands {
+        let [high, low] = word.to_be_bytes();
+        let x = high & 0xF;
+        let y = low >> 4;
+        match self {
+            Empty => Operands::default(),
+            Address => Operands::address(word & 0xFFF),
+            Reg => Operands::reg(x),
+            RegByte => Operands::reg_byte(x, low),
+            RegReg => Operands::reg_reg(x, y),
+            RegRegNibble => Operands::reg_reg_nibble(x, y, low & 0xF),
+        }
+    }
+
+    /// Operand bits for `operands`, values outside the operand ranges are masked
+    pub fn insert(&self, operands: Operands) -> u16 {
+        let x = cast_first_reg(operands.x);
+        let y = cast_second_reg(operands.y);
+        match self {
+            Empty => 0,
+            Address => operands.nnn & 0xFFF,
+            Reg => x,
+            RegByte => x | cast_8bit_num(operands.nn),
+            RegReg => x | y,
+            RegRegNibble => x | y | cast_4bit_num(operands.n),
+        }
+    }
+
+    /// Operands as written in assembly, such as `vA, 1F`
+    pub fn to_asm(&self, operands: Operands) -> String {
+        let x = format!("v{:X}", operands.x);
+        let y = format!("v{:X}", operands.y);
+        match self {
+            Empty => String::new(),
+            Address => format!("{:03X}", operands.nnn),
+            Reg => x,
+            RegByte => format!("{x}, {:02X}", operands.nn),
+            RegReg => format!("{x}, {y}"),
+            RegRegNibble => format!("{x}, {y}, {:X}", operands.n),
+        }
+    }
+}
+
+impl Definition {
+    pub fn encode(&self, operands: Operands) -> u16 {
+        self.pattern | self.operands.insert(operands)
+    }
+
+    /// Fill in the description template, `reg` formats register operands and `addr` formats
+    /// address operands
+    pub fn describe(
+        &self,
+        operands: Operands,
+        reg: impl Fn(u8) -> String,
+        addr: impl Fn(u16) -> String,
+    ) -> String {
+        self.description
+            .replace("{x}", &reg(operands.x))
+            .replace("{y}", &reg(operands.y))
+            .replace("{nnn}", &addr(operands.nnn))
+            .replace("{nn}", &format!("{:02X}", operands.nn))
+            .replace("{n}", &format!("{:01X}", operands.n))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::definitions::{by_mnemonic, lookup, Operands, DEFINITIONS};
+    use crate::OpCodes;
+
+    #[test]
+    fn check_table() {
+        for (i, def) in DEFINITIONS.iter().enumerate() {
+            assert_eq!(def.pattern & def.mask, def.pattern, "{:?}", def.opcode);
+            assert_eq!(def.opcode.definition(), def);
+            assert!(
+                DEFINITIONS[..i]
+                    .iter()
+                    .all(|other| other.opcode != def.opcode),
+                "{:?} is duplicated",
+                def.opcode
+            );
+            assert_eq!(lookup(def.pattern).unwrap().opcode, def.opcode);
+        }
+    }
+
+    #[test]
+    fn check_lookup() {
+        assert_eq!(
+            lookup(0xF330).unwrap().opcode,
+            OpCodes::SetMemRegToAsciiSprite
+        );
+        assert_eq!(lookup(0x0123).unwrap().opcode, OpCodes::SysCall);
+        assert!(lookup(0x5121).is_none());
+        assert!(lookup(0xE000).is_none());
+        assert_eq!(by_mnemonic("SKE").len(), 2);
+        assert!(by_mnemonic("").is_empty());
+    }
+
+    #[test]
+    fn check_describe() {
+        let operands = Operands::reg_reg_nibble(2, 0xA, 5);
+        assert_eq!(
+            OpCodes::DrawSprite.definition().describe(
+                operands,
+                |reg| format!("V{reg:X}"),
+                |addr| format!("{addr:03X}")
+            ),
+            "Draw sprite at V2,VA with 5 rows from I"
+        );
+        assert_eq!(
+            OpCodes::Jump.definition().describe(
+                Operands::address(0x21A),
+                |reg| format!("V{reg:X}"),
+                |addr| format!("lbl ({addr:03X})")
+            ),
+            "Jump to lbl (21A)"
+        );
+    }
+}
@@ -1,58 +1,29 @@
-use crate::nibbler::{Masher, Nibbler};
-use crate::opcodes::from_bytes;
-use crate::{OpCodes, REGISTER_COUNT};
+use crate::instruction::Instruction::*;
+use crate::instruction::{decode, Instruction};
+use crate::symbols::Symbols;
+use crate::REGISTER_COUNT;
 
-impl OpCodes {
-    pub fn simple_describe(&self, bytes: [u8; 2]) -> String {
-        let x = format!("V{:01X}", bytes[0].second_nibble());
-        let y = format!("V{:01X}", bytes[1].first_nibble_shifted());
-        let n = format!("{:01X}", bytes[1].second_nibble());
-        let nn = format!("{:02X}", bytes[1]);
-        let nnn = format!("{:03X}", u16::from_be_bytes(bytes) & 0xFFF);
-        match self {
-            OpCodes::SysCall => format!("SysCall to {nnn} (Unsupported)"),
-            OpCodes::ClearDisplay => "Clear the display".to_string(),
-            OpCodes::Return => "Return from subroutine".to_string(),
-            OpCodes::Jump => format!("Jump to {nnn}"),
-            OpCodes::Call => format!("Call subroutine at {nnn}"),
-            OpCodes::SkipIfEqualNum => format!("Skip if {x} == {nn}"),
-            OpCodes::SkipIfNotEqualNum => format!("Skip if {x} != {nn}"),
-            OpCodes::SkipIfEqualReg => format!("Skip if {x} == {y}"),
-            OpCodes::SetRegFromNum => format!("Set {x} to {nn}"),
-            OpCodes::AddNumToReg => format!("Set {x} to {x} + {nn}"),
-            OpCodes::SetRegFromReg => format!("Set {x} to {y}"),
-            OpCodes::BitwiseOr => format!("Set {x} to {x} | {y}"),
-            OpCodes::BitwiseAnd => format!("Set {x} to {x} & {y}"),
-            OpCodes::BitwiseXor => format!("Set {x} to {x} ^ {y}"),
-            OpCodes::AddReg => format!("Set {x} to {x} + {y}"),
-            OpCodes::SubRightReg => format!("Set {x} to {x} - {y}"),
-            OpCodes::ShiftRight => format!("Set VF to first bit of {x}, set {x} to {x} >> 1"),
-            OpCodes::SubLeftReg => format!("Set {x} to {y} - {x}"),
-            OpCodes::ShiftLeft => format!("Set VF to last bit of {x}, set {x} to {x} << 1"),
-            OpCodes::SkipIfNotEqualReg => format!("Skip if {x} != {y}"),
-            OpCodes::SetMemReg => format!("Set I to {nnn}"),
-            OpCodes::JumpOffset => format!("Jump to {nnn} + V0"),
-            OpCodes::SetRegRand => format!("Set {x} to rand(0..=255) & {nn}"),
-            OpCodes::DrawSprite => format!("Draw sprite at {x},{y} with {n} rows from I"),
-            OpCodes::SkipIfKeyPressed => format!("Skipping if key in {x} is pressed"),
-            OpCodes::SkipIfKeyNotPressed => format!("Skipping if key in {x} is not pressed"),
-            OpCodes::SetRegFromTimer => format!("Set {x} to delay timer"),
-            OpCodes::WaitForKey => format!("Wait for key press, and store it in {x}"),
-            OpCodes::SetDelayTimer => format!("Set delay timer to {x}"),
-            OpCodes::SetSoundTimer => format!("Set sound timer to {x}"),
-            OpCodes::AddMemReg => format!("Set I to I + {x}"),
-            OpCodes::SetMemRegToDigitSprite => format!("Set I to addr of digit in {x}"),
-            OpCodes::SetMemRegToAsciiSprite => format!("Set I to addr of ASCII in {x}"),
-            OpCodes::StoreBcd => format!("Store {x} as BCD starting at I"),
-            OpCodes::StoreRegs => format!("Store regs from V0 to {x} in memory starting at I"),
-            OpCodes::LoadRegs => format!("Load regs from V0 to {x} from memory starting at I"),
+impl Instruction {
+    pub fn simple_describe(&self) -> String {
+        self.simple_describe_with(&Symbols::default())
+    }
+
+    /// Description with addresses named using `symbols`
+    pub fn simple_describe_with(&self, symbols: &Symbols) -> String {
+        match self.parts() {
+            None => format!("Unknown {:04X}", self.encode()),
+            Some((opcode, operands)) => opcode.definition().describe(
+                operands,
+                |reg| format!("V{:01X}", reg),
+                |addr| symbols.format(addr),
+            ),
         }
     }
-    
+
+    /// Description including register values before and after, and the skipped instruction
     #[allow(clippy::too_many_arguments)] //it's a complicated debug method
     pub fn describe(
         &self,
-        bytes: [u8; 2],
         pre_registers: [u8; REGISTER_COUNT],
         pre_mem_reg: u16,
         post_registers: [u8; REGISTER_COUNT],
@@ -60,45 +31,41 @@ impl OpCodes {
         mut pc: u16,
         data: u16,
         pc_delta: u16,
+        symbols: &Symbols,
     ) -> String {
         let pc_set = pc_delta != 2;
-        let data_bytes = data.to_be_bytes();
         let next_instr = if pc_set {
-            from_bytes(data_bytes)
-                .map(|op| {
-                    op.describe(
-                        data_bytes,
-                        pre_registers,
-                        pre_mem_reg,
-                        post_registers,
-                        post_mem_reg,
-                        pc + 2,
-                        0,
-                        2,
-                    )
-                })
-                .unwrap_or(format!("DATA {:04X}", data))
+            match decode(&data.to_be_bytes()) {
+                Unknown(_) => format!("DATA {:04X}", data),
+                next => next.describe(
+                    pre_registers,
+                    pre_mem_reg,
+                    post_registers,
+                    post_mem_reg,
+                    pc + 2,
+                    0,
+                    2,
+                    symbols,
+                ),
+            }
         } else {
             "not skipped".to_string()
         };
-        let x_value =  bytes[0].second_nibble();
-        let x = format!("V{:01X}", bytes[0].second_nibble());
-        let y = format!("V{:01X}", bytes[1].first_nibble_shifted());
-        let n = format!("{:01X}", bytes[1].second_nibble());
-        let pre_vx = format!(
-            "{x} ({:02X})",
-            pre_registers[(bytes[0].second_nibble() as usize).min(15)]
-        );
-        let post_vx = format!(
-            "{x} ({:02X})",
-            post_registers[(bytes[0].second_nibble() as usize).min(15)]
-        );
-        let pre_vy = format!(
-            "{y} ({:02X})",
-            pre_registers[(bytes[1].first_nibble_shifted() as usize).min(15)]
-        );
-        let nn = format!("{:02X}", bytes[1]);
-        let addr = format!("{:03X}", bytes.mash_to_12bits());
+        let v = |reg: u8| format!("V{:01X}", reg);
+        let pre_v = |reg: u8| {
+            format!(
+                "V{:01X} ({:02X})",
+                reg,
+                pre_registers[(reg as usize).min(15)]
+            )
+        };
+        let post_v = |reg: u8| {
+            format!(
+                "V{:01X} ({:02X})",
+                reg,
+                post_registers[(reg as usize).min(15)]
+            )
+        };
         if pc_delta == 0 {
             pc = data
         }
@@ -106,96 +73,139 @@ impl OpCodes {
         let pre_mem_reg = format!("I ({:02X})", pre_mem_reg);
         let post_mem_reg = format!("I ({:02X})", post_mem_reg);
         let data_byte = format!("{:02X}", data);
-        let data_addr = format!("{:03X}", data);
+        let data_addr = symbols.format(data);
+        let addr = |nnn: u16| symbols.format(nnn);
         let regs = |stop_at: u8| {
             pre_registers
                 .iter()
                 .enumerate()
                 .filter(|(i, _)| i <= &(stop_at as usize))
-                .map(|(_,value)| format!("{:02X}", value))
+                .map(|(_, value)| format!("{:02X}", value))
                 .collect::<Vec<String>>()
                 .join(", ")
         };
         let next_instr = format!("\n  {next_instr}");
-        let text = match self {
-            OpCodes::SysCall => format!("SysCall to {addr} (Unsupported)"),
-            OpCodes::ClearDisplay => "Clear the display".to_string(),
-            OpCodes::Return => format!("Return from {data_addr}"),
-            OpCodes::Jump => format!("Jump to {addr}"),
-            OpCodes::Call => format!("Call subroutine at {addr}"),
-            OpCodes::SkipIfEqualNum => format!("Skipping if {pre_vx} == {nn}{next_instr}"),
-            OpCodes::SkipIfNotEqualNum => format!("Skipping if {pre_vx} != {nn}{next_instr}"),
-            OpCodes::SkipIfEqualReg => format!("Skipping if {pre_vx} == {pre_vy}{next_instr}"),
-            OpCodes::SetRegFromNum => format!("Set {x} to {nn}"),
-            OpCodes::AddNumToReg => format!("Set {post_vx} to {pre_vx} + {nn}"),
-            OpCodes::SetRegFromReg => format!("Set {x} from {pre_vy}"),
-            OpCodes::BitwiseOr => format!("Set {post_vx} to {pre_vx} | {pre_vy}"),
-            OpCodes::BitwiseAnd => format!("Set {post_vx} to {pre_vx} & {pre_vy}"),
-            OpCodes::BitwiseXor => format!("Set {post_vx} to {pre_vx} ^ {pre_vy}"),
-            OpCodes::AddReg => format!("Set {post_vx} to {pre_vx} + {pre_vy}"),
-            OpCodes::SubRightReg => format!("Set {post_vx} to {pre_vx} - {pre_vy}"),
-            OpCodes::ShiftRight => {
+        let text = match *self {
+            SysCall { nnn } => format!("SysCall to {} (Unsupported)", addr(nnn)),
+            ClearDisplay => "Clear the display".to_string(),
+            Return => format!("Return from {data_addr}"),
+            Jump { nnn } => format!("Jump to {}", addr(nnn)),
+            Call { nnn } => format!("Call subroutine at {}", addr(nnn)),
+            SkipIfEqualNum { x, nn } => format!("Skipping if {} == {nn:02X}{next_instr}", pre_v(x)),
+            SkipIfNotEqualNum { x, nn } => {
+                format!("Skipping if {} != {nn:02X}{next_instr}", pre_v(x))
+            }
+            SkipIfEqualReg { x, y } => {
+                format!("Skipping if {} == {}{next_instr}", pre_v(x), pre_v(y))
+            }
+            SetRegFromNum { x, nn } => format!("Set {} to {nn:02X}", v(x)),
+            AddNumToReg { x, nn } => format!("Set {} to {} + {nn:02X}", post_v(x), pre_v(x)),
+            SetRegFromReg { x, y } => format!("Set {} from {}", v(x), pre_v(y)),
+            BitwiseOr { x, y } => format!("Set {} to {} | {}", post_v(x), pre_v(x), pre_v(y)),
+            BitwiseAnd { x, y } => format!("Set {} to {} & {}", post_v(x), pre_v(x), pre_v(y)),
+            BitwiseXor { x, y } => format!("Set {} to {} ^ {}", post_v(x), pre_v(x), pre_v(y)),
+            AddReg { x, y } => format!("Set {} to {} + {}", post_v(x), pre_v(x), pre_v(y)),
+            SubRightReg { x, y } => format!("Set {} to {} - {}", post_v(x), pre_v(x), pre_v(y)),
+            ShiftRight { x, .. } => {
                 format!(
-                    "Set {post_vx} to {pre_vx} >> 1, set VF ({:02X}) to first bit of {pre_vx}",
+                    "Set {0} to {1} >> 1, set VF ({2:02X}) to first bit of {1}",
+                    post_v(x),
+                    pre_v(x),
                     post_registers[15]
                 )
             }
-            OpCodes::SubLeftReg => format!("Set {post_vx} to {pre_vy} - {pre_vx}"),
-            OpCodes::ShiftLeft => {
+            SubLeftReg { x, y } => format!("Set {} to {} - {}", post_v(x), pre_v(y), pre_v(x)),
+            ShiftLeft { x, .. } => {
                 format!(
-                    "Set {post_vx} to {pre_vx} << 1, set VF ({:02X}) to first bit of {pre_vx}",
+                    "Set {0} to {1} << 1, set VF ({2:02X}) to first bit of {1}",
+                    post_v(x),
+                    pre_v(x),
                     post_registers[15]
                 )
             }
-            OpCodes::SkipIfNotEqualReg => format!("Skipping if {pre_vx} != {pre_vy}{next_instr}"),
-            OpCodes::SetMemReg => format!("Set I to {addr}"),
-            OpCodes::JumpOffset => format!("Jump to V0 ({:02X}) + {addr}", pre_registers[0]),
-            OpCodes::SetRegRand => format!("Set {post_vx} to {pre_vx} + rand ({data_byte}) & {nn}"),
-            OpCodes::DrawSprite => {
-                format!("Draw sprite at {pre_vx},{pre_vx} with {n} rows from {pre_mem_reg}")
+            SkipIfNotEqualReg { x, y } => {
+                format!("Skipping if {} != {}{next_instr}", pre_v(x), pre_v(y))
             }
-            OpCodes::SkipIfKeyPressed => {
-                format!("Skipping if key in {pre_vx} is pressed{next_instr}")
+            SetMemReg { nnn } => format!("Set I to {}", addr(nnn)),
+            JumpOffset { nnn } => format!("Jump to V0 ({:02X}) + {}", pre_registers[0], addr(nnn)),
+            SetRegRand { x, nn } => format!(
+                "Set {} to {} + rand ({data_byte}) & {nn:02X}",
+                post_v(x),
+                pre_v(x)
+            ),
+            DrawSprite { x, y, n } => {
+                format!(
+                    "Draw sprite at {},{} with {n:01X} rows from {pre_mem_reg}",
+                    pre_v(x),
+                    pre_v(y)
+                )
             }
-            OpCodes::SkipIfKeyNotPressed => {
-                format!("Skipping if key in {pre_vx} is not pressed{next_instr}")
+            SkipIfKeyPressed { x } => {
+                format!("Skipping if key in {} is pressed{next_instr}", pre_v(x))
             }
-            OpCodes::SetRegFromTimer => format!("Set {x} to delay timer ({data_byte})"),
-            OpCodes::WaitForKey => format!("Wait for key press, and store it in {x}"),
-            OpCodes::SetDelayTimer => format!("Set delay timer to {pre_vx}"),
-            OpCodes::SetSoundTimer => format!("Set sound timer to {pre_vx}"),
-            OpCodes::AddMemReg => format!("Set {post_mem_reg} to {pre_mem_reg} + {pre_vx}"),
-            OpCodes::SetMemRegToDigitSprite => {
-                format!("Set {post_mem_reg} to addr of digit {pre_vx}")
+            SkipIfKeyNotPressed { x } => {
+                format!("Skipping if key in {} is not pressed{next_instr}", pre_v(x))
             }
-            OpCodes::SetMemRegToAsciiSprite => {
-                format!("Set {post_mem_reg} to addr of ASCII {pre_vx}")
+            SetRegFromTimer { x } => format!("Set {} to delay timer ({data_byte})", v(x)),
+            WaitForKey { x } => format!("Wait for key press, and store it in {}", v(x)),
+            SetDelayTimer { x } => format!("Set delay timer to {}", pre_v(x)),
+            SetSoundTimer { x } => format!("Set sound timer to {}", pre_v(x)),
+            AddMemReg { x } => format!("Set {post_mem_reg} to {pre_mem_reg} + {}", pre_v(x)),
+            SetMemRegToDigitSprite { x } => {
+                format!("Set {post_mem_reg} to addr of digit {}", pre_v(x))
             }
-            OpCodes::StoreBcd => format!("Store {pre_vx} as BCD starting at {pre_mem_reg}"),
-            OpCodes::StoreRegs => format!("Store registers ({}) to {pre_mem_reg}", regs(x_value)),
-            OpCodes::LoadRegs => format!("Load registers ({}) from {pre_mem_reg}", regs(x_value)),
+            SetMemRegToAsciiSprite { x } => {
+                format!("Set {post_mem_reg} to addr of ASCII {}", pre_v(x))
+            }
+            StoreBcd { x } => format!("Store {} as BCD starting at {pre_mem_reg}", pre_v(x)),
+            StoreRegs { x } => format!("Store registers ({}) to {pre_mem_reg}", regs(x)),
+            LoadRegs { x } => format!("Load registers ({}) from {pre_mem_reg}", regs(x)),
+            Unknown(word) => format!("Unknown {word:04X}"),
         };
-        format!("[{pc}] {:02X}{:02X} {text}", bytes[0], bytes[1])
+        format!("[{pc}] {:04X} {text}", self.encode())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{OpCodes, REGISTER_COUNT};
+    use crate::instruction::Instruction;
+    use crate::symbols::{SymbolKind, Symbols};
+    use crate::REGISTER_COUNT;
 
     #[test]
     fn check_simple() {
-        let output = OpCodes::Jump.simple_describe([0x13, 0x34]);
+        let output = Instruction::Jump { nnn: 0x334 }.simple_describe();
         assert_eq!(output, "Jump to 334".to_string());
 
-        let output = OpCodes::AddNumToReg.simple_describe([0x7B, 0x9A]);
+        let output = Instruction::AddNumToReg { x: 0xB, nn: 0x9A }.simple_describe();
         assert_eq!(output, "Set VB to VB + 9A".to_string());
     }
 
+    #[test]
+    fn check_symbols() {
+        let mut symbols = Symbols::default();
+        symbols.insert(0x21A, "__loop_1_start", SymbolKind::Code);
+        symbols.insert(0x21C, "str", SymbolKind::Data);
+
+        let output = Instruction::Jump { nnn: 0x21A }.simple_describe_with(&symbols);
+        assert_eq!(output, "Jump to __loop_1_start (0x21A)");
+
+        let output = Instruction::SetMemReg { nnn: 0x21C }.describe(
+            [0; REGISTER_COUNT],
+            0,
+            [0; REGISTER_COUNT],
+            0x21C,
+            0x204,
+            0,
+            2,
+            &symbols,
+        );
+        assert_eq!(output, "[0204] A21C Set I to data `str` +0");
+    }
+
     #[test]
     fn check_full() {
-        let output = OpCodes::ClearDisplay.describe(
-            [0x00, 0xE0],
+        let output = Instruction::ClearDisplay.describe(
             [0; REGISTER_COUNT],
             0x12,
             [0; REGISTER_COUNT],
@@ -203,11 +213,11 @@ mod test {
             0x12,
             0,
             2,
+            &Symbols::default(),
         );
         assert_eq!(output, "[0012] 00E0 Clear the display");
 
-        let output = OpCodes::SetMemReg.describe(
-            [0xA0, 0x67],
+        let output = Instruction::SetMemReg { nnn: 0x067 }.describe(
             [0; REGISTER_COUNT],
             0x3AA,
             [0; REGISTER_COUNT],
@@ -215,11 +225,11 @@ mod test {
             0x3AA,
             0,
             2,
+            &Symbols::default(),
         );
         assert_eq!(output, "[03AA] A067 Set I to 067");
 
-        let output = OpCodes::AddReg.describe(
-            [0x83, 0x14],
+        let output = Instruction::AddReg { x: 3, y: 1 }.describe(
             [0, 0x67, 0, 0x34, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
             0,
             [0, 0x67, 0, 0x9B, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
@@ -227,6 +237,7 @@ mod test {
             0x9,
             0,
             2,
+            &Symbols::default(),
         );
         assert_eq!(output, "[0009] 8314 Set V3 (9B) to V3 (34) + V1 (67)");
     }
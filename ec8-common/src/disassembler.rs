@@ -0,0 +1,252 @@
+//! Disassembler
+//!
+//! Finds code by following every path from the start of the program (recursive traversal)
+//! - `jmp` continues at the target
+//! - `cal` continues at the target and after the call
+//! - skips continue at both the next and the following instruction
+//! - `ret` stops
+//! - `jpo` stops, the target depends on V0 so it is recorded in [Disassembly::unknown_jumps]
+//!
+//! Anything not reached is treated as data, words that were reached but aren't instructions
+//! are recorded in [Disassembly::invalid]
+//!
+//! [Disassembly::to_source] produces source for ec8-assembler that assembles to the same bytes,
+//! instructions the assembler can't produce (such as system calls) are written as data
+
+use crate::instruction::decode_strict;
+use crate::instruction::Instruction::*;
+use crate::PROG_START_ADDRESS;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Max number of bytes per `dat` line
+const DATA_LINE_LEN: usize = 8;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Disassembly {
+    /// Address of the first byte
+    pub start: u16,
+    pub bytes: Vec<u8>,
+    /// Address of every instruction reached from the start
+    pub code: BTreeSet<u16>,
+    /// Generated names for jump, call and I targets
+    pub labels: BTreeMap<u16, String>,
+    /// Addresses of `jpo` instructions, where the targets can't be found
+    pub unknown_jumps: Vec<u16>,
+    /// Addresses reached from the start that don't contain a valid instruction
+    pub invalid: BTreeSet<u16>,
+}
+
+/// Disassemble a program loaded at [PROG_START_ADDRESS]
+pub fn disassemble(program: &[u8]) -> Disassembly {
+    let start = PROG_START_ADDRESS;
+    let end = start as usize + program.len();
+    let mut code = BTreeSet::new();
+    let mut jump_targets = BTreeSet::new();
+    let mut call_targets = BTreeSet::new();
+    let mut data_targets = BTreeSet::new();
+    let mut unknown_jumps = vec![];
+    let mut invalid = BTreeSet::new();
+    let mut pending = vec![start];
+
+    while let Some(addr) = pending.pop() {
+        if addr < start || addr as usize + 2 > end || code.contains(&addr) {
+            continue;
+        }
+        let offset = (addr - start) as usize;
+        let instruction = decode_strict(&program[offset..]);
+        if let Unknown(_) = instruction {
+            invalid.insert(addr);
+            continue;
+        }
+        code.insert(addr);
+        match instruction {
+            Jump { nnn } => {
+                jump_targets.insert(nnn);
+                pending.push(nnn);
+            }
+            Call { nnn } => {
+                call_targets.insert(nnn);
+                pending.push(addr + 2);
+                pending.push(nnn);
+            }
+            Return => {}
+            JumpOffset { .. } => unknown_jumps.push(addr),
+            SkipIfEqualNum { .. }
+            | SkipIfNotEqualNum { .. }
+            | SkipIfEqualReg { .. }
+            | SkipIfNotEqualReg { .. }
+            | SkipIfKeyPressed { .. }
+            | SkipIfKeyNotPressed { .. } => {
+                pending.push(addr + 4);
+                pending.push(addr + 2);
+            }
+            SetMemReg { nnn } => {
+                data_targets.insert(nnn);
+                pending.push(addr + 2);
+            }
+            _ => pending.push(addr + 2),
+        }
+    }
+    unknown_jumps.sort();
+
+    let in_program = |addr: &&u16| **addr >= start && (**addr as usize) < end;
+    let mut labels = BTreeMap::new();
+    for addr in data_targets.iter().filter(in_program) {
+        if !code.contains(addr) {
+            labels.insert(*addr, format!("data_{addr:03X}"));
+        }
+    }
+    for addr in jump_targets.iter().filter(in_program) {
+        labels.insert(*addr, format!("label_{addr:03X}"));
+    }
+    for addr in call_targets.iter().filter(in_program) {
+        labels.insert(*addr, format!("sub_{addr:03X}"));
+    }
+
+    Disassembly {
+        start,
+        bytes: program.to_vec(),
+        code,
+        labels,
+        unknown_jumps,
+        invalid,
+    }
+}
+
+impl Disassembly {
+    /// Source for ec8-assembler
+    pub fn to_source(&self) -> String {
+        let lines = self.lines();
+        let starts: BTreeSet<u16> = lines.iter().map(|(addr, _)| *addr as u16).collect();
+        let mut output = String::new();
+        for (addr, len) in lines {
+            if let Some(label) = self.labels.get(&(addr as u16)) {
+                output.push_str(&format!("{label}:\n"));
+            }
+            let offset = addr - self.start as usize;
+            if self.code.contains(&(addr as u16)) {
+                output.push_str(&self.instruction(addr as u16, &self.bytes[offset..], &starts));
+            } else {
+                let data: String = self.bytes[offset..offset + len]
+                    .iter()
+                    .map(|byte| format!("{byte:02X}"))
+                    .collect();
+                output.push_str(&format!("dat [{data}]"));
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Address and length of each line of [Disassembly::to_source]
+    ///
+    /// Data lines end before code and labels, so every label that's not inside an instruction
+    /// starts a line
+    fn lines(&self) -> Vec<(usize, usize)> {
+        let end = self.start as usize + self.bytes.len();
+        let mut lines = vec![];
+        let mut addr = self.start as usize;
+        while addr < end {
+            let mut len = 2;
+            if !self.code.contains(&(addr as u16)) {
+                len = 1;
+                while addr + len < end && len < DATA_LINE_LEN {
+                    let next = (addr + len) as u16;
+                    if self.code.contains(&next) || self.labels.contains_key(&next) {
+                        break;
+                    }
+                    len += 1;
+                }
+            }
+            lines.push((addr, len));
+            addr += len;
+        }
+        lines
+    }
+
+    /// `starts` are the addresses that start a line, only labels at these are written
+    fn instruction(&self, addr: u16, bytes: &[u8], starts: &BTreeSet<u16>) -> String {
+        let instruction = decode_strict(bytes);
+        let Some(text) = instruction.to_asm() else {
+            return format!(
+                "dat [{:04X}] ;{}",
+                instruction.encode(),
+                instruction.simple_describe()
+            );
+        };
+        let label = match instruction {
+            Jump { nnn } | Call { nnn } | SetMemReg { nnn } if starts.contains(&nnn) => {
+                self.labels.get(&nnn)
+            }
+            _ => None,
+        };
+        match (label, instruction.opcode()) {
+            (Some(label), Some(opcode)) => format!("{} {label}", opcode.mnemonic()),
+            _ if self.unknown_jumps.contains(&addr) => format!("{text} ;target unknown"),
+            _ => text,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::disassembler::disassemble;
+
+    #[test]
+    fn check_traversal() {
+        //0x200 call 0x20A
+        //0x202 skip if V0 == 1
+        //0x204 jump 0x208
+        //0x206 I = 0x20E
+        //0x208 jump 0x208
+        //0x20A return
+        //0x20C data (0x0000 sys call, 0x4A)
+        let program = [
+            0x22, 0x0A, 0x30, 0x01, 0x12, 0x08, 0xA2, 0x0E, 0x12, 0x08, 0x00, 0xEE, 0x00, 0x00,
+            0x4A,
+        ];
+        let disassembly = disassemble(&program);
+        assert_eq!(
+            disassembly.code.iter().copied().collect::<Vec<u16>>(),
+            vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20A]
+        );
+        assert_eq!(
+            disassembly.to_source(),
+            "cal sub_20A
+ske v0, 01
+jmp label_208
+sti data_20E
+label_208:
+jmp label_208
+sub_20A:
+ret
+dat [0000]
+data_20E:
+dat [4A]
+"
+        );
+    }
+
+    #[test]
+    fn check_unsupported() {
+        //sys call, jpo 300, skip if V1 == V2 (with non zero last nibble)
+        let program = [0x01, 0x23, 0xB3, 0x00, 0x51, 0x23];
+        let disassembly = disassemble(&program);
+        assert_eq!(disassembly.unknown_jumps, vec![0x202]);
+        assert!(disassembly.invalid.is_empty());
+        assert_eq!(
+            disassembly.to_source(),
+            "dat [0123] ;SysCall to 123 (Unsupported)
+jpo 300 ;target unknown
+dat [5123]
+"
+        );
+        //jump into the middle of itself, the label can't be written
+        assert_eq!(disassemble(&[0x12, 0x01]).to_source(), "jmp 201\n");
+        let disassembly = disassemble(&[0x51, 0x23]);
+        assert_eq!(
+            disassembly.invalid.into_iter().collect::<Vec<u16>>(),
+            vec![0x200]
+        );
+    }
+}
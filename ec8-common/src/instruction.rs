@@ -0,0 +1,310 @@
+//! Decoded instructions
+//!
+//! [decode_strict] turns two bytes into an [Instruction] with typed operands and
+//! [Instruction::encode] turns it back into the same two bytes, both use the
+//! [opcode table](crate::definitions)
+//!
+//! [decode] is how ec8-core runs instructions, it ignores the unused nibble of 5xy0 and 9xy0
+//! and the x nibble of 00E0 and 00EE, so some words decode to instructions that encode
+//! differently
+//!
+//! Operands
+//! nnn = Address 0-FFF
+//! nn = 8 bit literal number 0-FF
+//! n = 4 bit literal number 0-F
+//! x or y = Register number 0-F
+//!
+//! Operands outside these ranges are masked when encoding
+
+use crate::definitions::{lookup, Definition, Operands};
+use crate::OpCodes;
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum Instruction {
+    /// 0nnn
+    SysCall { nnn: u16 },
+    /// 00E0
+    ClearDisplay,
+    /// 00EE
+    Return,
+    /// 1nnn
+    Jump { nnn: u16 },
+    /// 2nnn
+    Call { nnn: u16 },
+    /// 3xnn
+    SkipIfEqualNum { x: u8, nn: u8 },
+    /// 4xnn
+    SkipIfNotEqualNum { x: u8, nn: u8 },
+    /// 5xy0
+    SkipIfEqualReg { x: u8, y: u8 },
+    /// 6xnn
+    SetRegFromNum { x: u8, nn: u8 },
+    /// 7xnn
+    AddNumToReg { x: u8, nn: u8 },
+    /// 8xy0
+    SetRegFromReg { x: u8, y: u8 },
+    /// 8xy1
+    BitwiseOr { x: u8, y: u8 },
+    /// 8xy2
+    BitwiseAnd { x: u8, y: u8 },
+    /// 8xy3
+    BitwiseXor { x: u8, y: u8 },
+    /// 8xy4
+    AddReg { x: u8, y: u8 },
+    /// 8xy5
+    SubRightReg { x: u8, y: u8 },
+    /// 8xy6
+    ShiftRight { x: u8, y: u8 },
+    /// 8xy7
+    SubLeftReg { x: u8, y: u8 },
+    /// 8xyE
+    ShiftLeft { x: u8, y: u8 },
+    /// 9xy0
+    SkipIfNotEqualReg { x: u8, y: u8 },
+    /// Annn
+    SetMemReg { nnn: u16 },
+    /// Bnnn
+    JumpOffset { nnn: u16 },
+    /// Cxnn
+    SetRegRand { x: u8, nn: u8 },
+    /// Dxyn
+    DrawSprite { x: u8, y: u8, n: u8 },
+    /// Ex9E
+    SkipIfKeyPressed { x: u8 },
+    /// ExA1
+    SkipIfKeyNotPressed { x: u8 },
+    /// Fx07
+    SetRegFromTimer { x: u8 },
+    /// Fx0A
+    WaitForKey { x: u8 },
+    /// Fx15
+    SetDelayTimer { x: u8 },
+    /// Fx18
+    SetSoundTimer { x: u8 },
+    /// Fx1E
+    AddMemReg { x: u8 },
+    /// Fx29
+    SetMemRegToDigitSprite { x: u8 },
+    /// Fx30
+    SetMemRegToAsciiSprite { x: u8 },
+    /// Fx33
+    StoreBcd { x: u8 },
+    /// Fx55
+    StoreRegs { x: u8 },
+    /// Fx65
+    LoadRegs { x: u8 },
+    /// Any word that isn't a valid instruction
+    Unknown(u16),
+}
+
+/// Decode the first two bytes of `bytes` the way ec8-core runs them
+///
+/// 5xyN and 9xyN are skips for any N and 0nE0 and 0nEE are clear and return for any n
+///
+/// # Panics
+/// If `bytes` has less than two bytes
+pub fn decode(bytes: &[u8]) -> Instruction {
+    let word = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let word = match word & 0xF000 {
+        0x5000 | 0x9000 => word & 0xFFF0,
+        0x0000 if matches!(word & 0x00FF, 0xE0 | 0xEE) => word & 0x00FF,
+        _ => word,
+    };
+    decode_strict(&word.to_be_bytes())
+}
+
+/// Decode the first two bytes of `bytes`, only words that match the opcode table exactly are
+/// valid, see [decode]
+///
+/// # Panics
+/// If `bytes` has less than two bytes
+pub fn decode_strict(bytes: &[u8]) -> Instruction {
+    let word = u16::from_be_bytes([bytes[0], bytes[1]]);
+    match lookup(word) {
+        None => Instruction::Unknown(word),
+        Some(def) => Instruction::new(def.opcode, def.operands.extract(word)),
+    }
+}
+
+impl Instruction {
+    /// Operands not used by `opcode` are ignored
+    pub fn new(opcode: OpCodes, operands: Operands) -> Instruction {
+        use Instruction::*;
+
+        let Operands { x, y, n, nn, nnn } = operands;
+        match opcode {
+            OpCodes::SysCall => SysCall { nnn },
+            OpCodes::ClearDisplay => ClearDisplay,
+            OpCodes::Return => Return,
+            OpCodes::Jump => Jump { nnn },
+            OpCodes::Call => Call { nnn },
+            OpCodes::SkipIfEqualNum => SkipIfEqualNum { x, nn },
+            OpCodes::SkipIfNotEqualNum => SkipIfNotEqualNum { x, nn },
+            OpCodes::SkipIfEqualReg => SkipIfEqualReg { x, y },
+            OpCodes::SetRegFromNum => SetRegFromNum { x, nn },
+            OpCodes::AddNumToReg => AddNumToReg { x, nn },
+            OpCodes::SetRegFromReg => SetRegFromReg { x, y },
+            OpCodes::BitwiseOr => BitwiseOr { x, y },
+            OpCodes::BitwiseAnd => BitwiseAnd { x, y },
+            OpCodes::BitwiseXor => BitwiseXor { x, y },
+            OpCodes::AddReg => AddReg { x, y },
+            OpCodes::SubRightReg => SubRightReg { x, y },
+            OpCodes::ShiftRight => ShiftRight { x, y },
+            OpCodes::SubLeftReg => SubLeftReg { x, y },
+            OpCodes::ShiftLeft => ShiftLeft { x, y },
+            OpCodes::SkipIfNotEqualReg => SkipIfNotEqualReg { x, y },
+            OpCodes::SetMemReg => SetMemReg { nnn },
+            OpCodes::JumpOffset => JumpOffset { nnn },
+            OpCodes::SetRegRand => SetRegRand { x, nn },
+            OpCodes::DrawSprite => DrawSprite { x, y, n },
+            OpCodes::SkipIfKeyPressed => SkipIfKeyPressed { x },
+            OpCodes::SkipIfKeyNotPressed => SkipIfKeyNotPressed { x },
+            OpCodes::SetRegFromTimer => SetRegFromTimer { x },
+            OpCodes::WaitForKey => WaitForKey { x },
+            OpCodes::SetDelayTimer => SetDelayTimer { x },
+            OpCodes::SetSoundTimer => SetSoundTimer { x },
+            OpCodes::AddMemReg => AddMemReg { x },
+            OpCodes::SetMemRegToDigitSprite => SetMemRegToDigitSprite { x },
+            OpCodes::SetMemRegToAsciiSprite => SetMemRegToAsciiSprite { x },
+            OpCodes::StoreBcd => StoreBcd { x },
+            OpCodes::StoreRegs => StoreRegs { x },
+            OpCodes::LoadRegs => LoadRegs { x },
+        }
+    }
+
+    /// Opcode and operands, or None for [Instruction::Unknown]
+    pub fn parts(&self) -> Option<(OpCodes, Operands)> {
+        use Instruction::*;
+
+        let none = Operands::default();
+        let x_only = Operands::reg;
+        let xy = Operands::reg_reg;
+        let xnn = Operands::reg_byte;
+        let addr = Operands::address;
+        let parts = match *self {
+            SysCall { nnn } => (OpCodes::SysCall, addr(nnn)),
+            ClearDisplay => (OpCodes::ClearDisplay, none),
+            Return => (OpCodes::Return, none),
+            Jump { nnn } => (OpCodes::Jump, addr(nnn)),
+            Call { nnn } => (OpCodes::Call, addr(nnn)),
+            SkipIfEqualNum { x, nn } => (OpCodes::SkipIfEqualNum, xnn(x, nn)),
+            SkipIfNotEqualNum { x, nn } => (OpCodes::SkipIfNotEqualNum, xnn(x, nn)),
+            SkipIfEqualReg { x, y } => (OpCodes::SkipIfEqualReg, xy(x, y)),
+            SetRegFromNum { x, nn } => (OpCodes::SetRegFromNum, xnn(x, nn)),
+            AddNumToReg { x, nn } => (OpCodes::AddNumToReg, xnn(x, nn)),
+            SetRegFromReg { x, y } => (OpCodes::SetRegFromReg, xy(x, y)),
+            BitwiseOr { x, y } => (OpCodes::BitwiseOr, xy(x, y)),
+            BitwiseAnd { x, y } => (OpCodes::BitwiseAnd, xy(x, y)),
+            BitwiseXor { x, y } => (OpCodes::BitwiseXor, xy(x, y)),
+            AddReg { x, y } => (OpCodes::AddReg, xy(x, y)),
+            SubRightReg { x, y } => (OpCodes::SubRightReg, xy(x, y)),
+            ShiftRight { x, y } => (OpCodes::ShiftRight, xy(x, y)),
+            SubLeftReg { x, y } => (OpCodes::SubLeftReg, xy(x, y)),
+            ShiftLeft { x, y } => (OpCodes::ShiftLeft, xy(x, y)),
+            SkipIfNotEqualReg { x, y } => (OpCodes::SkipIfNotEqualReg, xy(x, y)),
+            SetMemReg { nnn } => (OpCodes::SetMemReg, addr(nnn)),
+            JumpOffset { nnn } => (OpCodes::JumpOffset, addr(nnn)),
+            SetRegRand { x, nn } => (OpCodes::SetRegRand, xnn(x, nn)),
+            DrawSprite { x, y, n } => (OpCodes::DrawSprite, Operands::reg_reg_nibble(x, y, n)),
+            SkipIfKeyPressed { x } => (OpCodes::SkipIfKeyPressed, x_only(x)),
+            SkipIfKeyNotPressed { x } => (OpCodes::SkipIfKeyNotPressed, x_only(x)),
+            SetRegFromTimer { x } => (OpCodes::SetRegFromTimer, x_only(x)),
+            WaitForKey { x } => (OpCodes::WaitForKey, x_only(x)),
+            SetDelayTimer { x } => (OpCodes::SetDelayTimer, x_only(x)),
+            SetSoundTimer { x } => (OpCodes::SetSoundTimer, x_only(x)),
+            AddMemReg { x } => (OpCodes::AddMemReg, x_only(x)),
+            SetMemRegToDigitSprite { x } => (OpCodes::SetMemRegToDigitSprite, x_only(x)),
+            SetMemRegToAsciiSprite { x } => (OpCodes::SetMemRegToAsciiSprite, x_only(x)),
+            StoreBcd { x } => (OpCodes::StoreBcd, x_only(x)),
+            StoreRegs { x } => (OpCodes::StoreRegs, x_only(x)),
+            LoadRegs { x } => (OpCodes::LoadRegs, x_only(x)),
+            Unknown(_) => return None,
+        };
+        Some(parts)
+    }
+
+    pub fn encode(&self) -> u16 {
+        match (self, self.parts()) {
+            (_, Some((opcode, operands))) => opcode.definition().encode(operands),
+            (Instruction::Unknown(word), None) => *word,
+            (_, None) => unreachable!("Only unknown instructions have no parts"),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 2] {
+        self.encode().to_be_bytes()
+    }
+
+    /// Returns None for [Instruction::Unknown]
+    pub fn opcode(&self) -> Option<OpCodes> {
+        self.parts().map(|(opcode, _)| opcode)
+    }
+
+    /// Returns None for [Instruction::Unknown]
+    pub fn definition(&self) -> Option<&'static Definition> {
+        self.opcode().map(|opcode| opcode.definition())
+    }
+}
+
+impl Definition {
+    pub fn instruction(&self, operands: Operands) -> Instruction {
+        Instruction::new(self.opcode, operands)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruction::{decode, decode_strict, Instruction};
+
+    #[test]
+    fn check_round_trip() {
+        for word in 0..=u16::MAX {
+            let instruction = decode_strict(&word.to_be_bytes());
+            assert_eq!(instruction.encode(), word, "{word:04X} {instruction:?}");
+            assert_eq!(
+                decode_strict(&instruction.to_bytes()),
+                instruction,
+                "{word:04X}"
+            );
+        }
+    }
+
+    #[test]
+    fn check_decode() {
+        assert_eq!(
+            decode(&[0xD1, 0x25, 0xFF]),
+            Instruction::DrawSprite { x: 1, y: 2, n: 5 }
+        );
+        assert_eq!(decode(&[0x2A, 0xBC]), Instruction::Call { nnn: 0xABC });
+        assert_eq!(
+            decode(&[0x51, 0x21]),
+            Instruction::SkipIfEqualReg { x: 1, y: 2 }
+        );
+        assert_eq!(decode_strict(&[0x51, 0x21]), Instruction::Unknown(0x5121));
+        assert_eq!(
+            decode(&[0x9A, 0xBF]),
+            Instruction::SkipIfNotEqualReg { x: 0xA, y: 0xB }
+        );
+        assert_eq!(decode(&[0x03, 0xEE]), Instruction::Return);
+        assert_eq!(
+            decode_strict(&[0x03, 0xEE]),
+            Instruction::SysCall { nnn: 0x3EE }
+        );
+        assert_eq!(decode(&[0x13, 0xEE]), Instruction::Jump { nnn: 0x3EE });
+        assert_eq!(
+            decode(&[0xF3, 0x30]),
+            Instruction::SetMemRegToAsciiSprite { x: 3 }
+        );
+        assert_eq!(decode(&[0x00, 0xE0]), Instruction::ClearDisplay);
+        assert_eq!(decode(&[0x01, 0x23]), Instruction::SysCall { nnn: 0x123 });
+    }
+
+    #[test]
+    fn check_encode_masks() {
+        assert_eq!(Instruction::Jump { nnn: 0xF123 }.encode(), 0x1123);
+        assert_eq!(
+            Instruction::SetRegFromNum { x: 0x1F, nn: 2 }.encode(),
+            0x6F02
+        );
+    }
+}
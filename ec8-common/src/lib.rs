@@ -9,12 +9,21 @@
 //! Inaccessible registers
 //! PC = Program Counter, 16 bit
 
+pub mod debug_info;
+pub mod definitions;
 pub mod describe;
+pub mod disassembler;
 pub mod error;
 pub mod graphics;
+pub mod instruction;
 pub mod nibbler;
 pub mod opcodes;
 pub mod mnemonics;
+pub mod sprite;
+pub mod symbols;
+pub mod transpiler;
+
+use crate::definitions::Platform;
 
 pub const MAX_ADDRESS: u16 = 0xFFF;
 pub const PROG_START_ADDRESS: u16 = 0x200;
@@ -150,7 +159,7 @@ pub enum OpCodes {
     ///
     /// Set I to address of sprite for hex digit (5 lines)
     SetMemRegToDigitSprite,
-    /// Fx31
+    /// Fx30
     ///
     /// Set I to address of sprite for ASCII value
     SetMemRegToAsciiSprite,
@@ -170,6 +179,6 @@ pub enum OpCodes {
 
 impl OpCodes {
     pub fn is_ec8_only(&self) -> bool {
-        matches!(self, OpCodes::SetMemRegToAsciiSprite)
+        self.definition().platform == Platform::Ec8
     }
 }
\ No newline at end of file
@@ -1,44 +1,25 @@
+use crate::instruction::Instruction;
 use crate::OpCodes;
 
 impl OpCodes {
     pub fn mnemonic(&self) -> &'static str {
-        match self {
-            OpCodes::SysCall => "",
-            OpCodes::ClearDisplay => "clr",
-            OpCodes::Return => "ret",
-            OpCodes::Jump => "jmp",
-            OpCodes::Call => "cal",
-            OpCodes::SkipIfEqualNum => "ske",
-            OpCodes::SkipIfNotEqualNum => "skn",
-            OpCodes::SkipIfEqualReg => "ske",
-            OpCodes::SetRegFromNum => "set",
-            OpCodes::AddNumToReg => "add",
-            OpCodes::SetRegFromReg => "set",
-            OpCodes::BitwiseOr => "or",
-            OpCodes::BitwiseAnd => "and",
-            OpCodes::BitwiseXor => "xor",
-            OpCodes::AddReg => "add",
-            OpCodes::SubRightReg => "sub",
-            OpCodes::ShiftRight => "shr",
-            OpCodes::SubLeftReg => "sbr",
-            OpCodes::ShiftLeft => "shl",
-            OpCodes::SkipIfNotEqualReg => "skn",
-            OpCodes::SetMemReg => "sti",
-            OpCodes::JumpOffset => "jpo",
-            OpCodes::SetRegRand => "rnd",
-            OpCodes::DrawSprite => "drw",
-            OpCodes::SkipIfKeyPressed => "skp",
-            OpCodes::SkipIfKeyNotPressed => "skr",
-            OpCodes::SetRegFromTimer => "rdt",
-            OpCodes::WaitForKey => "key",
-            OpCodes::SetDelayTimer => "sdt",
-            OpCodes::SetSoundTimer => "sst",
-            OpCodes::AddMemReg => "adi",
-            OpCodes::SetMemRegToDigitSprite => "chr",
-            OpCodes::SetMemRegToAsciiSprite => "asc",
-            OpCodes::StoreBcd => "bcd",
-            OpCodes::StoreRegs => "str",
-            OpCodes::LoadRegs => "ldr"
+        self.definition().mnemonic
+    }
+}
+
+impl Instruction {
+    /// Source for ec8-assembler, or None if the assembler can't produce these bytes
+    pub fn to_asm(&self) -> Option<String> {
+        let (opcode, operands) = self.parts()?;
+        let def = opcode.definition();
+        if def.mnemonic.is_empty() {
+            return None;
+        }
+        let operands = def.operands.to_asm(operands);
+        if operands.is_empty() {
+            Some(def.mnemonic.to_string())
+        } else {
+            Some(format!("{} {operands}", def.mnemonic))
         }
     }
-}
\ No newline at end of file
+}
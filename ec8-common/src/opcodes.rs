@@ -1,89 +1,21 @@
+use crate::definitions::OperandKind::*;
+use crate::definitions::{OperandKind, Operands};
 use crate::error::ECommonError::*;
 use crate::error::ECommonResult;
-use crate::nibbler::Nibbler;
+use crate::instruction::decode;
 use crate::OpCodes;
-use crate::OpCodes::*;
 
+/// Returns None if `bytes` isn't a valid instruction, see [decode]
 pub fn from_bytes(bytes: [u8; 2]) -> Option<OpCodes> {
-    let first_nibble = bytes[0].first_nibble();
-    let last_nibble = bytes[1].second_nibble();
-    match first_nibble {
-        0x00 => match bytes[1] {
-            0xE0 => Some(ClearDisplay),
-            0xEE => Some(Return),
-            _ => Some(SysCall),
-        },
-        0x10 => Some(Jump),
-        0x20 => Some(Call),
-        0x30 => Some(SkipIfEqualNum),
-        0x40 => Some(SkipIfNotEqualNum),
-        0x50 => Some(SkipIfEqualReg),
-        0x60 => Some(SetRegFromNum),
-        0x70 => Some(AddNumToReg),
-        0x80 => match last_nibble {
-            0x0 => Some(SetRegFromReg),
-            0x1 => Some(BitwiseOr),
-            0x2 => Some(BitwiseAnd),
-            0x3 => Some(BitwiseXor),
-            0x4 => Some(AddReg),
-            0x5 => Some(SubRightReg),
-            0x6 => Some(ShiftRight),
-            0x7 => Some(SubLeftReg),
-            0xE => Some(ShiftLeft),
-            _ => None,
-        },
-        0x90 => Some(SkipIfNotEqualReg),
-        0xA0 => Some(SetMemReg),
-        0xB0 => Some(JumpOffset),
-        0xC0 => Some(SetRegRand),
-        0xD0 => Some(DrawSprite),
-        0xE0 => match bytes[1] {
-            0x9E => Some(SkipIfKeyPressed),
-            0xA1 => Some(SkipIfKeyNotPressed),
-            _ => None,
-        },
-        0xF0 => match bytes[1] {
-            0x07 => Some(SetRegFromTimer),
-            0x0A => Some(WaitForKey),
-            0x15 => Some(SetDelayTimer),
-            0x18 => Some(SetSoundTimer),
-            0x1E => Some(AddMemReg),
-            0x29 => Some(SetMemRegToDigitSprite),
-            0x30 => Some(SetMemRegToAsciiSprite),
-            0x33 => Some(StoreBcd),
-            0x55 => Some(StoreRegs),
-            0x65 => Some(LoadRegs),
-            _ => None,
-        },
-        _ => None,
-    }
+    decode(&bytes).opcode()
 }
 
 pub fn no_param(opcode: OpCodes) -> ECommonResult<u16> {
-    match opcode {
-        ClearDisplay => Ok(0x00E0),
-        Return => Ok(0x00EE),
-        _ => Err(InvalidOpCode(opcode)),
-    }
+    encode(opcode, Empty, Operands::default())
 }
 
 pub fn reg(opcode: OpCodes, reg: u8) -> ECommonResult<u16> {
-    let x = cast_first_reg(reg);
-    match opcode {
-        SkipIfKeyPressed => Ok(0xE09E | x),
-        SkipIfKeyNotPressed => Ok(0xE0A1 | x),
-        SetRegFromTimer => Ok(0xF007 | x),
-        WaitForKey => Ok(0xF00A | x),
-        SetDelayTimer => Ok(0xF015 | x),
-        SetSoundTimer => Ok(0xF018 | x),
-        AddMemReg => Ok(0xF01E | x),
-        SetMemRegToDigitSprite => Ok(0xF029 | x),
-        SetMemRegToAsciiSprite => Ok(0xF030 | x),
-        StoreBcd => Ok(0xF033 | x),
-        StoreRegs => Ok(0xF055 | x),
-        LoadRegs => Ok(0xF065 | x),
-        _ => Err(InvalidOpCode(opcode)),
-    }
+    encode(opcode, Reg, Operands::reg(reg))
 }
 
 pub fn reg_reg_num(opcode: OpCodes, reg_x: u8, reg_y: u8, num: u8) -> ECommonResult<u16> {
@@ -94,45 +26,19 @@ pub fn reg_reg_num(opcode: OpCodes, reg_x: u8, reg_y: u8, num: u8) -> ECommonRes
 }
 
 pub fn reg_reg_num_unchecked(opcode: OpCodes, reg_x: u8, reg_y: u8, num: u8) -> ECommonResult<u16> {
-    let x = cast_first_reg(reg_x);
-    let y = cast_second_reg(reg_y);
-    let n = cast_4bit_num(num);
-    match opcode {
-        DrawSprite => Ok(((0xD000 | x) | y) | n),
-        _ => Err(InvalidOpCode(opcode)),
-    }
+    encode(
+        opcode,
+        RegRegNibble,
+        Operands::reg_reg_nibble(reg_x, reg_y, num),
+    )
 }
 
 pub fn reg_reg(opcode: OpCodes, reg_x: u8, reg_y: u8) -> ECommonResult<u16> {
-    let x = cast_first_reg(reg_x);
-    let y = cast_second_reg(reg_y);
-    match opcode {
-        SkipIfEqualReg => Ok((0x5000 | x) | y),
-        SetRegFromReg => Ok((0x8000 | x) | y),
-        BitwiseOr => Ok((0x8001 | x) | y),
-        BitwiseAnd => Ok((0x8002 | x) | y),
-        BitwiseXor => Ok((0x8003 | x) | y),
-        AddReg => Ok((0x8004 | x) | y),
-        SubRightReg => Ok((0x8005 | x) | y),
-        ShiftRight => Ok((0x8006 | x) | y),
-        SubLeftReg => Ok((0x8007 | x) | y),
-        ShiftLeft => Ok((0x800E | x) | y),
-        SkipIfNotEqualReg => Ok((0x9000 | x) | y),
-        _ => Err(InvalidOpCode(opcode)),
-    }
+    encode(opcode, RegReg, Operands::reg_reg(reg_x, reg_y))
 }
 
 pub fn reg_num(opcode: OpCodes, reg_x: u8, num: u8) -> ECommonResult<u16> {
-    let x = cast_first_reg(reg_x);
-    let n = cast_8bit_num(num);
-    match opcode {
-        SkipIfEqualNum => Ok((0x3000 | x) | n),
-        SkipIfNotEqualNum => Ok((0x4000 | x) | n),
-        SetRegFromNum => Ok((0x6000 | x) | n),
-        AddNumToReg => Ok((0x7000 | x) | n),
-        SetRegRand => Ok((0xC000 | x) | n),
-        _ => Err(InvalidOpCode(opcode)),
-    }
+    encode(opcode, RegByte, Operands::reg_byte(reg_x, num))
 }
 
 pub fn address(opcode: OpCodes, address: u16) -> ECommonResult<u16> {
@@ -143,36 +49,38 @@ pub fn address(opcode: OpCodes, address: u16) -> ECommonResult<u16> {
 }
 
 pub fn address_unchecked(opcode: OpCodes, address: u16) -> ECommonResult<u16> {
-    let addr = address & 0xFFF;
-    match opcode {
-        SysCall => Ok(addr),
-        Jump => Ok(0x1000 | addr),
-        Call => Ok(0x2000 | addr),
-        SetMemReg => Ok(0xA000 | addr),
-        JumpOffset => Ok(0xB000 | addr),
-        _ => Err(InvalidOpCode(opcode)),
+    encode(opcode, Address, Operands::address(address))
+}
+
+/// Encode `opcode` if it takes `kind` operands
+fn encode(opcode: OpCodes, kind: OperandKind, operands: Operands) -> ECommonResult<u16> {
+    let def = opcode.definition();
+    if def.operands != kind {
+        return Err(InvalidOpCode(opcode));
     }
+    Ok(def.encode(operands))
 }
 
-fn cast_4bit_num(num: u8) -> u16 {
+pub(crate) fn cast_4bit_num(num: u8) -> u16 {
     (num as u16) & 0x000F
 }
 
-fn cast_8bit_num(num: u8) -> u16 {
+pub(crate) fn cast_8bit_num(num: u8) -> u16 {
     (num as u16) & 0x00FF
 }
 
-fn cast_first_reg(num: u8) -> u16 {
+pub(crate) fn cast_first_reg(num: u8) -> u16 {
     ((num as u16) << 8) & 0x0F00
 }
 
-fn cast_second_reg(num: u8) -> u16 {
+pub(crate) fn cast_second_reg(num: u8) -> u16 {
     ((num as u16) << 4) & 0x00F0
 }
 
 #[cfg(test)]
 mod test {
     use crate::opcodes::*;
+    use crate::OpCodes::*;
 
     #[test]
     fn check_from_byte() {
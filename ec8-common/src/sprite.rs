@@ -0,0 +1,204 @@
+//! Sprites
+//!
+//! Each byte of a sprite is a row of 8 pixels, the highest bit is the leftmost pixel
+//!
+//! Chip-8 sprites are 8 pixels wide and up to 15 rows tall (`drw vX, vY, n`)
+//! SCHIP sprites are 16x16 and each row is two bytes (`drw vX, vY, 0`)
+
+/// Max rows for a Chip-8 sprite
+pub const MAX_SPRITE_ROWS: usize = 15;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SpriteSize {
+    /// 8 pixels wide, up to 15 rows
+    Chip8,
+    /// 16x16
+    Schip,
+}
+
+impl SpriteSize {
+    pub fn width(&self) -> usize {
+        match self {
+            SpriteSize::Chip8 => 8,
+            SpriteSize::Schip => 16,
+        }
+    }
+
+    pub fn max_rows(&self) -> usize {
+        match self {
+            SpriteSize::Chip8 => MAX_SPRITE_ROWS,
+            SpriteSize::Schip => 16,
+        }
+    }
+}
+
+/// Black and white image, stored row by row
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /// Returns false if `x` or `y` is outside the image
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    /// # Panics
+    /// If `x` or `y` is outside the image
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        assert!(
+            x < self.width && y < self.height,
+            "{x},{y} is outside image"
+        );
+        self.pixels[y * self.width + x] = on;
+    }
+
+    /// Split into sprites, left to right then top to bottom
+    ///
+    /// The right edge is padded with unset pixels, as are the bottom rows for SCHIP
+    pub fn to_sprites(&self, size: SpriteSize) -> Vec<Vec<u8>> {
+        let width = size.width();
+        let rows = size.max_rows();
+        let mut sprites = vec![];
+        for left in (0..self.width).step_by(width) {
+            for top in (0..self.height).step_by(rows) {
+                let bottom = match size {
+                    SpriteSize::Chip8 => self.height.min(top + rows),
+                    SpriteSize::Schip => top + rows,
+                };
+                let mut sprite = vec![];
+                for y in top..bottom {
+                    for byte_left in (left..left + width).step_by(8) {
+                        let mut byte = 0;
+                        for bit in 0..8 {
+                            if self.get(byte_left + bit, y) {
+                                byte |= 0x80 >> bit;
+                            }
+                        }
+                        sprite.push(byte);
+                    }
+                }
+                sprites.push(sprite);
+            }
+        }
+        sprites
+    }
+
+    /// Image of a single sprite, for SCHIP an odd last byte is ignored
+    pub fn from_sprite(bytes: &[u8], size: SpriteSize) -> Bitmap {
+        let row_bytes = size.width() / 8;
+        let mut bitmap = Bitmap::new(size.width(), bytes.len() / row_bytes);
+        for (y, row) in bytes.chunks_exact(row_bytes).enumerate() {
+            for (i, byte) in row.iter().enumerate() {
+                for bit in 0..8 {
+                    bitmap.set(i * 8 + bit, y, byte & (0x80 >> bit) != 0);
+                }
+            }
+        }
+        bitmap
+    }
+}
+
+/// Names for each sprite, `name` if there's only one otherwise `name_0`, `name_1`, etc
+fn names(name: &str, count: usize) -> Vec<String> {
+    if count == 1 {
+        vec![name.to_string()]
+    } else {
+        (0..count).map(|i| format!("{name}_{i}")).collect()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+/// Source for ec8-assembler, a label with the name followed by `dat` for each sprite
+pub fn to_asm(name: &str, sprites: &[Vec<u8>]) -> String {
+    names(name, sprites.len())
+        .iter()
+        .zip(sprites)
+        .map(|(name, sprite)| format!("{name}:\ndat [{}]\n", hex(sprite)))
+        .collect()
+}
+
+/// Source for ec8-ll-compiler, `data` for each sprite
+pub fn to_ll(name: &str, sprites: &[Vec<u8>]) -> String {
+    names(name, sprites.len())
+        .iter()
+        .zip(sprites)
+        .map(|(name, sprite)| format!("data {name} {}\n", hex(sprite)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::graphics::ALPHA_MEMORY;
+    use crate::sprite::{to_asm, to_ll, Bitmap, SpriteSize};
+
+    fn bitmap(rows: &[&str]) -> Bitmap {
+        let mut bitmap = Bitmap::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, chr) in row.chars().enumerate() {
+                bitmap.set(x, y, chr == '#');
+            }
+        }
+        bitmap
+    }
+
+    #[test]
+    fn check_to_sprites() {
+        let zero = bitmap(&["####", "#..#", "#..#", "#..#", "####"]);
+        assert_eq!(
+            zero.to_sprites(SpriteSize::Chip8),
+            vec![ALPHA_MEMORY[0..5].to_vec()]
+        );
+
+        //10x16 splits into two columns, each with 15 rows then 1 row
+        let mut large = Bitmap::new(10, 16);
+        large.set(0, 0, true);
+        large.set(9, 15, true);
+        let sprites = large.to_sprites(SpriteSize::Chip8);
+        assert_eq!(sprites.len(), 4);
+        assert_eq!(sprites[0].len(), 15);
+        assert_eq!(sprites[0][0], 0x80);
+        assert_eq!(sprites[3], vec![0x40]);
+
+        let sprites = large.to_sprites(SpriteSize::Schip);
+        assert_eq!(sprites.len(), 1);
+        assert_eq!(sprites[0].len(), 32);
+        assert_eq!(&sprites[0][0..2], &[0x80, 0x00]);
+        assert_eq!(&sprites[0][30..32], &[0x00, 0x40]);
+    }
+
+    #[test]
+    fn check_from_sprite() {
+        let bitmap = Bitmap::from_sprite(&[0x81, 0x00, 0xFF], SpriteSize::Schip);
+        assert_eq!((bitmap.width, bitmap.height), (16, 1));
+        assert!(bitmap.get(0, 0) && bitmap.get(7, 0) && !bitmap.get(8, 0));
+
+        let sprite = ALPHA_MEMORY[5..10].to_vec();
+        let bitmap = Bitmap::from_sprite(&sprite, SpriteSize::Chip8);
+        assert_eq!(bitmap.to_sprites(SpriteSize::Chip8), vec![sprite]);
+    }
+
+    #[test]
+    fn check_source() {
+        let sprites = vec![vec![0xF0, 0x90], vec![0x01]];
+        assert_eq!(
+            to_asm("ship", &sprites),
+            "ship_0:\ndat [F090]\nship_1:\ndat [01]\n"
+        );
+        assert_eq!(to_ll("ship", &sprites[1..]), "data ship 01\n");
+    }
+}
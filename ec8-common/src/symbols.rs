@@ -0,0 +1,202 @@
+//! Symbols
+//!
+//! Names for addresses in a program, used to describe instructions
+//!
+//! Symbols files are written by ec8-ll-compiler, symbols can also be made from the debug info
+//! written by ec8-assembler and ec8-ll-compiler (see [DebugInfo::symbols](crate::debug_info::DebugInfo::symbols))
+//!
+//! Symbols file format
+//! ```text
+//! ;Comments start with a semicolon
+//! 21A code __loop_1_start
+//! 21C data str
+//! ```
+//! Each line is `address kind name`, the address is in hex and kind is `code` or `data`
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SymbolKind {
+    /// Label for an instruction
+    Code,
+    /// Name of a data block
+    Data,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Number of bytes, if known
+    pub len: Option<u16>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Symbols {
+    symbols: BTreeMap<u16, Symbol>,
+}
+
+impl Symbols {
+    pub const fn new() -> Self {
+        Self {
+            symbols: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, addr: u16, name: &str, kind: SymbolKind) {
+        self.symbols.insert(
+            addr,
+            Symbol {
+                name: name.to_string(),
+                kind,
+                len: None,
+            },
+        );
+    }
+
+    /// Insert a symbol that covers `len` bytes
+    pub fn insert_sized(&mut self, addr: u16, name: &str, kind: SymbolKind, len: u16) {
+        self.symbols.insert(
+            addr,
+            Symbol {
+                name: name.to_string(),
+                kind,
+                len: Some(len),
+            },
+        );
+    }
+
+    pub fn get(&self, addr: u16) -> Option<&Symbol> {
+        self.symbols.get(&addr)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u16, &Symbol)> {
+        self.symbols.iter()
+    }
+
+    /// Format `addr` using the nearest symbol at or before it, if `addr` is inside that symbol
+    ///
+    /// A symbol ends after its length, or at the next symbol if the length isn't known (so the
+    /// last symbol only covers its own address)
+    ///
+    /// Code is formatted as `name (0x21A)` or `name+4 (0x21E)`, data as ``data `name` +4``,
+    /// anything else as plain hex
+    pub fn format(&self, addr: u16) -> String {
+        let is_last = |symbol_addr: u16| self.symbols.range(symbol_addr..).nth(1).is_none();
+        let symbol = self
+            .symbols
+            .range(..=addr)
+            .next_back()
+            .filter(|(symbol_addr, symbol)| {
+                let offset = addr - **symbol_addr;
+                match symbol.len {
+                    Some(len) => offset < len,
+                    None => offset == 0 || !is_last(**symbol_addr),
+                }
+            });
+        match symbol {
+            None => format!("{addr:03X}"),
+            Some((symbol_addr, symbol)) => {
+                let offset = addr - symbol_addr;
+                match (symbol.kind, offset) {
+                    (SymbolKind::Code, 0) => format!("{} (0x{addr:03X})", symbol.name),
+                    (SymbolKind::Code, _) => format!("{}+{offset} (0x{addr:03X})", symbol.name),
+                    (SymbolKind::Data, _) => format!("data `{}` +{offset}", symbol.name),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Symbols {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut symbols = Symbols::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [addr, kind, name] = parts.as_slice() else {
+                return Err(format!("Line {}) Expected 'address kind name'", i + 1));
+            };
+            let addr = u16::from_str_radix(addr, 16)
+                .map_err(|_| format!("Line {}) Invalid address '{addr}'", i + 1))?;
+            let kind = match *kind {
+                "code" => SymbolKind::Code,
+                "data" => SymbolKind::Data,
+                _ => return Err(format!("Line {}) Invalid kind '{kind}'", i + 1)),
+            };
+            symbols.insert(addr, name, kind);
+        }
+        Ok(symbols)
+    }
+}
+
+impl Display for Symbols {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (addr, symbol) in &self.symbols {
+            let kind = match symbol.kind {
+                SymbolKind::Code => "code",
+                SymbolKind::Data => "data",
+            };
+            writeln!(f, "{addr:03X} {kind} {}", symbol.name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::symbols::{SymbolKind, Symbols};
+
+    #[test]
+    fn check_format() {
+        let mut symbols = Symbols::default();
+        symbols.insert(0x21A, "__loop_1_start", SymbolKind::Code);
+        symbols.insert(0x21C, "str", SymbolKind::Data);
+        assert_eq!(symbols.format(0x200), "200");
+        assert_eq!(symbols.format(0x21A), "__loop_1_start (0x21A)");
+        assert_eq!(symbols.format(0x21B), "__loop_1_start+1 (0x21B)");
+        assert_eq!(symbols.format(0x21C), "data `str` +0");
+        assert_eq!(symbols.format(0x220), "220");
+    }
+
+    #[test]
+    fn check_format_past_end() {
+        let mut symbols = Symbols::default();
+        symbols.insert(0x200, "start", SymbolKind::Code);
+        symbols.insert_sized(0x210, "str", SymbolKind::Data, 4);
+        assert_eq!(symbols.format(0x20E), "start+14 (0x20E)");
+        assert_eq!(symbols.format(0x213), "data `str` +3");
+        assert_eq!(symbols.format(0x214), "214");
+        assert_eq!(symbols.format(0xFFF), "FFF");
+    }
+
+    #[test]
+    fn check_file() {
+        let symbols = "21A code start\n;comment\n21c data str ;text"
+            .parse::<Symbols>()
+            .unwrap();
+        assert_eq!(symbols.get(0x21C).unwrap().kind, SymbolKind::Data);
+        assert_eq!(symbols.to_string(), "21A code start\n21C data str\n");
+        assert_eq!(symbols.to_string().parse::<Symbols>().unwrap(), symbols);
+
+        assert_eq!(
+            "21A code".parse::<Symbols>().unwrap_err(),
+            "Line 1) Expected 'address kind name'"
+        );
+        assert_eq!(
+            "\n21A label start".parse::<Symbols>().unwrap_err(),
+            "Line 2) Invalid kind 'label'"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Transpiler
+//!
+//! Converts EC8 programs into Chip-8 programs by replacing ASC (`Fx30`)
+//!
+//! The output starts with a jump over a block containing a subroutine for each register used
+//! with ASC and a copy of the ASCII glyphs, followed by the original program moved up by the size
+//! of the block. Each `Fx30` is replaced by a call to the subroutine for `x`, which sets I to the
+//! table address for code 0 then adds Vx to I five times (as each glyph is five bytes).
+//!
+//! Only code found by the [disassembler](crate::disassembler) is updated, so the addresses in
+//! `jmp`, `cal`, `sti` and `jpo` are moved but code only reached through `jpo` and addresses
+//! calculated at runtime aren't. Addresses past the end of the program are moved as well, as
+//! they are often used for variables
+//!
+//! The table covers every ASCII code (0 to 7F), codes without a glyph are drawn as blank instead
+//! of stopping the program. Codes above 7F aren't ASCII and point past the table
+
+use crate::disassembler::disassemble;
+use crate::error::{ECommonError, ECommonResult};
+use crate::graphics::{alpha_index, ALPHA_MEMORY};
+use crate::instruction::{decode_strict, Instruction};
+use crate::{ALPHA_BYTES, MAX_ADDRESS, PROG_START_ADDRESS};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Transpiled {
+    pub bytes: Vec<u8>,
+    /// Addresses (in the original program) of each replaced ASC
+    pub replaced: Vec<u16>,
+    /// Addresses (in the original program) of `jpo` instructions, code reached from these may
+    /// not have been updated
+    pub unknown_jumps: Vec<u16>,
+}
+
+/// Number of ASCII codes, the size of the glyph table
+const ASCII_CODES: u8 = 0x80;
+
+/// Glyphs for every ASCII code, codes without a glyph are blank
+fn glyph_table() -> Vec<u8> {
+    (0..ASCII_CODES)
+        .flat_map(|code| match alpha_index(code as char) {
+            Some(idx) => ALPHA_MEMORY[idx * ALPHA_BYTES..(idx + 1) * ALPHA_BYTES].to_vec(),
+            None => vec![0; ALPHA_BYTES],
+        })
+        .collect()
+}
+
+/// Replace every ASC in `program` (loaded at [PROG_START_ADDRESS]) with Chip-8 instructions
+///
+/// Returns the program unchanged if it doesn't use ASC
+///
+/// # Errors
+/// If the program or a moved address would be past [MAX_ADDRESS]
+pub fn transpile(program: &[u8]) -> ECommonResult<Transpiled> {
+    let disassembly = disassemble(program);
+    let start = PROG_START_ADDRESS;
+    let mut registers = BTreeMap::new();
+    let mut replaced = vec![];
+    for addr in &disassembly.code {
+        let offset = (addr - start) as usize;
+        if let Instruction::SetMemRegToAsciiSprite { x } = decode_strict(&program[offset..]) {
+            registers.insert(x, 0);
+            replaced.push(*addr);
+        }
+    }
+    if replaced.is_empty() {
+        return Ok(Transpiled {
+            bytes: program.to_vec(),
+            replaced,
+            unknown_jumps: disassembly.unknown_jumps,
+        });
+    }
+
+    //jump, subroutines (set I, add Vx five times, return) then glyphs
+    let sub_len = 2 * (2 + ALPHA_BYTES);
+    let glyphs = glyph_table();
+    let delta = (2 + sub_len * registers.len() + glyphs.len()) as u16;
+    let table_base = start + 2 + (sub_len * registers.len()) as u16;
+    let end = start as usize + delta as usize + program.len();
+    if end > MAX_ADDRESS as usize + 1 {
+        return Err(ECommonError::AddressOutOfRange(end as u16));
+    }
+
+    let mut bytes = Instruction::Jump { nnn: start + delta }.to_bytes().to_vec();
+    for (i, (x, sub_addr)) in registers.iter_mut().enumerate() {
+        *sub_addr = start + 2 + (sub_len * i) as u16;
+        bytes.extend_from_slice(&Instruction::SetMemReg { nnn: table_base }.to_bytes());
+        for _ in 0..ALPHA_BYTES {
+            bytes.extend_from_slice(&Instruction::AddMemReg { x: *x }.to_bytes());
+        }
+        bytes.extend_from_slice(&Instruction::Return.to_bytes());
+    }
+    bytes.extend_from_slice(&glyphs);
+
+    let relocate = |nnn: u16| -> ECommonResult<u16> {
+        if nnn < start {
+            Ok(nnn)
+        } else if nnn + delta > MAX_ADDRESS {
+            Err(ECommonError::AddressOutOfRange(nnn + delta))
+        } else {
+            Ok(nnn + delta)
+        }
+    };
+    let mut moved = program.to_vec();
+    for addr in &disassembly.code {
+        let offset = (addr - start) as usize;
+        let instruction = match decode_strict(&program[offset..]) {
+            Instruction::SetMemRegToAsciiSprite { x } => Instruction::Call { nnn: registers[&x] },
+            Instruction::Jump { nnn } => Instruction::Jump {
+                nnn: relocate(nnn)?,
+            },
+            Instruction::Call { nnn } => Instruction::Call {
+                nnn: relocate(nnn)?,
+            },
+            Instruction::SetMemReg { nnn } => Instruction::SetMemReg {
+                nnn: relocate(nnn)?,
+            },
+            Instruction::JumpOffset { nnn } => Instruction::JumpOffset {
+                nnn: relocate(nnn)?,
+            },
+            _ => continue,
+        };
+        moved[offset..offset + 2].copy_from_slice(&instruction.to_bytes());
+    }
+    bytes.extend_from_slice(&moved);
+
+    Ok(Transpiled {
+        bytes,
+        replaced,
+        unknown_jumps: disassembly.unknown_jumps,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::graphics::ALPHA_MEMORY;
+    use crate::instruction::decode_strict;
+    use crate::instruction::Instruction::*;
+    use crate::transpiler::transpile;
+
+    #[test]
+    fn check_transpile() {
+        //V3 = 'A', I = ascii V3, draw, I = 0x20A, jump to self, data
+        let program = [
+            0x63, 0x41, 0xF3, 0x30, 0xD0, 0x05, 0xA2, 0x0A, 0x12, 0x08, 0xAA,
+        ];
+        let transpiled = transpile(&program).unwrap();
+        assert_eq!(transpiled.replaced, vec![0x202]);
+        let bytes = transpiled.bytes;
+        let word = |addr: u16| decode_strict(&bytes[(addr - 0x200) as usize..]);
+
+        let Jump { nnn: entry } = word(0x200) else {
+            panic!("Doesn't start with a jump");
+        };
+        let delta = entry - 0x200;
+        assert_eq!(bytes.len(), program.len() + delta as usize);
+        assert_eq!(word(entry), SetRegFromNum { x: 3, nn: 0x41 });
+        assert_eq!(word(entry + 2), Call { nnn: 0x202 });
+        assert_eq!(word(entry + 6), SetMemReg { nnn: 0x20A + delta });
+        assert_eq!(word(entry + 8), Jump { nnn: 0x208 + delta });
+        assert_eq!(bytes[bytes.len() - 1], 0xAA);
+
+        //subroutine sets I to the glyph for V3
+        let SetMemReg { nnn: base } = word(0x202) else {
+            panic!("Subroutine doesn't set I");
+        };
+        for i in 1..=5 {
+            assert_eq!(word(0x202 + i * 2), AddMemReg { x: 3 });
+        }
+        assert_eq!(word(0x20E), Return);
+        let glyph = |code: u16| {
+            let offset = (base + 5 * code - 0x200) as usize;
+            &bytes[offset..offset + 5]
+        };
+        assert_eq!(glyph(0x41), &ALPHA_MEMORY[50..55]);
+        assert_eq!(glyph(0x61), &ALPHA_MEMORY[50..55]);
+    }
+
+    #[test]
+    fn check_blank_glyphs() {
+        //V0 = ' ', I = ascii V0
+        let transpiled = transpile(&[0x60, 0x20, 0xF0, 0x30]).unwrap();
+        let bytes = transpiled.bytes;
+        let SetMemReg { nnn: base } = decode_strict(&bytes[2..]) else {
+            panic!("Subroutine doesn't set I");
+        };
+        let Jump { nnn: entry } = decode_strict(&bytes) else {
+            panic!("Doesn't start with a jump");
+        };
+        for code in [0x00, 0x20, 0x7F] {
+            let addr = base + 5 * code;
+            assert!(addr + 5 <= entry, "{code:02X} is outside the table");
+            let offset = (addr - 0x200) as usize;
+            assert_eq!(&bytes[offset..offset + 5], &[0; 5], "{code:02X}");
+        }
+    }
+
+    #[test]
+    fn check_unchanged() {
+        let program = [0x63, 0x41, 0x12, 0x02];
+        let transpiled = transpile(&program).unwrap();
+        assert!(transpiled.replaced.is_empty());
+        assert_eq!(transpiled.bytes, program.to_vec());
+    }
+
+    #[test]
+    fn check_too_large() {
+        //I = 0xF00 can't be moved
+        assert!(transpile(&[0xF0, 0x30, 0xAF, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! Cheats
+//!
+//! [CheatSearch] narrows down the memory address of a value (such as lives or score)
+//! by comparing memory between snapshots
+//!
+//! Cheat file format
+//! ```text
+//! ;Comments start with a semicolon
+//! rom 8f3a0c1d2e4b5a69
+//! Infinite lives: 3A0=05
+//! Level 5: 3A1=05 3A2=00 once
+//! ```
+//! `rom` is optional and is the [program_hash](crate::program_hash) of the program the cheats are for
+//!
+//! Each cheat is `name: address=value ...` in hex, the values are written every frame
+//! or only on the first frame if `once` is at the end
+
+use crate::{program_hash, EmmaChip8};
+use ec8_common::{MAX_ADDRESS, MEMORY_SIZE};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Comparison {
+    /// Current value is equal to this number
+    EqualTo(u8),
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn matches(&self, previous: u8, current: u8) -> bool {
+        match self {
+            Comparison::EqualTo(value) => current == *value,
+            Comparison::Unchanged => current == previous,
+            Comparison::Changed => current != previous,
+            Comparison::Increased => current > previous,
+            Comparison::Decreased => current < previous,
+        }
+    }
+}
+
+/// Memory search, starting with every address as a candidate
+#[derive(Debug, Clone)]
+pub struct CheatSearch {
+    candidates: Vec<u16>,
+    snapshot: Vec<u8>,
+}
+
+impl CheatSearch {
+    pub fn new(machine: &EmmaChip8) -> Self {
+        Self {
+            candidates: (0..MEMORY_SIZE as u16).collect(),
+            snapshot: machine.memory.to_vec(),
+        }
+    }
+
+    /// Remove candidates that don't match `comparison` between the last snapshot and now,
+    /// then take a new snapshot
+    pub fn filter(&mut self, machine: &EmmaChip8, comparison: Comparison) {
+        self.candidates.retain(|addr| {
+            let addr = *addr as usize;
+            comparison.matches(self.snapshot[addr], machine.memory[addr])
+        });
+        self.snapshot = machine.memory.to_vec();
+    }
+
+    /// Candidate addresses and their values at the last snapshot
+    pub fn candidates(&self) -> Vec<(u16, u8)> {
+        self.candidates
+            .iter()
+            .map(|addr| (*addr, self.snapshot[*addr as usize]))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cheat {
+    pub name: String,
+    /// Addresses and values to write
+    pub pokes: Vec<(u16, u8)>,
+    pub once: bool,
+    pub enabled: bool,
+    applied: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CheatSet {
+    pub rom_hash: Option<u64>,
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatSet {
+    /// Returns true if these cheats are for `program` (or aren't tied to a program)
+    pub fn is_for(&self, program: &[u8]) -> bool {
+        self.rom_hash
+            .map(|hash| hash == program_hash(program))
+            .unwrap_or(true)
+    }
+
+    /// Write the values for all enabled cheats, should be called every frame
+    pub fn apply(&mut self, machine: &mut EmmaChip8) {
+        for cheat in self.cheats.iter_mut().filter(|cheat| cheat.enabled) {
+            if cheat.once && cheat.applied {
+                continue;
+            }
+            for (addr, value) in &cheat.pokes {
+                machine.memory[*addr as usize] = *value;
+            }
+            cheat.applied = true;
+        }
+    }
+
+    /// Enable or disable all cheats, `once` cheats will be applied again when enabled
+    pub fn set_enabled(&mut self, enabled: bool) {
+        for cheat in &mut self.cheats {
+            cheat.enabled = enabled;
+            cheat.applied = false;
+        }
+    }
+}
+
+impl FromStr for CheatSet {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut set = CheatSet::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(hash) = line.strip_prefix("rom ") {
+                let hash = u64::from_str_radix(hash.trim(), 16)
+                    .map_err(|_| format!("Line {}) Invalid ROM hash '{}'", i + 1, hash.trim()))?;
+                set.rom_hash = Some(hash);
+                continue;
+            }
+            let (name, pokes) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Line {}) Expected 'name: address=value'", i + 1))?;
+            let mut parts: Vec<&str> = pokes.split_whitespace().collect();
+            let once = parts.last() == Some(&"once");
+            if once {
+                parts.pop();
+            }
+            if parts.is_empty() {
+                return Err(format!(
+                    "Line {}) No addresses for '{}'",
+                    i + 1,
+                    name.trim()
+                ));
+            }
+            let pokes = parts
+                .iter()
+                .map(|poke| parse_poke(poke).map_err(|err| format!("Line {}) {err}", i + 1)))
+                .collect::<Result<Vec<(u16, u8)>, String>>()?;
+            set.cheats.push(Cheat {
+                name: name.trim().to_string(),
+                pokes,
+                once,
+                enabled: true,
+                applied: false,
+            });
+        }
+        Ok(set)
+    }
+}
+
+fn parse_poke(text: &str) -> Result<(u16, u8), String> {
+    let (addr, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("Expected address=value, found '{text}'"))?;
+    let addr = u16::from_str_radix(addr, 16)
+        .ok()
+        .filter(|addr| *addr <= MAX_ADDRESS)
+        .ok_or_else(|| format!("Invalid address '{addr}'"))?;
+    let value = u8::from_str_radix(value, 16).map_err(|_| format!("Invalid value '{value}'"))?;
+    Ok((addr, value))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cheats::{CheatSearch, CheatSet, Comparison};
+    use crate::EmmaChip8;
+
+    #[test]
+    fn check_search() {
+        let mut ec8 = EmmaChip8::new();
+        ec8.load_program(&[0x12, 0x00]).unwrap();
+        ec8.memory[0x300] = 3;
+        ec8.memory[0x301] = 3;
+        let mut search = CheatSearch::new(&ec8);
+        search.filter(&ec8, Comparison::EqualTo(3));
+        assert_eq!(search.candidates(), vec![(0x300, 3), (0x301, 3)]);
+        ec8.memory[0x300] = 2;
+        search.filter(&ec8, Comparison::Decreased);
+        assert_eq!(search.candidates(), vec![(0x300, 2)]);
+        search.filter(&ec8, Comparison::Changed);
+        assert!(search.candidates().is_empty());
+    }
+
+    #[test]
+    fn check_cheats() {
+        let mut set = "rom 1\nLives: 300=09\nLevel: 301=05 302=FF once ;comment"
+            .parse::<CheatSet>()
+            .unwrap();
+        assert_eq!(set.rom_hash, Some(1));
+        assert_eq!(set.cheats[1].pokes, vec![(0x301, 5), (0x302, 0xFF)]);
+        assert!(set.cheats[1].once);
+
+        let mut ec8 = EmmaChip8::new();
+        set.apply(&mut ec8);
+        assert_eq!(&ec8.memory[0x300..0x303], &[9, 5, 0xFF]);
+        ec8.memory[0x300..0x303].fill(0);
+        set.apply(&mut ec8);
+        assert_eq!(&ec8.memory[0x300..0x303], &[9, 0, 0]);
+        set.set_enabled(false);
+        ec8.memory[0x300] = 0;
+        set.apply(&mut ec8);
+        assert_eq!(ec8.memory[0x300], 0);
+    }
+
+    #[test]
+    fn check_invalid() {
+        assert_eq!(
+            "a: 1000=01".parse::<CheatSet>().unwrap_err(),
+            "Line 1) Invalid address '1000'"
+        );
+        assert_eq!(
+            "a: 300=100".parse::<CheatSet>().unwrap_err(),
+            "Line 1) Invalid value '100'"
+        );
+        assert_eq!(
+            "\na: once".parse::<CheatSet>().unwrap_err(),
+            "Line 2) No addresses for 'a'"
+        );
+        assert!("a 300=1".parse::<CheatSet>().is_err());
+    }
+}
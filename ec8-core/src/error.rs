@@ -7,12 +7,14 @@ pub type ECoreResult<T> = Result<T, ECoreError>;
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ECoreError {
     ProgramTooLarge,
+    InvalidSaveState,
 }
 
 impl Display for ECoreError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             ProgramTooLarge => write!(f, "Program is too large"),
+            InvalidSaveState => write!(f, "Save state is invalid or corrupt"),
         }
     }
 }
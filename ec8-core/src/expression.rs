@@ -0,0 +1,365 @@
+//! Expressions over machine state
+//!
+//! Used to read values out of a running program, for example `mem[0x3A0] * 10 + v5`
+//!
+//! Values
+//! `v0`-`vf` Data registers
+//! `i` Memory register
+//! `pc` Program counter
+//! `delay`, `sound` Timers
+//! `sp` Number of addresses on the stack
+//! `mem[expr]` Byte in memory
+//! `prev(expr)` Value of `expr` before the last step/frame
+//! `12`, `0x1F`, `0b101` Numbers
+//!
+//! Operators (lowest to highest precedence)
+//! `||`, `&&`, `|`, `^`, `&`, `== !=`, `< <= > >=`, `+ -`, `* / %`, unary `! -`
+//!
+//! Comparisons and logic operators produce 1 for true and 0 for false
+
+use crate::EmmaChip8;
+use ec8_common::MAX_ADDRESS;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Variable {
+    Reg(u8),
+    MemReg,
+    Pc,
+    Delay,
+    Sound,
+    StackSize,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Operator {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    BitAnd,
+    BitXor,
+    BitOr,
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    And,
+    Or,
+}
+
+impl Operator {
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::BitOr => 3,
+            Operator::BitXor => 4,
+            Operator::BitAnd => 5,
+            Operator::Eq | Operator::NotEq => 6,
+            Operator::Less | Operator::LessEq | Operator::Greater | Operator::GreaterEq => 7,
+            Operator::Add | Operator::Sub => 8,
+            Operator::Mul | Operator::Div | Operator::Rem => 9,
+        }
+    }
+
+    fn apply(&self, lhs: i64, rhs: i64) -> i64 {
+        match self {
+            Operator::Mul => lhs.wrapping_mul(rhs),
+            Operator::Div => lhs.checked_div(rhs).unwrap_or(0),
+            Operator::Rem => lhs.checked_rem(rhs).unwrap_or(0),
+            Operator::Add => lhs.wrapping_add(rhs),
+            Operator::Sub => lhs.wrapping_sub(rhs),
+            Operator::BitAnd => lhs & rhs,
+            Operator::BitXor => lhs ^ rhs,
+            Operator::BitOr => lhs | rhs,
+            Operator::Eq => (lhs == rhs) as i64,
+            Operator::NotEq => (lhs != rhs) as i64,
+            Operator::Less => (lhs < rhs) as i64,
+            Operator::LessEq => (lhs <= rhs) as i64,
+            Operator::Greater => (lhs > rhs) as i64,
+            Operator::GreaterEq => (lhs >= rhs) as i64,
+            Operator::And => (lhs != 0 && rhs != 0) as i64,
+            Operator::Or => (lhs != 0 || rhs != 0) as i64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Num(i64),
+    Var(Variable),
+    Mem(Box<Expression>),
+    Prev(Box<Expression>),
+    Not(Box<Expression>),
+    Neg(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Evaluate using `current` for values and `previous` for anything inside `prev()`
+    pub fn eval(&self, current: &EmmaChip8, previous: &EmmaChip8) -> i64 {
+        match self {
+            Expression::Num(num) => *num,
+            Expression::Var(var) => match var {
+                Variable::Reg(reg) => current.registers[*reg as usize] as i64,
+                Variable::MemReg => current.mem_reg as i64,
+                Variable::Pc => current.pc as i64,
+                Variable::Delay => current.delay as i64,
+                Variable::Sound => current.sound as i64,
+                Variable::StackSize => current.stack.len() as i64,
+            },
+            Expression::Mem(addr) => {
+                let addr = addr.eval(current, previous) & (MAX_ADDRESS as i64);
+                current.memory[addr as usize] as i64
+            }
+            Expression::Prev(expr) => expr.eval(previous, previous),
+            Expression::Not(expr) => (expr.eval(current, previous) == 0) as i64,
+            Expression::Neg(expr) => expr.eval(current, previous).wrapping_neg(),
+            Expression::Binary(op, lhs, rhs) => {
+                op.apply(lhs.eval(current, previous), rhs.eval(current, previous))
+            }
+        }
+    }
+
+    /// Evaluate as a condition, any non zero value is true
+    pub fn is_true(&self, current: &EmmaChip8, previous: &EmmaChip8) -> bool {
+        self.eval(current, previous) != 0
+    }
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenise(text)?;
+        if tokens.is_empty() {
+            return Err("Expression is empty".to_string());
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expression(0)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {:?} in '{text}'", token)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Op(Operator),
+    Not,
+    Open,
+    Close,
+    OpenSquare,
+    CloseSquare,
+}
+
+fn tokenise(text: &str) -> Result<Vec<Token>, String> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let chr = chars[i];
+        let next = chars.get(i + 1).copied();
+        if chr.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if chr.is_ascii_alphanumeric() || chr == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word = chars[start..i].iter().collect::<String>().to_lowercase();
+            if chr.is_ascii_digit() {
+                tokens.push(Token::Num(parse_num(&word)?));
+            } else {
+                tokens.push(Token::Ident(word));
+            }
+            continue;
+        }
+        let (token, len) = match (chr, next) {
+            ('|', Some('|')) => (Token::Op(Operator::Or), 2),
+            ('&', Some('&')) => (Token::Op(Operator::And), 2),
+            ('=', Some('=')) => (Token::Op(Operator::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Operator::NotEq), 2),
+            ('<', Some('=')) => (Token::Op(Operator::LessEq), 2),
+            ('>', Some('=')) => (Token::Op(Operator::GreaterEq), 2),
+            ('<', _) => (Token::Op(Operator::Less), 1),
+            ('>', _) => (Token::Op(Operator::Greater), 1),
+            ('|', _) => (Token::Op(Operator::BitOr), 1),
+            ('&', _) => (Token::Op(Operator::BitAnd), 1),
+            ('^', _) => (Token::Op(Operator::BitXor), 1),
+            ('+', _) => (Token::Op(Operator::Add), 1),
+            ('-', _) => (Token::Op(Operator::Sub), 1),
+            ('*', _) => (Token::Op(Operator::Mul), 1),
+            ('/', _) => (Token::Op(Operator::Div), 1),
+            ('%', _) => (Token::Op(Operator::Rem), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('[', _) => (Token::OpenSquare, 1),
+            (']', _) => (Token::CloseSquare, 1),
+            _ => return Err(format!("Invalid character '{chr}' in '{text}'")),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+fn parse_num(word: &str) -> Result<i64, String> {
+    let result = if let Some(hex) = word.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = word.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else {
+        word.parse::<i64>()
+    };
+    result.map_err(|err| format!("Invalid number '{word}': {err}"))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {:?} but found {:?}", expected, token)),
+            None => Err(format!("Expected {:?} but expression ended", expected)),
+        }
+    }
+
+    fn expression(&mut self, min_precedence: u8) -> Result<Expression, String> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos).cloned() {
+            if op.precedence() <= min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expression(op.precedence())?;
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expression::Not(Box::new(self.unary()?))),
+            Some(Token::Op(Operator::Sub)) => Ok(Expression::Neg(Box::new(self.unary()?))),
+            Some(Token::Num(num)) => Ok(Expression::Num(num)),
+            Some(Token::Open) => {
+                let expr = self.expression(0)?;
+                self.expect(Token::Close)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => self.ident(&name),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Expression ended unexpectedly".to_string()),
+        }
+    }
+
+    fn ident(&mut self, name: &str) -> Result<Expression, String> {
+        match name {
+            "i" => Ok(Expression::Var(Variable::MemReg)),
+            "pc" => Ok(Expression::Var(Variable::Pc)),
+            "delay" => Ok(Expression::Var(Variable::Delay)),
+            "sound" => Ok(Expression::Var(Variable::Sound)),
+            "sp" => Ok(Expression::Var(Variable::StackSize)),
+            "mem" => {
+                self.expect(Token::OpenSquare)?;
+                let addr = self.expression(0)?;
+                self.expect(Token::CloseSquare)?;
+                Ok(Expression::Mem(Box::new(addr)))
+            }
+            "prev" => {
+                self.expect(Token::Open)?;
+                let expr = self.expression(0)?;
+                self.expect(Token::Close)?;
+                Ok(Expression::Prev(Box::new(expr)))
+            }
+            _ => {
+                if let Some(reg) = name.strip_prefix('v') {
+                    if reg.len() == 1 {
+                        if let Ok(reg) = u8::from_str_radix(reg, 16) {
+                            return Ok(Expression::Var(Variable::Reg(reg)));
+                        }
+                    }
+                }
+                Err(format!("Unknown value '{name}'"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::expression::Expression;
+    use crate::EmmaChip8;
+
+    fn eval(text: &str, current: &EmmaChip8, previous: &EmmaChip8) -> i64 {
+        text.parse::<Expression>().unwrap().eval(current, previous)
+    }
+
+    #[test]
+    fn check_math() {
+        let ec8 = EmmaChip8::new();
+        assert_eq!(eval("1 + 2 * 3", &ec8, &ec8), 7);
+        assert_eq!(eval("(1 + 2) * 3", &ec8, &ec8), 9);
+        assert_eq!(eval("10 - 4 - 3", &ec8, &ec8), 3);
+        assert_eq!(eval("0x10 / 0b10", &ec8, &ec8), 8);
+        assert_eq!(eval("5 / 0", &ec8, &ec8), 0);
+        assert_eq!(eval("-3 + 1", &ec8, &ec8), -2);
+        assert_eq!(eval("1 < 2 && !(3 == 4)", &ec8, &ec8), 1);
+        assert_eq!(eval("0 || 2 >= 3", &ec8, &ec8), 0);
+    }
+
+    #[test]
+    fn check_state() {
+        let mut previous = EmmaChip8::new();
+        previous.load_program(&[0x00, 0xE0]).unwrap();
+        let mut current = previous.clone();
+        current.registers[5] = 12;
+        current.memory[0x3A0] = 1;
+        assert_eq!(eval("v5", &current, &previous), 12);
+        assert_eq!(eval("VF", &current, &previous), 0);
+        assert_eq!(eval("pc", &current, &previous), 0x200);
+        assert_eq!(eval("mem[0x3A0]", &current, &previous), 1);
+        assert_eq!(eval("mem[0x200] + mem[0x201]", &current, &previous), 0xE0);
+        assert_eq!(
+            eval(
+                "prev(mem[0x3A0]) == 0 && mem[0x3A0] == 1",
+                &current,
+                &previous
+            ),
+            1
+        );
+        assert_eq!(eval("v5 - prev(v5)", &current, &previous), 12);
+    }
+
+    #[test]
+    fn check_errors() {
+        assert!("".parse::<Expression>().is_err());
+        assert!("1 +".parse::<Expression>().is_err());
+        assert!("(1".parse::<Expression>().is_err());
+        assert!("vg".parse::<Expression>().is_err());
+        assert!("mem(1)".parse::<Expression>().is_err());
+        assert!("1 2".parse::<Expression>().is_err());
+        assert!("0xZZ".parse::<Expression>().is_err());
+    }
+}
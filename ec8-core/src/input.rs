@@ -32,6 +32,31 @@ impl Key {
         *self as usize
     }
 
+    /// 0 -> K0,
+    /// 10 -> KA,
+    /// etc
+    pub fn from_index(idx: usize) -> Option<Key> {
+        match idx {
+            0x0 => Some(K0),
+            0x1 => Some(K1),
+            0x2 => Some(K2),
+            0x3 => Some(K3),
+            0x4 => Some(K4),
+            0x5 => Some(K5),
+            0x6 => Some(K6),
+            0x7 => Some(K7),
+            0x8 => Some(K8),
+            0x9 => Some(K9),
+            0xA => Some(KA),
+            0xB => Some(KB),
+            0xC => Some(KC),
+            0xD => Some(KD),
+            0xE => Some(KE),
+            0xF => Some(KF),
+            _ => None,
+        }
+    }
+
     /// 0 -> K0,
     /// 1 -> K1,
     /// A -> KA,
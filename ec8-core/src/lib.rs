@@ -1,15 +1,33 @@
 use crate::error::ECoreError::ProgramTooLarge;
 use crate::error::ECoreResult;
+use crate::sanitizer::Sanitizer;
 use crate::State::{Running, Waiting};
 use ec8_common::graphics::ALPHA_MEMORY;
+use ec8_common::symbols::Symbols;
 use ec8_common::*;
 #[cfg(feature = "logging")]
 use log::info;
 use std::collections::VecDeque;
+use std::sync::Arc;
 
+pub mod cheats;
 pub mod error;
+pub mod expression;
 pub mod input;
 pub mod runtime;
+pub mod sanitizer;
+pub mod save_state;
+pub mod triggers;
+
+/// Number of instructions executed per 60Hz frame by [EmmaChip8::run_frame]
+pub const INSTRUCTIONS_PER_FRAME: usize = 16;
+
+/// Stable hash of a program (FNV-1a), used to tie files such as triggers to a ROM
+pub fn program_hash(program: &[u8]) -> u64 {
+    program.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum State {
@@ -35,6 +53,14 @@ pub struct EmmaChip8 {
     pub state: State,
     pub keys: [bool; BUTTON_COUNT],
     pub dirty: bool,
+    /// Random number generator state, used by `Cxnn`
+    pub rng: u64,
+    /// Runtime checks, see [EmmaChip8::enable_sanitizer]
+    pub sanitizer: Option<Sanitizer>,
+    /// Names of addresses, used when logging instructions (with the `logging` feature)
+    ///
+    /// Shared so cloning the machine stays cheap
+    pub symbols: Option<Arc<Symbols>>,
 }
 
 impl EmmaChip8 {
@@ -52,8 +78,18 @@ impl EmmaChip8 {
             state: Waiting,
             keys: [false; BUTTON_COUNT],
             dirty: false,
+            rng: fastrand::u64(..),
+            sanitizer: None,
+            symbols: None,
         }
     }
+
+    /// Set the random number generator state
+    ///
+    /// Two machines with the same program, seed and inputs will always produce the same output
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = seed;
+    }
 }
 
 impl EmmaChip8 {
@@ -80,10 +116,12 @@ impl EmmaChip8 {
         self.sound = 0;
         self.delay = 0;
         self.registers = [0; REGISTER_COUNT];
+        self.stack.clear();
         self.output = [false; PIXEL_COUNT];
         self.state = Running;
         self.keys = [false; BUTTON_COUNT];
         self.dirty = true;
+        self.reset_sanitizer(data.len());
 
         #[cfg(feature = "logging")]
         info!("Program loaded");
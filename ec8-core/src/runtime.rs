@@ -1,26 +1,55 @@
 use crate::State::{InvalidAscii, Running, WaitingForKey};
 use crate::{EmmaChip8, State};
 use ec8_common::graphics::alpha_addr;
-use ec8_common::nibbler::{Masher, Nibbler};
+use ec8_common::instruction::{decode, Instruction};
+use ec8_common::nibbler::Nibbler;
+#[cfg(feature = "logging")]
+use ec8_common::symbols::Symbols;
 use ec8_common::{
-    opcodes, OpCodes, ALPHA_BYTES, ALPHA_START_ADDRESS, MAX_STACK_COUNT, MAX_X, MAX_Y, REG_FLAG,
+    ALPHA_BYTES, ALPHA_START_ADDRESS, MAX_STACK_COUNT, MAX_X, MAX_Y, MEMORY_SIZE, REG_FLAG,
 };
 #[cfg(feature = "logging")]
 use log::{debug, error, info, warn};
 
+/// Used when logging without symbols
+#[cfg(feature = "logging")]
+static NO_SYMBOLS: Symbols = Symbols::new();
+
 impl EmmaChip8 {
+    /// Execute up to `instructions` instructions and then update the timers
+    ///
+    /// Execution stops early if the machine leaves the `Running` state
+    pub fn run_frame(&mut self, instructions: usize) {
+        for _ in 0..instructions {
+            if self.state != Running {
+                break;
+            }
+            self.run();
+        }
+        self.tick_timers();
+    }
+
+    /// Decrement the delay and sound timers, should be called at 60Hz
+    pub fn tick_timers(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
     pub fn run(&mut self) {
         if self.state == Running {
+            //memory wraps around, so jumps and skips past the end continue from the start
+            self.pc %= MEMORY_SIZE as u16;
+            self.sanitize_fetch(self.pc);
             let bytes = self.read_two_bytes(self.pc);
             #[cfg(feature = "logging")]
             debug!("Preparing to execute {:02X} {:02X}", bytes[0], bytes[1]);
-            match opcodes::from_bytes(bytes) {
-                None => {
+            match decode(&bytes) {
+                Instruction::Unknown(_) => {
                     self.state = State::InvalidOpcode;
                     #[cfg(feature = "logging")]
                     error!("State set to InvalidOpcode");
                 }
-                Some(opcode) => self.execute(opcode, bytes),
+                instruction => self.execute(instruction),
             }
         } else {
             #[cfg(feature = "logging")]
@@ -28,11 +57,10 @@ impl EmmaChip8 {
         }
     }
 
-    /// Execute Opcode with data
-    /// Returns true if PC was updated by the operation
-    fn execute(&mut self, opcode: OpCodes, bytes: [u8; 2]) {
+    /// Execute a decoded instruction
+    fn execute(&mut self, instruction: Instruction) {
         #[cfg(feature = "logging")]
-        debug!("Decoded as {:?}", opcode);
+        debug!("Decoded as {:?}", instruction);
         self.pc += 2;
         #[cfg(feature = "logging")]
         let mut debug_data = 0;
@@ -44,12 +72,11 @@ impl EmmaChip8 {
         let mut debug_pc = 2;
         #[cfg(feature = "logging")]
         let pre_op = self.clone();
-        let x = bytes[0].second_nibble();
-        let y = bytes[1].first_nibble_shifted();
-        match opcode {
-            OpCodes::SysCall => { /*do nothing, not supported*/ }
-            OpCodes::ClearDisplay => self.output.fill(false),
-            OpCodes::Return => match self.stack.pop_back() {
+        self.sanitize(self.pc - 2, instruction);
+        match instruction {
+            Instruction::SysCall { .. } => { /*do nothing, not supported*/ }
+            Instruction::ClearDisplay => self.output.fill(false),
+            Instruction::Return => match self.stack.pop_back() {
                 None => self.state = State::StackEmpty,
                 Some(addr) => {
                     #[cfg(feature = "logging")]
@@ -60,15 +87,15 @@ impl EmmaChip8 {
                     self.pc = addr
                 }
             },
-            OpCodes::Jump => {
+            Instruction::Jump { nnn } => {
                 #[cfg(feature = "logging")]
                 {
                     debug_pc = 0;
                     debug_data = self.pc - 2;
                 }
-                self.pc = bytes.mash_to_12bits();
+                self.pc = nnn;
             }
-            OpCodes::Call => {
+            Instruction::Call { nnn } => {
                 if self.stack.len() < MAX_STACK_COUNT {
                     self.stack.push_back(self.pc);
                     #[cfg(feature = "logging")]
@@ -76,13 +103,13 @@ impl EmmaChip8 {
                         debug_data = self.pc - 2;
                         debug_pc = 0;
                     }
-                    self.pc = bytes.mash_to_12bits()
+                    self.pc = nnn
                 } else {
                     self.state = State::StackOverflow;
                 }
             }
-            OpCodes::SkipIfEqualNum => {
-                if self.read_reg(x) == bytes[1] {
+            Instruction::SkipIfEqualNum { x, nn } => {
+                if self.read_reg(x) == nn {
                     #[cfg(feature = "logging")]
                     {
                         debug_data = self.read_next_instr_u16();
@@ -91,8 +118,8 @@ impl EmmaChip8 {
                     self.pc += 2;
                 }
             }
-            OpCodes::SkipIfNotEqualNum => {
-                if self.read_reg(x) != bytes[1] {
+            Instruction::SkipIfNotEqualNum { x, nn } => {
+                if self.read_reg(x) != nn {
                     #[cfg(feature = "logging")]
                     {
                         debug_data = self.read_next_instr_u16();
@@ -101,7 +128,7 @@ impl EmmaChip8 {
                     self.pc += 2;
                 }
             }
-            OpCodes::SkipIfEqualReg => {
+            Instruction::SkipIfEqualReg { x, y } => {
                 if self.read_reg(x) == self.read_reg(y) {
                     #[cfg(feature = "logging")]
                     {
@@ -111,50 +138,50 @@ impl EmmaChip8 {
                     self.pc += 2;
                 }
             }
-            OpCodes::SetRegFromNum => self.set_reg(x, bytes[1]),
-            OpCodes::AddNumToReg => {
+            Instruction::SetRegFromNum { x, nn } => self.set_reg(x, nn),
+            Instruction::AddNumToReg { x, nn } => {
                 let target = x;
                 let value = self.read_reg(target);
-                self.set_reg(target, value.overflowing_add(bytes[1]).0);
+                self.set_reg(target, value.overflowing_add(nn).0);
             }
-            OpCodes::SetRegFromReg => self.set_reg(x, self.read_reg(y)),
-            OpCodes::BitwiseOr => {
+            Instruction::SetRegFromReg { x, y } => self.set_reg(x, self.read_reg(y)),
+            Instruction::BitwiseOr { x, y } => {
                 self.set_reg(x, self.read_reg(x) | self.read_reg(y));
             }
-            OpCodes::BitwiseAnd => {
+            Instruction::BitwiseAnd { x, y } => {
                 self.set_reg(x, self.read_reg(x) & self.read_reg(y));
             }
-            OpCodes::BitwiseXor => {
+            Instruction::BitwiseXor { x, y } => {
                 self.set_reg(x, self.read_reg(x) ^ self.read_reg(y));
             }
-            OpCodes::AddReg => {
+            Instruction::AddReg { x, y } => {
                 let (result, overflowed) = self.read_reg(x).overflowing_add(self.read_reg(y));
                 self.set_reg(x, result);
                 self.set_flag(overflowed);
             }
-            OpCodes::SubRightReg => {
+            Instruction::SubRightReg { x, y } => {
                 let vx = self.read_reg(x);
                 let vy = self.read_reg(y);
                 self.set_flag(vx > vy);
                 self.set_reg(x, vx.wrapping_sub(vy));
             }
-            OpCodes::ShiftRight => {
+            Instruction::ShiftRight { x, .. } => {
                 let value = self.read_reg(x);
                 self.registers[REG_FLAG] = value & 0x01;
                 self.set_reg(x, value >> 1);
             }
-            OpCodes::SubLeftReg => {
+            Instruction::SubLeftReg { x, y } => {
                 let vx = self.read_reg(x);
                 let vy = self.read_reg(y);
                 self.set_flag(vy > vx);
                 self.set_reg(x, vy.wrapping_sub(vx));
             }
-            OpCodes::ShiftLeft => {
+            Instruction::ShiftLeft { x, .. } => {
                 let value = self.read_reg(x);
                 self.registers[REG_FLAG] = value >> 7;
                 self.set_reg(x, value << 1);
             }
-            OpCodes::SkipIfNotEqualReg => {
+            Instruction::SkipIfNotEqualReg { x, y } => {
                 if self.read_reg(x) != self.read_reg(y) {
                     #[cfg(feature = "logging")]
                     {
@@ -164,28 +191,30 @@ impl EmmaChip8 {
                     self.pc += 2;
                 }
             }
-            OpCodes::SetMemReg => self.mem_reg = bytes.mash_to_12bits(),
-            OpCodes::JumpOffset => {
+            Instruction::SetMemReg { nnn } => self.mem_reg = nnn,
+            Instruction::JumpOffset { nnn } => {
                 #[cfg(feature = "logging")]
                 {
                     debug_data = self.pc - 2;
                     debug_pc = 0;
                 }
-                self.pc = (self.read_reg(0) as u16) + bytes.mash_to_12bits();
+                self.pc = (self.read_reg(0) as u16) + nnn;
             }
-            OpCodes::SetRegRand => {
-                let rand = fastrand::u8(..);
+            Instruction::SetRegRand { x, nn } => {
+                let rng = fastrand::Rng::with_seed(self.rng);
+                let rand = rng.u8(..);
+                self.rng = rng.get_seed();
                 #[cfg(feature = "logging")]
                 {
                     debug_data = rand as u16;
                 }
-                self.set_reg(x, rand & bytes[1]);
+                self.set_reg(x, rand & nn);
             }
-            OpCodes::DrawSprite => {
-                self.draw_sprite(self.read_reg(x), self.read_reg(y), bytes[1].second_nibble())
+            Instruction::DrawSprite { x, y, n } => {
+                self.draw_sprite(self.read_reg(x), self.read_reg(y), n)
             }
-            OpCodes::SkipIfKeyPressed => {
-                let key = self.read_reg(x);
+            Instruction::SkipIfKeyPressed { x } => {
+                let key = self.read_reg(x).second_nibble();
                 if self.keys[key as usize] {
                     #[cfg(feature = "logging")]
                     {
@@ -195,8 +224,8 @@ impl EmmaChip8 {
                     self.pc += 2;
                 }
             }
-            OpCodes::SkipIfKeyNotPressed => {
-                let key = self.read_reg(x);
+            Instruction::SkipIfKeyNotPressed { x } => {
+                let key = self.read_reg(x).second_nibble();
                 if !self.keys[key as usize] {
                     #[cfg(feature = "logging")]
                     {
@@ -206,21 +235,21 @@ impl EmmaChip8 {
                     self.pc += 2;
                 }
             }
-            OpCodes::SetRegFromTimer => {
+            Instruction::SetRegFromTimer { x } => {
                 self.set_reg(x, self.delay);
                 #[cfg(feature = "logging")]
                 {
                     debug_data = self.delay as u16;
                 }
             }
-            OpCodes::WaitForKey => self.state = WaitingForKey(x),
-            OpCodes::SetDelayTimer => self.delay = self.read_reg(x),
-            OpCodes::SetSoundTimer => self.sound = self.read_reg(x),
-            OpCodes::AddMemReg => {
+            Instruction::WaitForKey { x } => self.state = WaitingForKey(x),
+            Instruction::SetDelayTimer { x } => self.delay = self.read_reg(x),
+            Instruction::SetSoundTimer { x } => self.sound = self.read_reg(x),
+            Instruction::AddMemReg { x } => {
                 let num = self.read_reg(x);
-                self.mem_reg += num as u16;
+                self.mem_reg = self.mem_reg.wrapping_add(num as u16);
             }
-            OpCodes::SetMemRegToAsciiSprite => {
+            Instruction::SetMemRegToAsciiSprite { x } => {
                 let chr = self.read_reg(x);
                 if let Some(addr) = alpha_addr(chr as char) {
                     self.mem_reg = addr;
@@ -228,43 +257,44 @@ impl EmmaChip8 {
                     self.state = InvalidAscii;
                 }
             }
-            OpCodes::SetMemRegToDigitSprite => {
+            Instruction::SetMemRegToDigitSprite { x } => {
                 let digit = self.read_reg(x).second_nibble();
                 self.mem_reg = ALPHA_START_ADDRESS + ALPHA_BYTES as u16 * digit as u16;
             }
-            OpCodes::StoreBcd => {
+            Instruction::StoreBcd { x } => {
                 let value = self.read_reg(x);
-                self.memory[self.mem_reg as usize] = value / 100;
-                self.memory[self.mem_reg as usize + 1] = (value / 10) % 10;
-                self.memory[self.mem_reg as usize + 2] = value % 10;
+                self.memory[self.mem_addr(0)] = value / 100;
+                self.memory[self.mem_addr(1)] = (value / 10) % 10;
+                self.memory[self.mem_addr(2)] = value % 10;
             }
-            OpCodes::StoreRegs => {
+            Instruction::StoreRegs { x } => {
                 let stop_at = x as usize;
                 for i in 0..=stop_at {
-                    let addr = (self.mem_reg as usize) + i;
+                    let addr = self.mem_addr(i);
                     self.memory[addr] = self.registers[i];
                 }
             }
-            OpCodes::LoadRegs => {
+            Instruction::LoadRegs { x } => {
                 let stop_at = x as usize;
                 for i in 0..=stop_at {
-                    let addr = (self.mem_reg as usize) + i;
+                    let addr = self.mem_addr(i);
                     self.registers[i] = self.memory[addr];
                 }
             }
+            Instruction::Unknown(_) => self.state = State::InvalidOpcode,
         }
         #[cfg(feature = "logging")]
         info!(
             "{}",
-            opcode.describe(
-                bytes,
+            instruction.describe(
                 pre_op.registers,
                 pre_op.mem_reg,
                 self.registers,
                 self.mem_reg,
                 self.pc - debug_pc,
                 debug_data,
-                debug_pc
+                debug_pc,
+                self.symbols.as_deref().unwrap_or(&NO_SYMBOLS)
             )
         );
     }
@@ -291,7 +321,16 @@ impl EmmaChip8 {
     #[inline(always)]
     fn read_two_bytes(&self, addr: u16) -> [u8; 2] {
         let addr = addr as usize;
-        [self.memory[addr], self.memory[addr + 1]]
+        [
+            self.memory[addr % MEMORY_SIZE],
+            self.memory[(addr + 1) % MEMORY_SIZE],
+        ]
+    }
+
+    /// Address of `I + offset`, wrapping at the end of memory
+    #[inline(always)]
+    fn mem_addr(&self, offset: usize) -> usize {
+        (self.mem_reg as usize + offset) % MEMORY_SIZE
     }
 
     #[cfg(feature = "logging")]
@@ -308,8 +347,7 @@ impl EmmaChip8 {
         self.dirty = true;
         let mut collision = false;
         for row in 0..(rows as usize) {
-            let addr = self.mem_reg as usize + row;
-            let pixels = self.memory[addr];
+            let pixels = self.memory[self.mem_addr(row)];
             let py = y as usize + row;
             for i in 0..8 {
                 let px = x as usize + i;
@@ -328,6 +366,7 @@ impl EmmaChip8 {
 
 #[cfg(test)]
 mod test {
+    use crate::input::Key;
     use crate::EmmaChip8;
     use crate::State::Running;
     use ec8_common::{ALPHA_BYTES, MAX_X, PIXEL_COUNT};
@@ -398,6 +437,43 @@ mod test {
         assert_eq!(ec8.output, expected);
     }
 
+    #[test]
+    fn check_end_of_memory() {
+        let mut ec8 = EmmaChip8::new();
+        //Set V0 = 0x1A
+        //Skip if key V0 (only the low nibble is used) is pressed
+        //Skipped
+        //Set I = 0xFFF
+        //Store BCD of V0 at I
+        //Store V0 to V2 at I
+        //Draw 5 rows from I
+        //Jump to 0xFFF
+        ec8.load_program(&[
+            0x60, 0x1A, 0xE0, 0x9E, 0x00, 0x00, 0xAF, 0xFF, 0xF0, 0x33, 0xF2, 0x55, 0xD0, 0x05,
+            0x1F, 0xFF,
+        ])
+        .unwrap();
+        ec8.on_key_pressed(Key::KA);
+        ec8.run();
+        ec8.run();
+        assert_eq!(ec8.pc, 0x206);
+        ec8.run();
+        ec8.run();
+        assert_eq!(ec8.memory[0xFFF], 0);
+        assert_eq!(ec8.memory[0], 2);
+        assert_eq!(ec8.memory[1], 6);
+        ec8.run();
+        assert_eq!(ec8.memory[0xFFF], 0x1A);
+        assert_eq!(ec8.memory[0], 0);
+        ec8.run();
+        ec8.run();
+        assert_eq!(ec8.pc, 0xFFF);
+        //Reads 0xFFF and 0x000, 0x1A00 jumps to 0xA00
+        ec8.run();
+        assert_eq!(ec8.state, Running);
+        assert_eq!(ec8.pc, 0xA00);
+    }
+
     #[test]
     fn check_sub() {
         let mut ec8 = EmmaChip8::new();
@@ -451,4 +527,39 @@ mod test {
         assert_eq!(ec8.state, Running);
         assert_eq!(ec8.pc, 0x20E);
     }
+
+    #[test]
+    fn check_seeded_rand() {
+        let program = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF];
+        let mut first = EmmaChip8::new();
+        first.load_program(&program).unwrap();
+        first.set_seed(42);
+        let mut second = EmmaChip8::new();
+        second.load_program(&program).unwrap();
+        second.set_seed(42);
+        first.run();
+        let mut cloned = first.clone();
+        for _ in 0..2 {
+            first.run();
+            second.run();
+            cloned.run();
+        }
+        second.run();
+        assert_eq!(first.registers, second.registers);
+        assert_eq!(first.registers, cloned.registers);
+    }
+
+    #[test]
+    fn check_run_frame() {
+        let mut ec8 = EmmaChip8::new();
+        //Set V0 = 10, set delay = V0, jump to self
+        ec8.load_program(&[0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04])
+            .unwrap();
+        ec8.run_frame(4);
+        assert_eq!(ec8.state, Running);
+        assert_eq!(ec8.pc, 0x204);
+        assert_eq!(ec8.delay, 9);
+        ec8.run_frame(4);
+        assert_eq!(ec8.delay, 8);
+    }
 }
@@ -0,0 +1,543 @@
+//! Sanitizer
+//!
+//! Optional runtime checks for bugs in programs, enabled with [EmmaChip8::enable_sanitizer]
+//!
+//! Tracks which registers and memory bytes have been written since the program was loaded
+//! and reports any instruction that reads a value that has never been set
+//! (the font and program are treated as set)
+//!
+//! The randomized power on state fills registers and unused memory with random values
+//! instead of zeros to expose programs that depend on them being zero
+//!
+//! Also checks control flow, reporting
+//! - jumps, calls and returns to odd addresses
+//! - executing data (see [ProgramInfo]) or anything outside of the program
+//! - returns with an empty stack or to an address that was never after a call
+//! - writes into the code of the program
+
+use crate::EmmaChip8;
+use ec8_common::debug_info::{DebugInfo, RegionKind};
+use ec8_common::graphics::ALPHA_MEMORY;
+use ec8_common::instruction::Instruction;
+use ec8_common::symbols::Symbols;
+use ec8_common::{MEMORY_SIZE, PROG_START_ADDRESS, REGISTER_COUNT, REG_FLAG};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Issue {
+    UninitializedRegister(u8),
+    UninitializedMemory(u16),
+    /// Jump, call or return to an odd address
+    OddAddress(u16),
+    ExecutingData,
+    OutsideProgram,
+    ReturnEmptyStack,
+    /// Return to an address that no call returns to
+    ReturnNotCallSite(u16),
+    /// Write into the code of the program
+    SelfModifying(u16),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Report {
+    /// Address of the instruction
+    pub pc: u16,
+    pub issue: Issue,
+}
+
+impl Report {
+    fn format(&self, addr: impl Fn(u16) -> String) -> String {
+        let issue = match self.issue {
+            Issue::UninitializedRegister(reg) => format!("read of uninitialized V{reg:X}"),
+            Issue::UninitializedMemory(target) => {
+                format!("read of uninitialized memory at {}", addr(target))
+            }
+            Issue::OddAddress(target) => format!("control flow to odd address {}", addr(target)),
+            Issue::ExecutingData => String::from("executing data"),
+            Issue::OutsideProgram => String::from("executing outside of program"),
+            Issue::ReturnEmptyStack => String::from("return with empty stack"),
+            Issue::ReturnNotCallSite(target) => {
+                format!("return to {} which is not after a call", addr(target))
+            }
+            Issue::SelfModifying(target) => format!("write into code at {}", addr(target)),
+        };
+        format!("{}: {issue}", addr(self.pc))
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format(|addr| format!("{addr:03X}")))
+    }
+}
+
+/// Extra details about the loaded program, improves sanitizer reports
+#[derive(Debug, Clone, Default)]
+pub struct ProgramInfo {
+    /// Address ranges containing data rather than code
+    pub data: Vec<Range<u16>>,
+    /// Names of addresses
+    pub symbols: Symbols,
+}
+
+impl ProgramInfo {
+    /// Data regions and names from debug info made by the assembler or LL compiler
+    pub fn from_debug_info(info: &DebugInfo) -> Self {
+        let data = info
+            .regions
+            .iter()
+            .filter(|region| region.kind == RegionKind::Data)
+            .map(|region| region.start..region.end)
+            .collect();
+        Self {
+            data,
+            symbols: info.symbols(),
+        }
+    }
+
+    /// Format `addr` using the nearest symbol at or before it, see [Symbols::format]
+    pub fn name(&self, addr: u16) -> String {
+        self.symbols.format(addr)
+    }
+
+    fn is_data(&self, addr: u16) -> bool {
+        self.data.iter().any(|range| range.contains(&addr))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sanitizer {
+    randomize: bool,
+    info: ProgramInfo,
+    program: Range<u16>,
+    registers: [bool; REGISTER_COUNT],
+    memory: Vec<bool>,
+    /// Addresses after every executed call
+    return_addresses: HashSet<u16>,
+    reported: HashSet<Report>,
+    reports: Vec<Report>,
+}
+
+/// Registers (as bit flags) and memory used by an instruction
+#[derive(Debug, Default)]
+struct Access {
+    reg_reads: u16,
+    reg_writes: u16,
+    mem_reads: Range<usize>,
+    mem_writes: Range<usize>,
+}
+
+impl Sanitizer {
+    fn new(randomize: bool) -> Self {
+        Self {
+            randomize,
+            info: ProgramInfo::default(),
+            program: PROG_START_ADDRESS..PROG_START_ADDRESS,
+            registers: [false; REGISTER_COUNT],
+            memory: vec![false; MEMORY_SIZE],
+            return_addresses: HashSet::new(),
+            reported: HashSet::new(),
+            reports: vec![],
+        }
+    }
+
+    pub fn set_program_info(&mut self, info: ProgramInfo) {
+        self.info = info;
+    }
+
+    /// Issues found so far, each issue is only reported once per instruction address
+    pub fn reports(&self) -> &[Report] {
+        &self.reports
+    }
+
+    /// Remove and return issues found since the last call
+    pub fn take_reports(&mut self) -> Vec<Report> {
+        self.reports.drain(..).collect()
+    }
+
+    /// Describe `report` using labels from the [ProgramInfo]
+    pub fn describe(&self, report: &Report) -> String {
+        report.format(|addr| self.info.name(addr))
+    }
+
+    fn report(&mut self, pc: u16, issue: Issue) {
+        let report = Report { pc, issue };
+        if self.reported.insert(report) {
+            #[cfg(feature = "logging")]
+            log::warn!("Sanitizer: {}", self.describe(&report));
+            self.reports.push(report);
+        }
+    }
+
+    fn reset(&mut self, program_len: usize) {
+        let program_start = PROG_START_ADDRESS as usize;
+        self.program = PROG_START_ADDRESS..PROG_START_ADDRESS + program_len as u16;
+        self.registers = [false; REGISTER_COUNT];
+        self.memory.fill(false);
+        self.memory[..ALPHA_MEMORY.len()].fill(true);
+        self.memory[program_start..program_start + program_len].fill(true);
+        self.return_addresses.clear();
+        self.reported.clear();
+        self.reports.clear();
+    }
+
+    /// Check the address of an instruction that is about to be decoded
+    fn on_fetch(&mut self, pc: u16) {
+        if self.info.is_data(pc) {
+            self.report(pc, Issue::ExecutingData);
+        } else if !self.program.contains(&pc) {
+            self.report(pc, Issue::OutsideProgram);
+        }
+    }
+
+    /// Check an instruction at `pc` that is about to be executed
+    fn on_execute(&mut self, machine: &EmmaChip8, pc: u16, instruction: Instruction) {
+        self.check_control_flow(machine, pc, instruction);
+        let access = access(instruction, machine.mem_reg);
+        for reg in 0..REGISTER_COUNT {
+            if access.reg_reads & (1 << reg) != 0 && !self.registers[reg] {
+                self.report(pc, Issue::UninitializedRegister(reg as u8));
+            }
+        }
+        for addr in access.mem_reads {
+            if !self.memory[addr] {
+                self.report(pc, Issue::UninitializedMemory(addr as u16));
+            }
+        }
+        for reg in 0..REGISTER_COUNT {
+            if access.reg_writes & (1 << reg) != 0 {
+                self.registers[reg] = true;
+            }
+        }
+        for addr in access.mem_writes.clone() {
+            let addr = addr as u16;
+            if self.program.contains(&addr) && !self.info.is_data(addr) {
+                self.report(pc, Issue::SelfModifying(addr));
+            }
+        }
+        self.memory[access.mem_writes].fill(true);
+    }
+
+    fn check_control_flow(&mut self, machine: &EmmaChip8, pc: u16, instruction: Instruction) {
+        let target = match instruction {
+            Instruction::Jump { nnn } => Some(nnn),
+            Instruction::Call { nnn } => {
+                self.return_addresses.insert(pc + 2);
+                Some(nnn)
+            }
+            Instruction::JumpOffset { nnn } => Some(machine.registers[0] as u16 + nnn),
+            Instruction::Return => match machine.stack.back() {
+                None => {
+                    self.report(pc, Issue::ReturnEmptyStack);
+                    None
+                }
+                Some(addr) => {
+                    if !self.return_addresses.contains(addr) {
+                        self.report(pc, Issue::ReturnNotCallSite(*addr));
+                    }
+                    Some(*addr)
+                }
+            },
+            _ => None,
+        };
+        if let Some(target) = target {
+            if target % 2 != 0 {
+                self.report(pc, Issue::OddAddress(target));
+            }
+        }
+    }
+}
+
+/// Bit flags for V0 to `last`
+fn regs_to(last: u8) -> u16 {
+    ((1_u32 << (last + 1)) - 1) as u16
+}
+
+/// Memory range of `len` bytes from `start`, clamped to memory
+fn mem_range(start: u16, len: usize) -> Range<usize> {
+    let start = (start as usize).min(MEMORY_SIZE);
+    start..(start + len).min(MEMORY_SIZE)
+}
+
+fn access(instruction: Instruction, mem_reg: u16) -> Access {
+    let flag = 1 << REG_FLAG;
+    let mut access = Access::default();
+    match instruction {
+        Instruction::SkipIfEqualNum { x, .. }
+        | Instruction::SkipIfNotEqualNum { x, .. }
+        | Instruction::SkipIfKeyPressed { x }
+        | Instruction::SkipIfKeyNotPressed { x }
+        | Instruction::SetDelayTimer { x }
+        | Instruction::SetSoundTimer { x }
+        | Instruction::AddMemReg { x }
+        | Instruction::SetMemRegToAsciiSprite { x }
+        | Instruction::SetMemRegToDigitSprite { x } => access.reg_reads = 1 << x,
+        Instruction::SkipIfEqualReg { x, y } | Instruction::SkipIfNotEqualReg { x, y } => {
+            access.reg_reads = 1 << x | 1 << y
+        }
+        Instruction::SetRegFromNum { x, .. }
+        | Instruction::SetRegRand { x, .. }
+        | Instruction::SetRegFromTimer { x }
+        | Instruction::WaitForKey { x } => access.reg_writes = 1 << x,
+        Instruction::AddNumToReg { x, .. } => {
+            access.reg_reads = 1 << x;
+            access.reg_writes = 1 << x;
+        }
+        Instruction::SetRegFromReg { x, y } => {
+            access.reg_reads = 1 << y;
+            access.reg_writes = 1 << x;
+        }
+        Instruction::BitwiseOr { x, y }
+        | Instruction::BitwiseAnd { x, y }
+        | Instruction::BitwiseXor { x, y } => {
+            access.reg_reads = 1 << x | 1 << y;
+            access.reg_writes = 1 << x;
+        }
+        Instruction::AddReg { x, y }
+        | Instruction::SubRightReg { x, y }
+        | Instruction::SubLeftReg { x, y } => {
+            access.reg_reads = 1 << x | 1 << y;
+            access.reg_writes = 1 << x | flag;
+        }
+        Instruction::ShiftRight { x, .. } | Instruction::ShiftLeft { x, .. } => {
+            access.reg_reads = 1 << x;
+            access.reg_writes = 1 << x | flag;
+        }
+        Instruction::JumpOffset { .. } => access.reg_reads = 1,
+        Instruction::DrawSprite { x, y, n } => {
+            access.reg_reads = 1 << x | 1 << y;
+            access.reg_writes = flag;
+            access.mem_reads = mem_range(mem_reg, n as usize);
+        }
+        Instruction::StoreBcd { x } => {
+            access.reg_reads = 1 << x;
+            access.mem_writes = mem_range(mem_reg, 3);
+        }
+        Instruction::StoreRegs { x } => {
+            access.reg_reads = regs_to(x);
+            access.mem_writes = mem_range(mem_reg, x as usize + 1);
+        }
+        Instruction::LoadRegs { x } => {
+            access.reg_writes = regs_to(x);
+            access.mem_reads = mem_range(mem_reg, x as usize + 1);
+        }
+        Instruction::SysCall { .. }
+        | Instruction::ClearDisplay
+        | Instruction::Return
+        | Instruction::Jump { .. }
+        | Instruction::Call { .. }
+        | Instruction::SetMemReg { .. }
+        | Instruction::Unknown(_) => {}
+    }
+    access
+}
+
+impl EmmaChip8 {
+    /// Enable the sanitizer, takes effect from the next [EmmaChip8::load_program]
+    ///
+    /// If `randomize` is true then registers and unused memory are filled with random values
+    /// when a program is loaded
+    pub fn enable_sanitizer(&mut self, randomize: bool) {
+        self.sanitizer = Some(Sanitizer::new(randomize));
+    }
+
+    pub fn disable_sanitizer(&mut self) {
+        self.sanitizer = None;
+    }
+
+    pub(crate) fn sanitize_fetch(&mut self, pc: u16) {
+        if let Some(sanitizer) = &mut self.sanitizer {
+            sanitizer.on_fetch(pc);
+        }
+    }
+
+    /// Run the sanitizer checks for an instruction at `pc` that is about to be executed
+    pub(crate) fn sanitize(&mut self, pc: u16, instruction: Instruction) {
+        if let Some(mut sanitizer) = self.sanitizer.take() {
+            sanitizer.on_execute(self, pc, instruction);
+            self.sanitizer = Some(sanitizer);
+        }
+    }
+
+    pub(crate) fn reset_sanitizer(&mut self, program_len: usize) {
+        if let Some(sanitizer) = &mut self.sanitizer {
+            sanitizer.reset(program_len);
+            if sanitizer.randomize {
+                let rng = fastrand::Rng::with_seed(self.rng);
+                for reg in self.registers.iter_mut() {
+                    *reg = rng.u8(..);
+                }
+                for (byte, set) in self.memory.iter_mut().zip(sanitizer.memory.iter()) {
+                    if !set {
+                        *byte = rng.u8(..);
+                    }
+                }
+                //so Cxnn doesn't repeat the values used for the fill
+                self.rng = rng.get_seed();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sanitizer::Issue::*;
+    use crate::sanitizer::{ProgramInfo, Report};
+    use crate::EmmaChip8;
+    use ec8_common::debug_info::{DebugInfo, LineInfo, RegionKind};
+
+    fn run(program: &[u8], steps: usize) -> Vec<Report> {
+        run_with_info(program, steps, ProgramInfo::default())
+    }
+
+    fn run_with_info(program: &[u8], steps: usize, info: ProgramInfo) -> Vec<Report> {
+        let mut ec8 = EmmaChip8::new();
+        ec8.enable_sanitizer(false);
+        if let Some(sanitizer) = &mut ec8.sanitizer {
+            sanitizer.set_program_info(info);
+        }
+        ec8.load_program(program).unwrap();
+        for _ in 0..steps {
+            ec8.run();
+        }
+        ec8.sanitizer.unwrap().reports().to_vec()
+    }
+
+    #[test]
+    fn check_registers() {
+        //V1 = 2, V0 += V1, V1 += V2
+        let reports = run(&[0x61, 0x02, 0x80, 0x14, 0x81, 0x24], 3);
+        assert_eq!(
+            reports,
+            vec![
+                Report {
+                    pc: 0x202,
+                    issue: UninitializedRegister(0)
+                },
+                Report {
+                    pc: 0x204,
+                    issue: UninitializedRegister(2)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn check_memory() {
+        //V0 = 0, I = 0x300, V0 = mem[I], store V0 at I, V0 = mem[I], I = 0x200 (program), draw 2 rows
+        let program = [
+            0x60, 0x00, 0xA3, 0x00, 0xF0, 0x65, 0xF0, 0x55, 0xF0, 0x65, 0xA2, 0x00, 0xD0, 0x02,
+        ];
+        let reports = run(&program, 7);
+        assert_eq!(
+            reports,
+            vec![Report {
+                pc: 0x204,
+                issue: UninitializedMemory(0x300)
+            }]
+        );
+    }
+
+    #[test]
+    fn check_randomized() {
+        let mut ec8 = EmmaChip8::new();
+        ec8.set_seed(3);
+        ec8.enable_sanitizer(true);
+        ec8.load_program(&[0x12, 0x00]).unwrap();
+        assert_ne!(ec8.registers, [0; 16]);
+        assert_eq!(&ec8.memory[0x200..0x202], &[0x12, 0x00]);
+        assert_ne!(&ec8.memory[0x300..0x310], &[0; 16]);
+        //the fill advances the seed, so random numbers don't repeat it
+        assert_ne!(ec8.rng, 3);
+        let registers = ec8.registers;
+        ec8.set_seed(3);
+        ec8.load_program(&[0x12, 0x00]).unwrap();
+        assert_eq!(ec8.registers, registers);
+    }
+
+    #[test]
+    fn check_control_flow() {
+        //Call 0x206, jump to 0x20B, V0 = 0, return, return
+        let reports = run(
+            &[0x22, 0x06, 0x12, 0x0B, 0x00, 0x00, 0x60, 0x00, 0x00, 0xEE],
+            5,
+        );
+        assert_eq!(
+            reports,
+            vec![
+                Report {
+                    pc: 0x202,
+                    issue: OddAddress(0x20B)
+                },
+                Report {
+                    pc: 0x20B,
+                    issue: OutsideProgram
+                },
+            ]
+        );
+
+        //V0 = 0x00, V1 = 0xEE, I = 0x208, store V0..V1 at I (overwriting the jump with return)
+        let program = [0x60, 0x00, 0x61, 0xEE, 0xA2, 0x08, 0xF1, 0x55, 0x12, 0x08];
+        let reports = run(&program, 5);
+        assert_eq!(
+            reports,
+            vec![
+                Report {
+                    pc: 0x206,
+                    issue: SelfModifying(0x208)
+                },
+                Report {
+                    pc: 0x206,
+                    issue: SelfModifying(0x209)
+                },
+                Report {
+                    pc: 0x208,
+                    issue: ReturnEmptyStack
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn check_program_info() {
+        let mut debug = DebugInfo::new(vec![String::from("test.eca")]);
+        let line = |addr, len, kind| LineInfo {
+            addr,
+            len,
+            kind,
+            file: 0,
+            line: 1,
+            column: 1,
+        };
+        debug.add_line(line(0x200, 2, RegionKind::Code));
+        debug.add_line(line(0x202, 2, RegionKind::Data));
+        debug.add_line(line(0x204, 2, RegionKind::Code));
+        debug.add_data("table", 0x202, 2);
+        let info = ProgramInfo::from_debug_info(&debug);
+        assert_eq!(info.data, vec![0x202..0x204]);
+
+        let mut ec8 = EmmaChip8::new();
+        ec8.enable_sanitizer(false);
+        ec8.sanitizer.as_mut().unwrap().set_program_info(info);
+        //Jump to 0x202 (data)
+        ec8.load_program(&[0x12, 0x02, 0x00, 0x00]).unwrap();
+        ec8.run();
+        ec8.run();
+        let sanitizer = ec8.sanitizer.unwrap();
+        assert_eq!(
+            sanitizer.reports(),
+            &[Report {
+                pc: 0x202,
+                issue: ExecutingData
+            }]
+        );
+        assert_eq!(
+            sanitizer.describe(&sanitizer.reports()[0]),
+            "data `table` +0: executing data"
+        );
+        assert_eq!(sanitizer.info.name(0x203), "data `table` +1");
+        assert_eq!(sanitizer.info.name(0x205), "205");
+    }
+}
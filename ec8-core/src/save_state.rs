@@ -0,0 +1,230 @@
+//! Save states
+//!
+//! Format (all numbers big endian)
+//! `EC8S` then version (1 byte)
+//! PC, I (2 bytes each)
+//! Delay, sound (1 byte each)
+//! Registers (16 bytes)
+//! Memory (4096 bytes)
+//! Stack size (1 byte), stack (2 bytes per address)
+//! Output (1 byte per pixel)
+//! State (2 bytes, kind and key register)
+//! Keys (1 byte per key)
+//! RNG (8 bytes)
+
+use crate::error::ECoreError::InvalidSaveState;
+use crate::error::ECoreResult;
+use crate::{EmmaChip8, State};
+use ec8_common::{BUTTON_COUNT, MAX_STACK_COUNT, MEMORY_SIZE, PIXEL_COUNT, REGISTER_COUNT};
+use std::collections::VecDeque;
+
+const MAGIC: [u8; 4] = *b"EC8S";
+const VERSION: u8 = 1;
+
+impl EmmaChip8 {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(MEMORY_SIZE + PIXEL_COUNT + 128);
+        output.extend_from_slice(&MAGIC);
+        output.push(VERSION);
+        output.extend_from_slice(&self.pc.to_be_bytes());
+        output.extend_from_slice(&self.mem_reg.to_be_bytes());
+        output.push(self.delay);
+        output.push(self.sound);
+        output.extend_from_slice(&self.registers);
+        output.extend_from_slice(&self.memory);
+        output.push(self.stack.len() as u8);
+        for addr in &self.stack {
+            output.extend_from_slice(&addr.to_be_bytes());
+        }
+        output.extend(self.output.iter().map(|pixel| *pixel as u8));
+        output.extend_from_slice(&state_to_bytes(self.state));
+        output.extend(self.keys.iter().map(|key| *key as u8));
+        output.extend_from_slice(&self.rng.to_be_bytes());
+        output
+    }
+
+    /// Replace the machine state with one made by [EmmaChip8::save_state]
+    ///
+    /// The machine is unchanged if the data is invalid, including if PC, I or any
+    /// return address is outside of memory or the stack is too deep
+    pub fn load_state(&mut self, data: &[u8]) -> ECoreResult<()> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC || reader.u8()? != VERSION {
+            return Err(InvalidSaveState);
+        }
+        let pc = reader.addr()?;
+        let mem_reg = reader.addr()?;
+        let delay = reader.u8()?;
+        let sound = reader.u8()?;
+        let mut registers = [0; REGISTER_COUNT];
+        registers.copy_from_slice(reader.take(REGISTER_COUNT)?);
+        let mut memory = [0; MEMORY_SIZE];
+        memory.copy_from_slice(reader.take(MEMORY_SIZE)?);
+        let stack_size = reader.u8()? as usize;
+        if stack_size > MAX_STACK_COUNT {
+            return Err(InvalidSaveState);
+        }
+        let mut stack = VecDeque::with_capacity(stack_size);
+        for _ in 0..stack_size {
+            stack.push_back(reader.addr()?);
+        }
+        let mut output = [false; PIXEL_COUNT];
+        for (pixel, value) in output.iter_mut().zip(reader.take(PIXEL_COUNT)?) {
+            *pixel = *value != 0;
+        }
+        let state = state_from_bytes(reader.u8()?, reader.u8()?)?;
+        let mut keys = [false; BUTTON_COUNT];
+        for (key, value) in keys.iter_mut().zip(reader.take(BUTTON_COUNT)?) {
+            *key = *value != 0;
+        }
+        let rng = u64::from_be_bytes(
+            reader
+                .take(8)?
+                .try_into()
+                .expect("Reader returned wrong number of bytes, please raise an issue"),
+        );
+        if reader.pos != data.len() {
+            return Err(InvalidSaveState);
+        }
+
+        self.pc = pc;
+        self.mem_reg = mem_reg;
+        self.delay = delay;
+        self.sound = sound;
+        self.registers = registers;
+        self.memory = memory;
+        self.stack = stack;
+        self.output = output;
+        self.state = state;
+        self.keys = keys;
+        self.rng = rng;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+fn state_to_bytes(state: State) -> [u8; 2] {
+    match state {
+        State::Waiting => [0, 0],
+        State::Running => [1, 0],
+        State::StackOverflow => [2, 0],
+        State::InvalidOpcode => [3, 0],
+        State::StackEmpty => [4, 0],
+        State::InvalidAscii => [5, 0],
+        State::WaitingForKey(reg) => [6, reg],
+    }
+}
+
+fn state_from_bytes(kind: u8, reg: u8) -> ECoreResult<State> {
+    match kind {
+        0 => Ok(State::Waiting),
+        1 => Ok(State::Running),
+        2 => Ok(State::StackOverflow),
+        3 => Ok(State::InvalidOpcode),
+        4 => Ok(State::StackEmpty),
+        5 => Ok(State::InvalidAscii),
+        6 if (reg as usize) < REGISTER_COUNT => Ok(State::WaitingForKey(reg)),
+        _ => Err(InvalidSaveState),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> ECoreResult<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + count)
+            .ok_or(InvalidSaveState)?;
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> ECoreResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> ECoreResult<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Read a memory address, failing if it's outside of memory
+    fn addr(&mut self) -> ECoreResult<u16> {
+        let addr = self.u16()?;
+        if addr as usize >= MEMORY_SIZE {
+            return Err(InvalidSaveState);
+        }
+        Ok(addr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ECoreError::InvalidSaveState;
+    use crate::EmmaChip8;
+    use ec8_common::{MAX_STACK_COUNT, MEMORY_SIZE, REGISTER_COUNT};
+
+    #[test]
+    fn check_round_trip() {
+        let mut ec8 = EmmaChip8::new();
+        //Call 0x204, draw 0 at 0,0, wait for key in V3
+        ec8.load_program(&[0x22, 0x04, 0x00, 0x00, 0xD0, 0x05, 0xF3, 0x0A])
+            .unwrap();
+        ec8.run();
+        ec8.run();
+        ec8.run();
+        let saved = ec8.save_state();
+
+        let mut loaded = EmmaChip8::new();
+        loaded.load_state(&saved).unwrap();
+        assert_eq!(loaded.save_state(), saved);
+        assert_eq!(loaded.pc, ec8.pc);
+        assert_eq!(loaded.stack, ec8.stack);
+        assert_eq!(loaded.state, ec8.state);
+        assert_eq!(loaded.output, ec8.output);
+        assert_eq!(loaded.rng, ec8.rng);
+    }
+
+    #[test]
+    fn check_invalid() {
+        let mut ec8 = EmmaChip8::new();
+        let saved = ec8.save_state();
+        assert_eq!(ec8.load_state(&saved[..10]), Err(InvalidSaveState));
+        assert_eq!(ec8.load_state(&[]), Err(InvalidSaveState));
+        let mut extra = saved.clone();
+        extra.push(0);
+        assert_eq!(ec8.load_state(&extra), Err(InvalidSaveState));
+        let mut wrong_magic = saved;
+        wrong_magic[0] = b'X';
+        assert_eq!(ec8.load_state(&wrong_magic), Err(InvalidSaveState));
+    }
+
+    #[test]
+    fn check_out_of_range() {
+        let mut ec8 = EmmaChip8::new();
+        //Call 0x202
+        ec8.load_program(&[0x22, 0x02]).unwrap();
+        ec8.run();
+        let saved = ec8.save_state();
+        let stack_pos = 5 + 4 + 2 + REGISTER_COUNT + MEMORY_SIZE;
+        assert_eq!(saved[stack_pos], 1);
+
+        let mut bad_pc = saved.clone();
+        bad_pc[5] = 0x10;
+        assert_eq!(ec8.load_state(&bad_pc), Err(InvalidSaveState));
+        let mut bad_i = saved.clone();
+        bad_i[7] = 0xFF;
+        assert_eq!(ec8.load_state(&bad_i), Err(InvalidSaveState));
+        let mut bad_return = saved.clone();
+        bad_return[stack_pos + 1] = 0x20;
+        assert_eq!(ec8.load_state(&bad_return), Err(InvalidSaveState));
+        let mut too_deep = saved.clone();
+        too_deep[stack_pos] = MAX_STACK_COUNT as u8 + 1;
+        assert_eq!(ec8.load_state(&too_deep), Err(InvalidSaveState));
+        assert_eq!(ec8.save_state(), saved);
+    }
+}
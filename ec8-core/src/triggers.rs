@@ -0,0 +1,219 @@
+//! Triggers
+//!
+//! Conditions over the machine state that are checked every frame, used for achievements
+//! and for assertions in automated tests
+//!
+//! File format
+//! ```text
+//! ;Comments start with a semicolon
+//! rom 8f3a0c1d2e4b5a69
+//! Got key: prev(mem[0x3A0]) == 0 && mem[0x3A0] == 1
+//! Rich: v5 >= 10 for 60
+//! ```
+//! `rom` is optional and is the [program_hash](crate::program_hash) of the program the triggers are for
+//!
+//! Each trigger is `name: condition`, see [crate::expression] for the syntax,
+//! `prev(..)` reads the state from the previous frame
+//!
+//! `for n` means the condition has to be true for `n` frames in a row
+
+use crate::expression::Expression;
+use crate::{program_hash, EmmaChip8};
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub name: String,
+    pub condition: Expression,
+    /// Number of consecutive frames the condition must be true for
+    pub frames: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TriggerSet {
+    pub rom_hash: Option<u64>,
+    pub triggers: Vec<Trigger>,
+}
+
+impl TriggerSet {
+    /// Returns true if these triggers are for `program` (or aren't tied to a program)
+    pub fn is_for(&self, program: &[u8]) -> bool {
+        self.rom_hash
+            .map(|hash| hash == program_hash(program))
+            .unwrap_or(true)
+    }
+}
+
+impl FromStr for TriggerSet {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut set = TriggerSet::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(hash) = line.strip_prefix("rom ") {
+                let hash = u64::from_str_radix(hash.trim(), 16)
+                    .map_err(|_| format!("Line {}) Invalid ROM hash '{}'", i + 1, hash.trim()))?;
+                set.rom_hash = Some(hash);
+                continue;
+            }
+            let (name, condition) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Line {}) Expected 'name: condition'", i + 1))?;
+            let (condition, frames) = match condition.rsplit_once(" for ") {
+                Some((condition, frames)) => {
+                    let frames = frames
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|frames| *frames > 0)
+                        .ok_or_else(|| {
+                            format!("Line {}) Invalid frame count '{}'", i + 1, frames.trim())
+                        })?;
+                    (condition, frames)
+                }
+                None => (condition, 1),
+            };
+            let condition = condition
+                .parse::<Expression>()
+                .map_err(|err| format!("Line {}) {err}", i + 1))?;
+            set.triggers.push(Trigger {
+                name: name.trim().to_string(),
+                condition,
+                frames,
+            });
+        }
+        Ok(set)
+    }
+}
+
+/// Checks triggers every frame, once unlocked a trigger stays unlocked
+#[derive(Debug, Clone)]
+pub struct TriggerEngine {
+    triggers: Vec<Trigger>,
+    /// Number of frames in a row each condition has been true
+    held: Vec<usize>,
+    /// Frame each trigger was unlocked on
+    unlocked: Vec<Option<usize>>,
+    frame: usize,
+    previous: Option<EmmaChip8>,
+}
+
+impl TriggerEngine {
+    pub fn new(set: TriggerSet) -> Self {
+        let count = set.triggers.len();
+        Self {
+            triggers: set.triggers,
+            held: vec![0; count],
+            unlocked: vec![None; count],
+            frame: 0,
+            previous: None,
+        }
+    }
+
+    /// Check all locked triggers, should be called after every frame
+    ///
+    /// Returns the triggers unlocked by this frame
+    pub fn update(&mut self, machine: &EmmaChip8) -> Vec<&Trigger> {
+        let previous = self.previous.as_ref().unwrap_or(machine);
+        let mut newly_unlocked = vec![];
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if self.unlocked[i].is_some() {
+                continue;
+            }
+            if trigger.condition.is_true(machine, previous) {
+                self.held[i] += 1;
+                if self.held[i] >= trigger.frames {
+                    self.unlocked[i] = Some(self.frame);
+                    newly_unlocked.push(trigger);
+                }
+            } else {
+                self.held[i] = 0;
+            }
+        }
+        self.frame += 1;
+        self.previous = Some(machine.clone());
+        newly_unlocked
+    }
+
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    /// Unlocked triggers and the frame they were unlocked on
+    pub fn unlocked(&self) -> Vec<(&Trigger, usize)> {
+        self.triggers
+            .iter()
+            .zip(self.unlocked.iter())
+            .filter_map(|(trigger, frame)| frame.map(|frame| (trigger, frame)))
+            .collect()
+    }
+
+    pub fn locked(&self) -> Vec<&Trigger> {
+        self.triggers
+            .iter()
+            .zip(self.unlocked.iter())
+            .filter(|(_, frame)| frame.is_none())
+            .map(|(trigger, _)| trigger)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::program_hash;
+    use crate::triggers::{TriggerEngine, TriggerSet};
+    use crate::EmmaChip8;
+
+    #[test]
+    fn check_parse() {
+        let set = "; test\nrom 00000000000000ff\nFirst: v0 == 1\nHeld : v1 >= 2 for 3 ;comment"
+            .parse::<TriggerSet>()
+            .unwrap();
+        assert_eq!(set.rom_hash, Some(0xFF));
+        assert_eq!(set.triggers.len(), 2);
+        assert_eq!(set.triggers[1].name, "Held");
+        assert_eq!(set.triggers[1].frames, 3);
+        assert!(!set.is_for(&[0x12, 0x00]));
+
+        assert_eq!(
+            "a: v0 for 0".parse::<TriggerSet>().unwrap_err(),
+            "Line 1) Invalid frame count '0'"
+        );
+        assert!("\nno condition".parse::<TriggerSet>().is_err());
+        assert!("a: v0 +".parse::<TriggerSet>().is_err());
+
+        let program = [0x12, 0x00];
+        let set = format!("rom {:016x}", program_hash(&program))
+            .parse::<TriggerSet>()
+            .unwrap();
+        assert!(set.is_for(&program));
+    }
+
+    #[test]
+    fn check_engine() {
+        let set = "Changed: prev(mem[0x300]) == 0 && mem[0x300] == 1\nHeld: v5 >= 10 for 3"
+            .parse::<TriggerSet>()
+            .unwrap();
+        let mut engine = TriggerEngine::new(set);
+        let mut ec8 = EmmaChip8::new();
+        ec8.load_program(&[0x12, 0x00]).unwrap();
+        ec8.memory[0x300] = 1;
+        assert!(engine.update(&ec8).is_empty());
+        ec8.memory[0x300] = 0;
+        ec8.registers[5] = 10;
+        assert!(engine.update(&ec8).is_empty());
+        ec8.memory[0x300] = 1;
+        let unlocked = engine.update(&ec8);
+        assert_eq!(unlocked.len(), 1);
+        assert_eq!(unlocked[0].name, "Changed");
+        let unlocked = engine.update(&ec8);
+        assert_eq!(unlocked[0].name, "Held");
+        assert!(engine.update(&ec8).is_empty());
+        assert!(engine.locked().is_empty());
+        assert_eq!(engine.unlocked()[1].1, 3);
+    }
+}
@@ -0,0 +1,400 @@
+//! EmmaChip8 C API
+//!
+//! The header is generated by cbindgen into `ec8.h` in the build script's `OUT_DIR` when the
+//! crate is built
+//!
+//! All functions taking a machine pointer must be given a pointer returned by `ec8_new`
+//! that has not been passed to `ec8_free`
+//!
+//! Panics never cross into C, functions return `EC8_RESULT_PANICKED` instead and the machine
+//! should be freed
+
+use ec8_common::{BUTTON_COUNT, MAX_X, MAX_Y, PIXEL_COUNT};
+use ec8_core::error::ECoreError;
+use ec8_core::input::Key;
+use ec8_core::{EmmaChip8, State, INSTRUCTIONS_PER_FRAME};
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+pub const EC8_SCREEN_WIDTH: usize = 63;
+pub const EC8_SCREEN_HEIGHT: usize = 31;
+pub const EC8_PIXEL_COUNT: usize = 1953;
+pub const EC8_KEY_COUNT: usize = 16;
+pub const EC8_INSTRUCTIONS_PER_FRAME: u32 = 16;
+
+const _: () = assert!(EC8_SCREEN_WIDTH == MAX_X);
+const _: () = assert!(EC8_SCREEN_HEIGHT == MAX_Y);
+const _: () = assert!(EC8_PIXEL_COUNT == PIXEL_COUNT);
+const _: () = assert!(EC8_KEY_COUNT == BUTTON_COUNT);
+const _: () = assert!(EC8_INSTRUCTIONS_PER_FRAME as usize == INSTRUCTIONS_PER_FRAME);
+
+/// Opaque emulator instance
+pub struct Ec8Machine {
+    machine: EmmaChip8,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Ec8Result {
+    Ok = 0,
+    NullPointer = 1,
+    ProgramTooLarge = 2,
+    InvalidSaveState = 3,
+    InvalidKey = 4,
+    BufferTooSmall = 5,
+    /// The emulator panicked, the machine may be in an invalid state
+    Panicked = 6,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Ec8State {
+    Waiting = 0,
+    Running = 1,
+    StackOverflow = 2,
+    InvalidOpcode = 3,
+    StackEmpty = 4,
+    InvalidAscii = 5,
+    WaitingForKey = 6,
+}
+
+impl From<State> for Ec8State {
+    fn from(state: State) -> Self {
+        match state {
+            State::Waiting => Ec8State::Waiting,
+            State::Running => Ec8State::Running,
+            State::StackOverflow => Ec8State::StackOverflow,
+            State::InvalidOpcode => Ec8State::InvalidOpcode,
+            State::StackEmpty => Ec8State::StackEmpty,
+            State::InvalidAscii => Ec8State::InvalidAscii,
+            State::WaitingForKey(_) => Ec8State::WaitingForKey,
+        }
+    }
+}
+
+impl From<ECoreError> for Ec8Result {
+    fn from(err: ECoreError) -> Self {
+        match err {
+            ECoreError::ProgramTooLarge => Ec8Result::ProgramTooLarge,
+            ECoreError::InvalidSaveState => Ec8Result::InvalidSaveState,
+        }
+    }
+}
+
+/// Run `func`, turning a panic into `Ec8Result::Panicked` as unwinding into C is undefined behaviour
+fn guard(func: impl FnOnce() -> Ec8Result) -> Ec8Result {
+    panic::catch_unwind(AssertUnwindSafe(func)).unwrap_or(Ec8Result::Panicked)
+}
+
+/// Create a new machine, it must be freed with `ec8_free`
+///
+/// Returns NULL if the machine couldn't be created
+#[no_mangle]
+pub extern "C" fn ec8_new() -> *mut Ec8Machine {
+    panic::catch_unwind(|| {
+        Box::into_raw(Box::new(Ec8Machine {
+            machine: EmmaChip8::new(),
+        }))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Destroy a machine made by `ec8_new`, passing NULL does nothing
+///
+/// # Safety
+///
+/// `machine` must be NULL or from `ec8_new` and not already freed
+#[no_mangle]
+pub unsafe extern "C" fn ec8_free(machine: *mut Ec8Machine) -> Ec8Result {
+    guard(|| {
+        if !machine.is_null() {
+            drop(Box::from_raw(machine));
+        }
+        Ec8Result::Ok
+    })
+}
+
+/// Load a program and reset the machine
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`, `data` must point to at least `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn ec8_load_rom(
+    machine: *mut Ec8Machine,
+    data: *const u8,
+    len: usize,
+) -> Ec8Result {
+    guard(|| {
+        let (Some(machine), false) = (machine.as_mut(), data.is_null()) else {
+            return Ec8Result::NullPointer;
+        };
+        let program = slice::from_raw_parts(data, len);
+        match machine.machine.load_program(program) {
+            Ok(_) => Ec8Result::Ok,
+            Err(err) => err.into(),
+        }
+    })
+}
+
+/// Set the random number generator seed, the same seed and inputs always give the same output
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`
+#[no_mangle]
+pub unsafe extern "C" fn ec8_set_seed(machine: *mut Ec8Machine, seed: u64) -> Ec8Result {
+    guard(|| {
+        let Some(machine) = machine.as_mut() else {
+            return Ec8Result::NullPointer;
+        };
+        machine.machine.set_seed(seed);
+        Ec8Result::Ok
+    })
+}
+
+/// Run one 60Hz frame of `instructions` instructions (`EC8_INSTRUCTIONS_PER_FRAME` is the default)
+/// and then update the timers
+///
+/// `state` is set to the state after the frame
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`, `state` must point to an `Ec8State`
+#[no_mangle]
+pub unsafe extern "C" fn ec8_run_frame(
+    machine: *mut Ec8Machine,
+    instructions: u32,
+    state: *mut Ec8State,
+) -> Ec8Result {
+    guard(|| {
+        let (Some(machine), Some(state)) = (machine.as_mut(), state.as_mut()) else {
+            return Ec8Result::NullPointer;
+        };
+        machine.machine.run_frame(instructions as usize);
+        *state = machine.machine.state.into();
+        Ec8Result::Ok
+    })
+}
+
+/// # Safety
+///
+/// `machine` must be from `ec8_new`, `state` must point to an `Ec8State`
+#[no_mangle]
+pub unsafe extern "C" fn ec8_get_state(
+    machine: *const Ec8Machine,
+    state: *mut Ec8State,
+) -> Ec8Result {
+    guard(|| {
+        let (Some(machine), Some(state)) = (machine.as_ref(), state.as_mut()) else {
+            return Ec8Result::NullPointer;
+        };
+        *state = machine.machine.state.into();
+        Ec8Result::Ok
+    })
+}
+
+/// Set `playing` to true if the sound timer is active
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`, `playing` must point to a `bool`
+#[no_mangle]
+pub unsafe extern "C" fn ec8_is_sound_playing(
+    machine: *const Ec8Machine,
+    playing: *mut bool,
+) -> Ec8Result {
+    guard(|| {
+        let (Some(machine), Some(playing)) = (machine.as_ref(), playing.as_mut()) else {
+            return Ec8Result::NullPointer;
+        };
+        *playing = machine.machine.sound > 0;
+        Ec8Result::Ok
+    })
+}
+
+/// Press or release a key, `key` is 0x0 to 0xF
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`
+#[no_mangle]
+pub unsafe extern "C" fn ec8_set_key(
+    machine: *mut Ec8Machine,
+    key: u8,
+    pressed: bool,
+) -> Ec8Result {
+    guard(|| {
+        let Some(machine) = machine.as_mut() else {
+            return Ec8Result::NullPointer;
+        };
+        let Some(key) = Key::from_index(key as usize) else {
+            return Ec8Result::InvalidKey;
+        };
+        if pressed {
+            machine.machine.on_key_pressed(key);
+        } else {
+            machine.machine.on_key_released(key);
+        }
+        Ec8Result::Ok
+    })
+}
+
+/// Copy the display into `out`, row by row, 1 for set pixels and 0 for unset
+///
+/// `len` must be at least `EC8_PIXEL_COUNT`
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`, `out` must point to at least `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn ec8_get_framebuffer(
+    machine: *const Ec8Machine,
+    out: *mut u8,
+    len: usize,
+) -> Ec8Result {
+    guard(|| {
+        let (Some(machine), false) = (machine.as_ref(), out.is_null()) else {
+            return Ec8Result::NullPointer;
+        };
+        if len < PIXEL_COUNT {
+            return Ec8Result::BufferTooSmall;
+        }
+        let out = slice::from_raw_parts_mut(out, PIXEL_COUNT);
+        for (byte, pixel) in out.iter_mut().zip(machine.machine.output.iter()) {
+            *byte = *pixel as u8;
+        }
+        Ec8Result::Ok
+    })
+}
+
+/// Write the machine state into `out`
+///
+/// `written` is always set to the size of the state, if `out` is NULL or `len` is too small
+/// then nothing is copied and `EC8_RESULT_BUFFER_TOO_SMALL` is returned
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`, `out` must be NULL or point to at least `len` bytes,
+/// `written` must point to a `size_t`
+#[no_mangle]
+pub unsafe extern "C" fn ec8_save_state(
+    machine: *const Ec8Machine,
+    out: *mut u8,
+    len: usize,
+    written: *mut usize,
+) -> Ec8Result {
+    guard(|| {
+        let (Some(machine), Some(written)) = (machine.as_ref(), written.as_mut()) else {
+            return Ec8Result::NullPointer;
+        };
+        let state = machine.machine.save_state();
+        *written = state.len();
+        if out.is_null() || len < state.len() {
+            return Ec8Result::BufferTooSmall;
+        }
+        slice::from_raw_parts_mut(out, state.len()).copy_from_slice(&state);
+        Ec8Result::Ok
+    })
+}
+
+/// Restore a state made by `ec8_save_state`, the machine is unchanged if the state is invalid
+///
+/// # Safety
+///
+/// `machine` must be from `ec8_new`, `data` must point to at least `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn ec8_load_state(
+    machine: *mut Ec8Machine,
+    data: *const u8,
+    len: usize,
+) -> Ec8Result {
+    guard(|| {
+        let (Some(machine), false) = (machine.as_mut(), data.is_null()) else {
+            return Ec8Result::NullPointer;
+        };
+        match machine.machine.load_state(slice::from_raw_parts(data, len)) {
+            Ok(_) => Ec8Result::Ok,
+            Err(err) => err.into(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ptr::null_mut;
+
+    #[test]
+    fn check_lifecycle() {
+        unsafe {
+            let machine = ec8_new();
+            let program = [0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04];
+            assert_eq!(
+                ec8_load_rom(machine, program.as_ptr(), program.len()),
+                Ec8Result::Ok
+            );
+            let mut run_state = Ec8State::Waiting;
+            assert_eq!(
+                ec8_run_frame(machine, EC8_INSTRUCTIONS_PER_FRAME, &mut run_state),
+                Ec8Result::Ok
+            );
+            assert_eq!(run_state, Ec8State::Running);
+            let mut pixels = [0_u8; EC8_PIXEL_COUNT];
+            assert_eq!(
+                ec8_get_framebuffer(machine, pixels.as_mut_ptr(), pixels.len()),
+                Ec8Result::Ok
+            );
+            assert_eq!(&pixels[..4], &[1, 1, 1, 1]);
+
+            let mut size = 0;
+            assert_eq!(
+                ec8_save_state(machine, null_mut(), 0, &mut size),
+                Ec8Result::BufferTooSmall
+            );
+            let mut state = vec![0; size];
+            assert_eq!(
+                ec8_save_state(machine, state.as_mut_ptr(), state.len(), &mut size),
+                Ec8Result::Ok
+            );
+            assert_eq!(ec8_set_key(machine, 16, true), Ec8Result::InvalidKey);
+            assert_eq!(
+                ec8_load_state(machine, state.as_ptr(), state.len() - 1),
+                Ec8Result::InvalidSaveState
+            );
+            assert_eq!(
+                ec8_load_state(machine, state.as_ptr(), state.len()),
+                Ec8Result::Ok
+            );
+            assert_eq!(ec8_free(machine), Ec8Result::Ok);
+        }
+    }
+
+    #[test]
+    fn check_guard() {
+        assert_eq!(guard(|| panic!("test")), Ec8Result::Panicked);
+        assert_eq!(guard(|| Ec8Result::Ok), Ec8Result::Ok);
+    }
+
+    #[test]
+    fn check_null() {
+        unsafe {
+            assert_eq!(
+                ec8_load_rom(null_mut(), null_mut(), 0),
+                Ec8Result::NullPointer
+            );
+            assert_eq!(ec8_set_key(null_mut(), 1, true), Ec8Result::NullPointer);
+            let mut state = Ec8State::Running;
+            assert_eq!(
+                ec8_run_frame(null_mut(), 1, &mut state),
+                Ec8Result::NullPointer
+            );
+            assert_eq!(
+                ec8_get_state(null_mut(), &mut state),
+                Ec8Result::NullPointer
+            );
+            assert_eq!(state, Ec8State::Running);
+            assert_eq!(ec8_free(null_mut()), Ec8Result::Ok);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+#include <stdio.h>
+#include <string.h>
+#include "ec8.h"
+
+#define CHECK(cond)                                                  \
+    if (!(cond)) {                                                   \
+        fprintf(stderr, "%s:%d check failed: %s\n", __FILE__, __LINE__, #cond); \
+        return 1;                                                    \
+    }
+
+int main(void) {
+    /* I = 0, draw '0' at V0,V0, wait for key in V1, jump to self */
+    const uint8_t rom[] = {0xA0, 0x00, 0xD0, 0x05, 0xF1, 0x0A, 0x12, 0x06};
+    uint8_t pixels[EC8_PIXEL_COUNT];
+    uint8_t state[8192];
+    size_t state_size = 0;
+    Ec8State run_state = EC8_STATE_WAITING;
+    bool playing = true;
+
+    Ec8Machine *machine = ec8_new();
+    CHECK(machine != NULL);
+    CHECK(ec8_load_rom(machine, rom, sizeof(rom)) == EC8_RESULT_OK);
+    CHECK(ec8_set_seed(machine, 1234) == EC8_RESULT_OK);
+
+    CHECK(ec8_run_frame(machine, EC8_INSTRUCTIONS_PER_FRAME, &run_state) == EC8_RESULT_OK);
+    CHECK(run_state == EC8_STATE_WAITING_FOR_KEY);
+    CHECK(ec8_get_framebuffer(machine, pixels, sizeof(pixels)) == EC8_RESULT_OK);
+    CHECK(pixels[0] == 1 && pixels[3] == 1 && pixels[4] == 0);
+    CHECK(pixels[EC8_SCREEN_WIDTH] == 1 && pixels[EC8_SCREEN_WIDTH + 1] == 0);
+    CHECK(ec8_get_framebuffer(machine, pixels, 10) == EC8_RESULT_BUFFER_TOO_SMALL);
+
+    CHECK(ec8_save_state(machine, NULL, 0, &state_size) == EC8_RESULT_BUFFER_TOO_SMALL);
+    CHECK(state_size > 0 && state_size <= sizeof(state));
+    CHECK(ec8_save_state(machine, state, sizeof(state), &state_size) == EC8_RESULT_OK);
+
+    CHECK(ec8_set_key(machine, 0x20, true) == EC8_RESULT_INVALID_KEY);
+    CHECK(ec8_set_key(machine, 0xA, true) == EC8_RESULT_OK);
+    CHECK(ec8_run_frame(machine, EC8_INSTRUCTIONS_PER_FRAME, &run_state) == EC8_RESULT_OK);
+    CHECK(run_state == EC8_STATE_RUNNING);
+    CHECK(ec8_set_key(machine, 0xA, false) == EC8_RESULT_OK);
+
+    CHECK(ec8_load_state(machine, state, state_size - 1) == EC8_RESULT_INVALID_SAVE_STATE);
+    CHECK(ec8_load_state(machine, state, state_size) == EC8_RESULT_OK);
+    CHECK(ec8_get_state(machine, &run_state) == EC8_RESULT_OK);
+    CHECK(run_state == EC8_STATE_WAITING_FOR_KEY);
+    CHECK(ec8_is_sound_playing(machine, &playing) == EC8_RESULT_OK);
+    CHECK(!playing);
+
+    CHECK(ec8_run_frame(NULL, EC8_INSTRUCTIONS_PER_FRAME, &run_state) == EC8_RESULT_NULL_POINTER);
+    CHECK(ec8_free(machine) == EC8_RESULT_OK);
+    printf("ok\n");
+    return 0;
+}
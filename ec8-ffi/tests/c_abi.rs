@@ -0,0 +1,55 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Compile `tests/c/abi_test.c` against the static library and generated header and run it
+#[test]
+fn check_c_program() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = env::current_exe()
+        .expect("Unable to get test path")
+        .parent()
+        .and_then(|deps| deps.parent())
+        .expect("Unable to find target dir")
+        .to_path_buf();
+    // `cargo test` only builds the rlib, so build the static library as well
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--offline", "--manifest-path"])
+        .arg(crate_dir.join("Cargo.toml"))
+        .status()
+        .expect("Unable to run cargo");
+    assert!(status.success(), "Unable to build static library");
+    let library = target_dir.join("libec8.a");
+    assert!(
+        library.is_file(),
+        "Static library not found at {}",
+        library.display()
+    );
+    let output = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("abi_test");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(env!("OUT_DIR"))
+        .arg(crate_dir.join("tests").join("c").join("abi_test.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .expect("Unable to run C compiler");
+    assert!(status.success(), "C test program failed to compile");
+
+    let result = Command::new(&output)
+        .output()
+        .expect("Unable to run C test program");
+    assert!(
+        result.status.success(),
+        "C test program failed: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&result.stdout).trim(), "ok");
+}
@@ -0,0 +1,106 @@
+use crate::error::GymError::WrongActionCount;
+use crate::error::GymResult;
+use crate::{Environment, Observation};
+use ec8_core::input::Key;
+use std::thread;
+
+/// Group of environments that are reset and stepped together
+///
+/// Stepping is spread across the available threads
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub envs: Vec<Environment>,
+}
+
+impl Batch {
+    pub fn new(envs: Vec<Environment>) -> Self {
+        Self { envs }
+    }
+
+    /// Create `count` copies of `env`, each seeded with `first_seed + index`
+    pub fn from_env(env: &Environment, count: usize, first_seed: u64) -> Self {
+        let envs = (0..count)
+            .map(|i| {
+                let mut env = env.clone();
+                env.set_seed(first_seed.wrapping_add(i as u64));
+                env.reset();
+                env
+            })
+            .collect();
+        Self { envs }
+    }
+
+    pub fn reset_all(&mut self) -> Vec<Observation> {
+        self.envs.iter_mut().map(|env| env.reset()).collect()
+    }
+
+    /// Step every environment, `actions` must have one entry per environment
+    ///
+    /// # Errors
+    /// If the number of actions doesn't match the number of environments
+    pub fn step_all(&mut self, actions: &[Vec<Key>]) -> GymResult<Vec<(Observation, f32, bool)>> {
+        if actions.len() != self.envs.len() {
+            return Err(WrongActionCount {
+                envs: self.envs.len(),
+                actions: actions.len(),
+            });
+        }
+        let threads = thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(1);
+        let chunk_size = self.envs.len().div_ceil(threads).max(1);
+        Ok(thread::scope(|scope| {
+            let handles = self
+                .envs
+                .chunks_mut(chunk_size)
+                .zip(actions.chunks(chunk_size))
+                .map(|(envs, actions)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .zip(actions)
+                            .map(|(env, action)| env.step(action))
+                            .collect::<Vec<(Observation, f32, bool)>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("Environment step panicked"))
+                .collect()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::batch::Batch;
+    use crate::error::GymError::WrongActionCount;
+    use crate::Environment;
+    use ec8_core::input::Key;
+
+    #[test]
+    fn check_step_all() {
+        //V0 += 1, skip if key in V1 (K0) not pressed, V2 += 1, jump to start
+        let program = [0x70, 0x01, 0xE1, 0xA1, 0x72, 0x01, 0x12, 0x00];
+        let env = Environment::new(&program, "v2", "0").unwrap();
+        let mut batch = Batch::from_env(&env, 5, 0);
+        let mut actions = vec![vec![]; 5];
+        actions[3] = vec![Key::K0];
+        let results = batch.step_all(&actions).unwrap();
+        assert_eq!(results.len(), 5);
+        for (i, (_, reward, _)) in results.iter().enumerate() {
+            if i == 3 {
+                assert!(*reward > 0.0);
+            } else {
+                assert_eq!(*reward, 0.0);
+            }
+        }
+        assert_eq!(
+            batch.step_all(&actions[..4]),
+            Err(WrongActionCount {
+                envs: 5,
+                actions: 4
+            })
+        );
+    }
+}
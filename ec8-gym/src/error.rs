@@ -0,0 +1,40 @@
+use ec8_core::error::ECoreError;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use GymError::*;
+
+pub type GymResult<T> = Result<T, GymError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GymError {
+    Core(ECoreError),
+    InvalidReward(String),
+    InvalidDone(String),
+    /// A batch was stepped with a different number of actions than environments
+    WrongActionCount {
+        envs: usize,
+        actions: usize,
+    },
+}
+
+impl Display for GymError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Core(err) => write!(f, "{err}"),
+            InvalidReward(err) => write!(f, "Invalid reward expression: {err}"),
+            InvalidDone(err) => write!(f, "Invalid done expression: {err}"),
+            WrongActionCount { envs, actions } => write!(
+                f,
+                "Batch has {envs} environments but {actions} actions were provided"
+            ),
+        }
+    }
+}
+
+impl Error for GymError {}
+
+impl From<ECoreError> for GymError {
+    fn from(err: ECoreError) -> Self {
+        Core(err)
+    }
+}
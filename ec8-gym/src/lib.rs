@@ -0,0 +1,198 @@
+//! EmmaChip8 Gym
+//!
+//! Reinforcement learning style environment for EmmaChip8 programs
+//!
+//! Rewards and the end of an episode are calculated from expressions over the
+//! machine state, see [ec8_core::expression]
+
+use crate::error::GymError::{InvalidDone, InvalidReward};
+use crate::error::GymResult;
+use ec8_common::PIXEL_COUNT;
+use ec8_core::expression::Expression;
+use ec8_core::input::Key;
+use ec8_core::State::{Running, WaitingForKey};
+use ec8_core::{EmmaChip8, INSTRUCTIONS_PER_FRAME};
+use std::sync::Arc;
+
+pub mod batch;
+pub mod error;
+
+/// Contents of the display, row by row
+pub type Observation = [bool; PIXEL_COUNT];
+
+/// Saved machine state, see [Environment::snapshot]
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    machine: EmmaChip8,
+}
+
+#[derive(Debug, Clone)]
+pub struct Environment {
+    program: Arc<[u8]>,
+    machine: EmmaChip8,
+    reward: Expression,
+    done: Expression,
+    seed: u64,
+    frames_per_step: usize,
+    instructions_per_frame: usize,
+}
+
+impl Environment {
+    /// Create an environment for `program`
+    ///
+    /// `reward` and `done` are expressions evaluated after every step
+    pub fn new(program: &[u8], reward: &str, done: &str) -> GymResult<Self> {
+        let reward = reward.parse::<Expression>().map_err(InvalidReward)?;
+        let done = done.parse::<Expression>().map_err(InvalidDone)?;
+        let mut machine = EmmaChip8::new();
+        machine.load_program(program)?;
+        let mut env = Self {
+            program: Arc::from(program),
+            machine,
+            reward,
+            done,
+            seed: 0,
+            frames_per_step: 1,
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+        };
+        env.reset();
+        Ok(env)
+    }
+
+    /// Seed used for random numbers on the next [Environment::reset]
+    ///
+    /// For reproducible runs call this before `reset`, the seed isn't applied until then and
+    /// machines otherwise start with a random seed (`EmmaChip8::new` uses `fastrand::u64(..)`)
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Number of frames to run for each step (frame skip), minimum 1
+    pub fn set_frames_per_step(&mut self, frames: usize) {
+        self.frames_per_step = frames.max(1);
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions: usize) {
+        self.instructions_per_frame = instructions;
+    }
+
+    /// Reload the program and return the initial observation
+    pub fn reset(&mut self) -> Observation {
+        self.machine.set_seed(self.seed);
+        self.machine
+            .load_program(&self.program)
+            .expect("Program was valid when environment was created, please raise an issue");
+        self.machine.output
+    }
+
+    /// Hold `action` keys down (releasing all others) and run for one step
+    ///
+    /// Returns the new observation, the reward and if the episode has finished
+    pub fn step(&mut self, action: &[Key]) -> (Observation, f32, bool) {
+        let previous = self.machine.clone();
+        for i in 0..self.machine.keys.len() {
+            if let Some(key) = Key::from_index(i) {
+                self.machine.on_key_released(key);
+            }
+        }
+        for key in action {
+            self.machine.on_key_pressed(*key);
+        }
+        for _ in 0..self.frames_per_step {
+            self.machine.run_frame(self.instructions_per_frame);
+        }
+        let reward = self.reward.eval(&self.machine, &previous) as f32;
+        let crashed = !matches!(self.machine.state, Running | WaitingForKey(_));
+        let done = crashed || self.done.is_true(&self.machine, &previous);
+        (self.machine.output, reward, done)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            machine: self.machine.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.machine = snapshot.machine.clone();
+    }
+
+    pub fn machine(&self) -> &EmmaChip8 {
+        &self.machine
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::GymError;
+    use crate::Environment;
+    use ec8_core::input::Key;
+
+    //V0 += 1, skip if key in V1 (K0) not pressed, V2 += 1, jump to start
+    const COUNTER: [u8; 8] = [0x70, 0x01, 0xE1, 0xA1, 0x72, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn check_step() {
+        let mut env = Environment::new(&COUNTER, "v2 - prev(v2)", "v0 >= 5").unwrap();
+        env.set_instructions_per_frame(4);
+        let (_, reward, done) = env.step(&[]);
+        assert_eq!(reward, 0.0);
+        assert!(!done);
+        let (_, reward, done) = env.step(&[Key::K0]);
+        assert_eq!(reward, 1.0);
+        assert!(!done);
+        let (_, _, done) = env.step(&[Key::K0]);
+        assert!(!done);
+        let (_, _, done) = env.step(&[]);
+        assert!(done);
+
+        env.reset();
+        assert_eq!(env.machine().registers[0], 0);
+    }
+
+    #[test]
+    fn check_snapshot() {
+        let mut env = Environment::new(&COUNTER, "v0", "0").unwrap();
+        env.step(&[Key::K0]);
+        let snapshot = env.snapshot();
+        let (_, first, _) = env.step(&[]);
+        env.restore(&snapshot);
+        let (_, second, _) = env.step(&[]);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn check_deterministic() {
+        //V0 = rand, draw V0 at V0,V0
+        let program = [0xC0, 0xFF, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x00];
+        let mut env = Environment::new(&program, "v0", "0").unwrap();
+        env.set_seed(99);
+        env.reset();
+        let mut cloned = env.clone();
+        for _ in 0..10 {
+            assert_eq!(env.step(&[]), cloned.step(&[]));
+        }
+    }
+
+    #[test]
+    fn check_reset() {
+        //call 0x200
+        let mut env = Environment::new(&[0x22, 0x00], "0", "0").unwrap();
+        env.step(&[]);
+        assert!(!env.machine().stack.is_empty());
+        env.reset();
+        assert!(env.machine().stack.is_empty());
+    }
+
+    #[test]
+    fn check_invalid_expressions() {
+        assert!(matches!(
+            Environment::new(&COUNTER, "v0 +", "0"),
+            Err(GymError::InvalidReward(_))
+        ));
+        assert!(matches!(
+            Environment::new(&COUNTER, "v0", "x"),
+            Err(GymError::InvalidDone(_))
+        ));
+    }
+}
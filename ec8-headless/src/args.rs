@@ -0,0 +1,98 @@
+use clap::ValueHint::FilePath;
+use clap::{arg, command, value_parser, ArgMatches};
+use clap_common::arg_matcher::ArgMatchesFiles;
+use clap_common::{arg_input_file, arg_log_level};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use env_logger::Builder;
+use log::LevelFilter;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub input_file: PathBuf,
+    pub triggers_file: Option<PathBuf>,
+    pub cheats_file: Option<PathBuf>,
+    pub search: bool,
+    pub frames: usize,
+    pub seed: Option<u64>,
+    pub assert: bool,
+}
+
+pub fn arg_matches() -> ArgMatches {
+    command!()
+        .arg(arg_input_file!("EC8 file (*.c8)"))
+        .arg(
+            arg!(-t --triggers <FILE> "Triggers file")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(FilePath),
+        )
+        .arg(
+            arg!(-c --cheats <FILE> "Cheats file")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(FilePath),
+        )
+        .arg(
+            arg!(--search "Interactive memory search for making cheats").conflicts_with("triggers"),
+        )
+        .arg(
+            arg!(-f --frames <COUNT> "Number of frames to run")
+                .value_parser(value_parser!(usize))
+                .default_value("3600"),
+        )
+        .arg(arg!(-s --seed <SEED> "Random number seed").value_parser(value_parser!(u64)))
+        .arg(arg!(-a --assert "Fail if any trigger is not unlocked").requires("triggers"))
+        .arg(arg_log_level!())
+        .get_matches()
+}
+
+pub fn setup_logging(matches: &ArgMatches) {
+    Builder::from_default_env()
+        .format_module_path(false)
+        .format_level(false)
+        .format_target(false)
+        .format_timestamp(None)
+        .filter(
+            Some("ec8_headless"),
+            *matches
+                .get_one::<LevelFilter>("level")
+                .expect("Invalid level filter"),
+        )
+        .init();
+}
+
+pub fn read_options(matches: &ArgMatches) -> Result<Options> {
+    let input_file = matches
+        .get_file("INPUT_FILE", "Input file")
+        .map_err(|txt| eyre!(txt))?;
+    let triggers_file = if matches.contains_id("triggers") {
+        Some(
+            matches
+                .get_file("triggers", "Triggers file")
+                .map_err(|txt| eyre!(txt))?,
+        )
+    } else {
+        None
+    };
+    let cheats_file = if matches.contains_id("cheats") {
+        Some(
+            matches
+                .get_file("cheats", "Cheats file")
+                .map_err(|txt| eyre!(txt))?,
+        )
+    } else {
+        None
+    };
+
+    Ok(Options {
+        input_file,
+        triggers_file,
+        cheats_file,
+        search: matches.get_flag("search"),
+        frames: *matches
+            .get_one::<usize>("frames")
+            .expect("Frames has a default"),
+        seed: matches.get_one::<u64>("seed").copied(),
+        assert: matches.get_flag("assert"),
+    })
+}
@@ -0,0 +1,180 @@
+mod args;
+mod search;
+
+use crate::args::{arg_matches, read_options, setup_logging, Options};
+use crate::search::{Session, HELP};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use ec8_core::cheats::CheatSet;
+use ec8_core::triggers::{TriggerEngine, TriggerSet};
+use ec8_core::State::{Running, WaitingForKey};
+use ec8_core::{program_hash, EmmaChip8, INSTRUCTIONS_PER_FRAME};
+use log::info;
+use std::fs;
+use std::io::{stdin, BufRead};
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let matches = arg_matches();
+
+    setup_logging(&matches);
+    let options = read_options(&matches)?;
+
+    let program = fs::read(&options.input_file)?;
+    let triggers = match &options.triggers_file {
+        None => TriggerSet::default(),
+        Some(file) => fs::read_to_string(file)?
+            .parse::<TriggerSet>()
+            .map_err(|err| eyre!("Invalid triggers file: {err}"))?,
+    };
+
+    let cheats = match &options.cheats_file {
+        None => CheatSet::default(),
+        Some(file) => fs::read_to_string(file)?
+            .parse::<CheatSet>()
+            .map_err(|err| eyre!("Invalid cheats file: {err}"))?,
+    };
+    if !cheats.is_for(&program) {
+        return Err(eyre!(
+            "Cheats are for a different ROM, this ROM is {:016x}",
+            program_hash(&program)
+        ));
+    }
+
+    if options.search {
+        return search(&program, cheats, &options);
+    }
+
+    let (output, locked) = process(&program, triggers, cheats, &options)?;
+    for line in output {
+        println!("{line}");
+    }
+
+    if options.assert && !locked.is_empty() {
+        return Err(eyre!("Triggers not unlocked: {}", locked.join(", ")));
+    }
+
+    Ok(())
+}
+
+fn search(program: &[u8], cheats: CheatSet, options: &Options) -> Result<()> {
+    let mut machine = EmmaChip8::new();
+    if let Some(seed) = options.seed {
+        machine.set_seed(seed);
+    }
+    machine.load_program(program)?;
+    let mut session = Session::new(machine, cheats);
+    println!("{HELP}");
+    for line in stdin().lock().lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        }
+        match session.command(&line) {
+            Ok(text) if text.is_empty() => {}
+            Ok(text) => println!("{text}"),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+    Ok(())
+}
+
+/// Run the program for the number of frames in `options`
+///
+/// Returns a line for each unlocked trigger (and if the program crashed)
+/// and the names of the triggers that weren't unlocked
+fn process(
+    program: &[u8],
+    triggers: TriggerSet,
+    mut cheats: CheatSet,
+    options: &Options,
+) -> Result<(Vec<String>, Vec<String>)> {
+    if !triggers.is_for(program) {
+        return Err(eyre!(
+            "Triggers are for a different ROM, this ROM is {:016x}",
+            program_hash(program)
+        ));
+    }
+
+    let mut machine = EmmaChip8::new();
+    if let Some(seed) = options.seed {
+        machine.set_seed(seed);
+    }
+    machine.load_program(program)?;
+    let mut engine = TriggerEngine::new(triggers);
+
+    let mut output = vec![];
+    for frame in 0..options.frames {
+        cheats.apply(&mut machine);
+        machine.run_frame(INSTRUCTIONS_PER_FRAME);
+        for trigger in engine.update(&machine) {
+            output.push(format!("Frame {frame}: {}", trigger.name));
+        }
+        if !matches!(machine.state, Running | WaitingForKey(_)) {
+            output.push(format!("Frame {frame}: stopped, {:?}", machine.state));
+            break;
+        }
+    }
+    info!("Finished at {:03X}", machine.pc);
+
+    let locked = engine
+        .locked()
+        .iter()
+        .map(|trigger| trigger.name.clone())
+        .collect();
+
+    Ok((output, locked))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::args::Options;
+    use crate::process;
+    use ec8_core::cheats::CheatSet;
+    use ec8_core::triggers::TriggerSet;
+
+    fn make_options(frames: usize) -> Options {
+        Options {
+            input_file: Default::default(),
+            triggers_file: None,
+            cheats_file: None,
+            search: false,
+            frames,
+            seed: None,
+            assert: true,
+        }
+    }
+
+    #[test]
+    fn check_process() {
+        //V0 += 1, skip if V0 == 40, jump to start, return (crash)
+        let program = [0x70, 0x01, 0x30, 0x28, 0x12, 0x00, 0x00, 0xEE];
+        let triggers = "Ten: v0 >= 10\nHeld: v0 >= 20 for 2\nNever: v1 == 1"
+            .parse::<TriggerSet>()
+            .unwrap();
+        let (output, locked) =
+            process(&program, triggers, CheatSet::default(), &make_options(100)).unwrap();
+        assert_eq!(
+            output,
+            vec![
+                "Frame 1: Ten",
+                "Frame 4: Held",
+                "Frame 7: stopped, StackEmpty",
+            ]
+        );
+        assert_eq!(locked, vec!["Never"]);
+    }
+
+    #[test]
+    fn check_wrong_rom() {
+        let triggers = "rom 0000000000000001".parse::<TriggerSet>().unwrap();
+        assert!(process(
+            &[0x12, 0x00],
+            triggers,
+            CheatSet::default(),
+            &make_options(1)
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use ec8_core::cheats::{CheatSearch, CheatSet, Comparison};
+use ec8_core::input::Key;
+use ec8_core::{EmmaChip8, INSTRUCTIONS_PER_FRAME};
+
+const MAX_LISTED: usize = 32;
+
+pub const HELP: &str = "Commands
+  run [frames]       Run for a number of frames (default 1)
+  press <key>        Hold key (0-F)
+  release <key>      Release key (0-F)
+  eq <value>         Keep addresses with this value (hex)
+  same               Keep addresses that haven't changed
+  changed            Keep addresses that have changed
+  inc                Keep addresses that have increased
+  dec                Keep addresses that have decreased
+  list               Show candidates
+  poke <addr> <val>  Write value to memory (hex)
+  restart            Start a new search
+  quit";
+
+/// Interactive memory search, see [HELP]
+pub struct Session {
+    machine: EmmaChip8,
+    cheats: CheatSet,
+    search: CheatSearch,
+}
+
+impl Session {
+    pub fn new(machine: EmmaChip8, cheats: CheatSet) -> Self {
+        let search = CheatSearch::new(&machine);
+        Self {
+            machine,
+            cheats,
+            search,
+        }
+    }
+
+    /// Run a command and return the text to show
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["run"] => self.run(1),
+            ["run", frames] => self.run(
+                frames
+                    .parse()
+                    .map_err(|_| format!("Invalid frame count '{frames}'"))?,
+            ),
+            ["press", key] => {
+                self.machine.on_key_pressed(parse_key(key)?);
+                Ok(String::new())
+            }
+            ["release", key] => {
+                self.machine.on_key_released(parse_key(key)?);
+                Ok(String::new())
+            }
+            ["eq", value] => self.filter(Comparison::EqualTo(parse_hex(value)?)),
+            ["same"] => self.filter(Comparison::Unchanged),
+            ["changed"] => self.filter(Comparison::Changed),
+            ["inc"] => self.filter(Comparison::Increased),
+            ["dec"] => self.filter(Comparison::Decreased),
+            ["list"] => Ok(self.list()),
+            ["poke", addr, value] => {
+                let addr = u16::from_str_radix(addr, 16)
+                    .ok()
+                    .filter(|addr| (*addr as usize) < self.machine.memory.len())
+                    .ok_or_else(|| format!("Invalid address '{addr}'"))?;
+                self.machine.memory[addr as usize] = parse_hex(value)?;
+                Ok(String::new())
+            }
+            ["restart"] => {
+                self.search = CheatSearch::new(&self.machine);
+                Ok(self.count())
+            }
+            ["help"] => Ok(HELP.to_string()),
+            _ => Err(format!("Unknown command '{line}', try help")),
+        }
+    }
+
+    fn run(&mut self, frames: usize) -> Result<String, String> {
+        for _ in 0..frames {
+            self.cheats.apply(&mut self.machine);
+            self.machine.run_frame(INSTRUCTIONS_PER_FRAME);
+        }
+        Ok(format!("State: {:?}", self.machine.state))
+    }
+
+    fn filter(&mut self, comparison: Comparison) -> Result<String, String> {
+        self.search.filter(&self.machine, comparison);
+        Ok(self.count())
+    }
+
+    fn count(&self) -> String {
+        format!("{} candidates", self.search.candidates().len())
+    }
+
+    fn list(&self) -> String {
+        let candidates = self.search.candidates();
+        let mut output: Vec<String> = candidates
+            .iter()
+            .take(MAX_LISTED)
+            .map(|(addr, value)| format!("{addr:03X}={value:02X}"))
+            .collect();
+        if candidates.len() > MAX_LISTED {
+            output.push(format!("and {} more", candidates.len() - MAX_LISTED));
+        }
+        output.join("\n")
+    }
+}
+
+fn parse_key(text: &str) -> Result<Key, String> {
+    let mut chars = text.chars();
+    match (chars.next().and_then(Key::from_direct), chars.next()) {
+        (Some(key), None) => Ok(key),
+        _ => Err(format!("Invalid key '{text}'")),
+    }
+}
+
+fn parse_hex(text: &str) -> Result<u8, String> {
+    u8::from_str_radix(text, 16).map_err(|_| format!("Invalid value '{text}'"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::search::Session;
+    use ec8_core::cheats::CheatSet;
+    use ec8_core::EmmaChip8;
+
+    #[test]
+    fn check_session() {
+        //I = 0x300, V0 = 3, store V0, jump to self
+        let mut machine = EmmaChip8::new();
+        machine
+            .load_program(&[0xA3, 0x00, 0x60, 0x03, 0xF0, 0x55, 0x12, 0x06])
+            .unwrap();
+        let mut session = Session::new(machine, CheatSet::default());
+        session.command("run").unwrap();
+        session.command("eq 3").unwrap();
+        session.command("poke 300 2").unwrap();
+        session.command("press 1").unwrap();
+        session.command("run").unwrap();
+        session.command("dec").unwrap();
+        session.command("run 2").unwrap();
+        assert_eq!(session.command("same").unwrap(), "1 candidates");
+        assert!(session.command("list").unwrap().starts_with("300="));
+        assert!(session.command("press g").is_err());
+        assert!(session.command("poke 1000 1").is_err());
+        assert!(session.command("jump").is_err());
+    }
+}
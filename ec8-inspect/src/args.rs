@@ -0,0 +1,25 @@
+use clap::{command, ArgMatches};
+use clap_common::arg_input_file;
+use clap_common::arg_matcher::ArgMatchesFiles;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub input_file: PathBuf,
+}
+
+pub fn arg_matches() -> ArgMatches {
+    command!()
+        .arg(arg_input_file!("Chip-8 or EC8 file (*.c8, *.ch8)"))
+        .get_matches()
+}
+
+pub fn read_options(matches: &ArgMatches) -> Result<Options> {
+    let input_file = matches
+        .get_file("INPUT_FILE", "Input file")
+        .map_err(|txt| eyre!(txt))?;
+
+    Ok(Options { input_file })
+}
@@ -0,0 +1,18 @@
+mod args;
+mod report;
+
+use crate::args::{arg_matches, read_options};
+use crate::report::Report;
+use color_eyre::Result;
+use std::fs;
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let options = read_options(&arg_matches())?;
+
+    let program = fs::read(&options.input_file)?;
+    println!("{}", Report::new(&program));
+
+    Ok(())
+}
@@ -0,0 +1,384 @@
+//! Static analysis of a program
+//!
+//! Only code reachable from the start is checked (see [disassemble]),
+//! code that is only reached through `jpo` will be missed
+
+use crate::report::Extension::*;
+use ec8_common::disassembler::{disassemble, Disassembly};
+use ec8_common::instruction::decode_strict;
+use ec8_common::instruction::Instruction;
+use ec8_common::instruction::Instruction::*;
+use ec8_common::{OpCodes, MAX_PROG_SIZE};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+
+/// Chip-8 extensions with opcodes EC8 doesn't support
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Extension {
+    Schip,
+    XoChip,
+}
+
+/// Behaviour that differs between Chip-8 interpreters
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Quirk {
+    /// 8xy6 and 8xyE with x != y, originally Vy was shifted into Vx
+    Shift,
+    /// Fx55 and Fx65 followed by code that reads I, originally I was incremented
+    LoadStore,
+    /// Bnnn, SCHIP uses Vx (the highest nibble of nnn) instead of V0
+    Jump,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Profile {
+    Chip8,
+    Schip,
+    XoChip,
+}
+
+impl Quirk {
+    fn name(&self) -> &'static str {
+        match self {
+            Quirk::Shift => "Shift",
+            Quirk::LoadStore => "Load/store",
+            Quirk::Jump => "Jump with offset",
+        }
+    }
+
+    fn behaviour(&self, original: bool) -> &'static str {
+        match (self, original) {
+            (Quirk::Shift, true) => "Vy shifted into Vx",
+            (Quirk::Shift, false) => "Vx shifted",
+            (Quirk::LoadStore, true) => "I incremented",
+            (Quirk::LoadStore, false) => "I unchanged",
+            (Quirk::Jump, true) => "V0 used",
+            (Quirk::Jump, false) => "Vx used",
+        }
+    }
+
+    /// Returns true if EC8 has the original behaviour
+    fn is_original_on_ec8(&self) -> bool {
+        matches!(self, Quirk::Jump)
+    }
+}
+
+impl Profile {
+    /// Returns true if this profile has the original behaviour for every quirk
+    fn is_original(&self) -> bool {
+        !matches!(self, Profile::Schip)
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Profile::Chip8 => write!(f, "Chip-8"),
+            Profile::Schip => write!(f, "SCHIP"),
+            Profile::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+impl Display for Extension {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schip => write!(f, "SCHIP"),
+            XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Report {
+    pub size: usize,
+    /// Number of bytes reachable as code
+    pub code_size: usize,
+    /// Number of times each opcode is used, most used first
+    pub histogram: Vec<(OpCodes, usize)>,
+    /// Addresses of each EC8 only opcode
+    pub ec8_only: Vec<(OpCodes, Vec<u16>)>,
+    pub sys_calls: Vec<u16>,
+    /// Address, extension and description of each SCHIP or XO-CHIP opcode
+    pub extensions: Vec<(u16, Extension, &'static str)>,
+    /// Addresses of code that depends on each quirk
+    pub quirks: BTreeMap<Quirk, Vec<u16>>,
+    /// Addresses of `jpo` instructions, see [Disassembly::unknown_jumps]
+    pub unknown_jumps: Vec<u16>,
+    /// Addresses reached that aren't an instruction on any supported platform
+    pub invalid: Vec<u16>,
+}
+
+impl Report {
+    pub fn new(program: &[u8]) -> Report {
+        let disassembly = disassemble(program);
+        let mut histogram: HashMap<OpCodes, usize> = HashMap::new();
+        let mut ec8_only: HashMap<OpCodes, Vec<u16>> = HashMap::new();
+        let mut sys_calls = vec![];
+        let mut extensions = vec![];
+        let mut quirks: BTreeMap<Quirk, Vec<u16>> = BTreeMap::new();
+        let mut invalid = vec![];
+
+        for addr in &disassembly.code {
+            let instruction = instruction_at(&disassembly, *addr);
+            if let Some((extension, description)) = extension(instruction.encode()) {
+                extensions.push((*addr, extension, description));
+                continue;
+            }
+            let opcode = instruction
+                .opcode()
+                .expect("Disassembler only returns valid instructions as code");
+            *histogram.entry(opcode).or_default() += 1;
+            if opcode.is_ec8_only() {
+                ec8_only.entry(opcode).or_default().push(*addr);
+            }
+            let quirk = match instruction {
+                SysCall { .. } => {
+                    sys_calls.push(*addr);
+                    None
+                }
+                ShiftRight { x, y } | ShiftLeft { x, y } if x != y => Some(Quirk::Shift),
+                StoreRegs { .. } | LoadRegs { .. } if reads_mem_reg_after(&disassembly, *addr) => {
+                    Some(Quirk::LoadStore)
+                }
+                JumpOffset { .. } => Some(Quirk::Jump),
+                _ => None,
+            };
+            if let Some(quirk) = quirk {
+                quirks.entry(quirk).or_default().push(*addr);
+            }
+        }
+
+        for addr in &disassembly.invalid {
+            match extension(instruction_at(&disassembly, *addr).encode()) {
+                Some((extension, description)) => extensions.push((*addr, extension, description)),
+                None => invalid.push(*addr),
+            }
+        }
+        extensions.sort_by_key(|(addr, _, _)| *addr);
+
+        let mut histogram: Vec<(OpCodes, usize)> = histogram.into_iter().collect();
+        histogram.sort_by_key(|(opcode, count)| (usize::MAX - count, format!("{opcode:?}")));
+        let mut ec8_only: Vec<(OpCodes, Vec<u16>)> = ec8_only.into_iter().collect();
+        ec8_only.sort_by_key(|(_, addresses)| addresses[0]);
+
+        Report {
+            size: program.len(),
+            code_size: disassembly.code.len() * 2,
+            histogram,
+            ec8_only,
+            sys_calls,
+            extensions,
+            quirks,
+            unknown_jumps: disassembly.unknown_jumps,
+            invalid,
+        }
+    }
+
+    /// Platform the program was most likely written for
+    pub fn profile(&self) -> Profile {
+        let uses = |target: Extension| {
+            self.extensions
+                .iter()
+                .any(|(_, extension, _)| *extension == target)
+        };
+        if uses(XoChip) {
+            Profile::XoChip
+        } else if uses(Schip) {
+            Profile::Schip
+        } else {
+            Profile::Chip8
+        }
+    }
+}
+
+fn instruction_at(disassembly: &Disassembly, addr: u16) -> Instruction {
+    decode_strict(&disassembly.bytes[(addr - disassembly.start) as usize..])
+}
+
+/// Returns true if I is read after `addr` before it's set or the code jumps away
+fn reads_mem_reg_after(disassembly: &Disassembly, addr: u16) -> bool {
+    let mut next = addr + 2;
+    while disassembly.code.contains(&next) {
+        match instruction_at(disassembly, next) {
+            DrawSprite { .. }
+            | AddMemReg { .. }
+            | StoreBcd { .. }
+            | StoreRegs { .. }
+            | LoadRegs { .. } => return true,
+            SetMemReg { .. }
+            | SetMemRegToDigitSprite { .. }
+            | SetMemRegToAsciiSprite { .. }
+            | Jump { .. }
+            | JumpOffset { .. }
+            | Call { .. }
+            | Return => return false,
+            _ => next += 2,
+        }
+    }
+    false
+}
+
+/// SCHIP and XO-CHIP opcodes, Fx30 is not included as it's ASC on EC8
+fn extension(word: u16) -> Option<(Extension, &'static str)> {
+    match word {
+        0x00C1..=0x00CF => Some((Schip, "Scroll down")),
+        0x00D1..=0x00DF => Some((XoChip, "Scroll up")),
+        0x00FB => Some((Schip, "Scroll right")),
+        0x00FC => Some((Schip, "Scroll left")),
+        0x00FD => Some((Schip, "Exit")),
+        0x00FE => Some((Schip, "Low resolution")),
+        0x00FF => Some((Schip, "High resolution")),
+        0xF000 => Some((XoChip, "Long I")),
+        0xF002 => Some((XoChip, "Load audio pattern")),
+        _ => match (word & 0xF00F, word & 0xF0FF) {
+            (0xD000, _) => Some((Schip, "16x16 sprite")),
+            (0x5002, _) => Some((XoChip, "Store Vx to Vy")),
+            (0x5003, _) => Some((XoChip, "Load Vx to Vy")),
+            (_, 0xF001) => Some((XoChip, "Select planes")),
+            (_, 0xF03A) => Some((XoChip, "Set pitch")),
+            (_, 0xF075) => Some((Schip, "Store flags")),
+            (_, 0xF085) => Some((Schip, "Load flags")),
+            _ => None,
+        },
+    }
+}
+
+fn join(addresses: &[u16]) -> String {
+    addresses
+        .iter()
+        .map(|addr| format!("{addr:03X}"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Size: {} bytes (code {}, data {})",
+            self.size,
+            self.code_size,
+            self.size - self.code_size
+        )?;
+        if self.size > MAX_PROG_SIZE {
+            write!(f, "\nToo big for EC8, max is {MAX_PROG_SIZE} bytes")?;
+        }
+        write!(f, "\nOpcodes:")?;
+        for (opcode, count) in &self.histogram {
+            write!(f, "\n  {opcode:?}: {count}")?;
+        }
+        if !self.ec8_only.is_empty() {
+            write!(f, "\nEC8 only:")?;
+            for (opcode, addresses) in &self.ec8_only {
+                write!(f, "\n  {opcode:?}: {}", join(addresses))?;
+            }
+        }
+        if !self.sys_calls.is_empty() {
+            write!(f, "\nSystem calls: {}", join(&self.sys_calls))?;
+        }
+        if !self.extensions.is_empty() {
+            write!(f, "\nSCHIP/XO-CHIP:")?;
+            for (addr, extension, description) in &self.extensions {
+                write!(f, "\n  {addr:03X}: {description} ({extension})")?;
+            }
+        }
+        if !self.invalid.is_empty() {
+            write!(f, "\nInvalid instructions: {}", join(&self.invalid))?;
+        }
+        if !self.unknown_jumps.is_empty() {
+            write!(
+                f,
+                "\nJumps with unknown targets: {} (code only reached from these isn't checked)",
+                join(&self.unknown_jumps)
+            )?;
+        }
+        let profile = self.profile();
+        write!(f, "\nSuggested quirk profile: {profile}")?;
+        if self.quirks.is_empty() {
+            write!(f, "\n  No quirks used")?;
+        }
+        for (quirk, addresses) in &self.quirks {
+            let behaviour = quirk.behaviour(profile.is_original());
+            let ec8 = quirk.behaviour(quirk.is_original_on_ec8());
+            write!(f, "\n  {}: {behaviour}", quirk.name())?;
+            if behaviour != ec8 {
+                write!(f, " (EC8: {ec8})")?;
+            }
+            write!(f, " at {}", join(addresses))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::report::Extension::*;
+    use crate::report::{Profile, Quirk, Report};
+    use ec8_common::OpCodes;
+
+    #[test]
+    fn check_report() {
+        //0x200 high res
+        //0x202 V1 = V2 >> 1
+        //0x204 I = ascii V0
+        //0x206 store V0-V2
+        //0x208 draw V0, V1
+        //0x20A sys call
+        //0x20C skip if V0 == 0
+        //0x20E jump to V0 + 300
+        //0x210 store flags
+        let program = [
+            0x00, 0xFF, 0x81, 0x26, 0xF0, 0x30, 0xF2, 0x55, 0xD0, 0x15, 0x01, 0x23, 0x30, 0x00,
+            0xB3, 0x00, 0xF1, 0x75,
+        ];
+        let report = Report::new(&program);
+        assert_eq!(report.size, 18);
+        assert_eq!(report.code_size, 16);
+        assert_eq!(report.histogram.len(), 7);
+        assert_eq!(
+            report.ec8_only,
+            vec![(OpCodes::SetMemRegToAsciiSprite, vec![0x204])]
+        );
+        assert_eq!(report.sys_calls, vec![0x20A]);
+        assert_eq!(
+            report.extensions,
+            vec![
+                (0x200, Schip, "High resolution"),
+                (0x210, Schip, "Store flags")
+            ]
+        );
+        assert_eq!(report.quirks[&Quirk::Shift], vec![0x202]);
+        assert_eq!(report.quirks[&Quirk::LoadStore], vec![0x206]);
+        assert_eq!(report.quirks[&Quirk::Jump], vec![0x20E]);
+        assert_eq!(report.unknown_jumps, vec![0x20E]);
+        assert!(report.invalid.is_empty());
+        assert_eq!(report.profile(), Profile::Schip);
+    }
+
+    #[test]
+    fn check_output() {
+        //V0 = 1, V0 = V0 << 1, store V0, I = 300, store V0, jump to self
+        let program = [
+            0x60, 0x01, 0x80, 0x0E, 0xF0, 0x55, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x0A,
+        ];
+        assert_eq!(
+            Report::new(&program).to_string(),
+            "Size: 12 bytes (code 12, data 0)
+Opcodes:
+  StoreRegs: 2
+  Jump: 1
+  SetMemReg: 1
+  SetRegFromNum: 1
+  ShiftLeft: 1
+Suggested quirk profile: Chip-8
+  No quirks used"
+        );
+
+        //store V0, draw V0, V0, invalid
+        let report = Report::new(&[0xF0, 0x55, 0xD0, 0x05, 0x51, 0x24]);
+        assert!(report
+            .to_string()
+            .ends_with("Invalid instructions: 204\nSuggested quirk profile: Chip-8\n  Load/store: I incremented (EC8: I unchanged) at 200"));
+    }
+}
@@ -1,4 +1,5 @@
-use clap::{command, ArgMatches};
+use clap::ValueHint::FilePath;
+use clap::{arg, command, value_parser, ArgMatches};
 use clap_common::arg_matcher::{create_output_default, ArgMatchesFiles};
 use clap_common::{arg_check_level, arg_input_file, arg_log_level, arg_output_file, CheckLevel};
 use color_eyre::eyre::eyre;
@@ -11,6 +12,8 @@ use std::path::PathBuf;
 pub struct Options {
     pub input_file: PathBuf,
     pub output_file: PathBuf,
+    pub symbols_file: Option<PathBuf>,
+    pub debug_file: Option<PathBuf>,
     pub ec8_level: CheckLevel,
     pub lint_level: CheckLevel,
 }
@@ -19,6 +22,16 @@ pub fn arg_matches() -> ArgMatches {
     command!()
         .arg(arg_input_file!("EC8 code file (*.ecc)"))
         .arg(arg_output_file!())
+        .arg(
+            arg!(-s --symbols [FILE] "Generate symbols file")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(FilePath),
+        )
+        .arg(
+            arg!(-g --debug [FILE] "Generate debug info file (JSON)")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(FilePath),
+        )
         .arg(arg_log_level!())
         .arg(arg_check_level!(ec8, e, "EC8 check level", "warn"))
         .arg(arg_check_level!(
@@ -54,6 +67,24 @@ pub fn read_options(matches: &ArgMatches) -> Result<Options> {
         .get_output_file("output", "Output file", default_output)
         .map_err(|txt| eyre!(txt))?;
 
+    let mut symbols_file = None;
+    if matches.contains_id("symbols") {
+        let default_output = create_output_default(&input_file, "sym", "Symbols file");
+        let file = matches
+            .get_output_file("symbols", "Symbols file", default_output)
+            .map_err(|txt| eyre!(txt))?;
+        symbols_file = Some(file);
+    }
+
+    let mut debug_file = None;
+    if matches.contains_id("debug") {
+        let default_output = create_output_default(&input_file, "debug.json", "Debug info file");
+        let file = matches
+            .get_output_file("debug", "Debug info file", default_output)
+            .map_err(|txt| eyre!(txt))?;
+        debug_file = Some(file);
+    }
+
     let ec8_level = *matches
         .get_one::<CheckLevel>("ec8")
         .expect("Invalid EC8 arg");
@@ -64,6 +95,8 @@ pub fn read_options(matches: &ArgMatches) -> Result<Options> {
     Ok(Options {
         input_file,
         output_file: output,
+        symbols_file,
+        debug_file,
         ec8_level,
         lint_level,
     })
@@ -1,10 +1,67 @@
 use crate::ast::Program;
 use std::collections::HashMap;
 use ec8_common::PROG_START_ADDRESS;
+use ec8_common::debug_info::{DebugInfo, LineInfo, RegionKind};
+use ec8_common::symbols::{SymbolKind, Symbols};
 use crate::ast::data::Data;
 use crate::parser::line::tokens::Param;
 
 impl Program {
+    /// Addresses of every label and data block
+    pub fn symbols(&self) -> Symbols {
+        let mut symbols = Symbols::default();
+        let data_start = (self.asm_lines.len() * 2) as u16 + PROG_START_ADDRESS;
+        for (i, line) in self.asm_lines.iter().enumerate() {
+            for lbl in &line.labels {
+                symbols.insert((i * 2) as u16 + PROG_START_ADDRESS, lbl, SymbolKind::Code);
+            }
+        }
+        for data in &self.datas {
+            symbols.insert_sized(
+                data.addr + data_start,
+                &data.name,
+                SymbolKind::Data,
+                data.bytes.len() as u16,
+            );
+        }
+        symbols
+    }
+
+    /// Line of `source` (read from `file`) for every instruction and data block, along with
+    /// every label and data block
+    pub fn debug_info(&self, file: &str, source: &[&str]) -> DebugInfo {
+        let mut info = DebugInfo::new(vec![file.to_string()]);
+        let data_start = (self.asm_lines.len() * 2) as u16 + PROG_START_ADDRESS;
+        let column = |line: usize| source.get(line).map(|text| code_column(text)).unwrap_or(0);
+        for (i, line) in self.asm_lines.iter().enumerate() {
+            let addr = (i * 2) as u16 + PROG_START_ADDRESS;
+            info.add_line(LineInfo {
+                addr,
+                len: 2,
+                kind: RegionKind::Code,
+                file: 0,
+                line: line.line + 1,
+                column: column(line.line) + 1,
+            });
+            for lbl in &line.labels {
+                info.add_label(lbl, addr);
+            }
+        }
+        for data in &self.datas {
+            let addr = data.addr + data_start;
+            info.add_line(LineInfo {
+                addr,
+                len: data.bytes.len() as u16,
+                kind: RegionKind::Data,
+                file: 0,
+                line: data.line + 1,
+                column: column(data.line) + 1,
+            });
+            info.add_data(&data.name, addr, data.bytes.len() as u16);
+        }
+        info
+    }
+
     pub fn set_addresses(&mut self) {
         let mut labels = HashMap::new();
         let data_start = (self.asm_lines.len() * 2) as u16;
@@ -29,7 +86,7 @@ impl Program {
                         addrs.insert(i, Param::Addr(dat.addr + data_start + PROG_START_ADDRESS));
                     }
                     Param::Unknown(txt) => {
-                        let addr = labels.get(txt).map(|num| *num).unwrap_or_else(|| datas.get(txt).map(|dat| dat.addr + data_start ).unwrap_or_else(|| panic!("{txt} not found as data or label, please raise an issue")));
+                        let addr = labels.get(txt).copied().unwrap_or_else(|| datas.get(txt).map(|dat| dat.addr + data_start ).unwrap_or_else(|| panic!("{txt} not found as data or label, please raise an issue")));
                         addrs.insert(i, Param::Addr(addr + PROG_START_ADDRESS));
                     }
                     _ => {}
@@ -42,3 +99,13 @@ impl Program {
         }
     }
 }
+
+/// Column of the first character after the label (if any) in `text`
+fn code_column(text: &str) -> usize {
+    let start = text
+        .split_once(':')
+        .filter(|(lbl, _)| lbl.trim().chars().all(|chr| chr.is_alphanumeric() || chr == '_'))
+        .map(|(lbl, _)| lbl.len() + 1)
+        .unwrap_or(0);
+    start + text[start..].len() - text[start..].trim_start().len()
+}
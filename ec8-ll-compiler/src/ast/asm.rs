@@ -1,7 +1,8 @@
+use crate::ast::data::Data;
 use crate::ast::{AsmLine, Program};
 use crate::parser::line::tokens::Param;
-use ec8_common::OpCodes;
-use crate::ast::data::Data;
+use ec8_common::definitions::{OperandKind, Operands};
+use ec8_common::instruction::Instruction;
 
 impl Program {
     pub fn to_asm(&self) -> Vec<String> {
@@ -18,75 +19,73 @@ impl Program {
 
 impl Data {
     pub fn to_asm(&self) -> String {
-        format!("dat [{}]", self.bytes.iter().map(|byt| format!("{:02X}", byt)).collect::<Vec<String>>().join(""))
+        format!(
+            "dat [{}]",
+            self.bytes
+                .iter()
+                .map(|byt| format!("{:02X}", byt))
+                .collect::<Vec<String>>()
+                .join("")
+        )
     }
 }
 
 impl AsmLine {
-    pub fn to_asm(&self) -> String {
-        match self.opcode {
-            OpCodes::SysCall | OpCodes::Return | OpCodes::ClearDisplay => {
-                self.opcode.mnemonic().to_string()
+    /// Must be called after addresses are set
+    pub fn instruction(&self) -> Instruction {
+        let def = self.opcode.definition();
+        let reg = |i: usize| self.params[i].reg();
+        let operands = match def.operands {
+            OperandKind::Empty => Operands::default(),
+            OperandKind::Address => Operands::address(self.params[0].addr()),
+            OperandKind::Reg => Operands::reg(reg(0)),
+            OperandKind::RegByte => Operands::reg_byte(reg(0), self.params[1].num()),
+            //shifts only have one register
+            OperandKind::RegReg => {
+                Operands::reg_reg(reg(0), self.params.get(1).map(Param::reg).unwrap_or(reg(0)))
             }
-            OpCodes::SkipIfNotEqualReg
-            | OpCodes::SkipIfEqualReg
-            | OpCodes::SkipIfNotEqualNum
-            | OpCodes::SkipIfEqualNum
-            | OpCodes::SetRegRand
-            | OpCodes::SetRegFromNum
-            | OpCodes::AddNumToReg
-            | OpCodes::SetRegFromReg
-            | OpCodes::AddReg
-            | OpCodes::SubLeftReg
-            | OpCodes::SubRightReg
-            | OpCodes::BitwiseAnd
-            | OpCodes::BitwiseXor
-            | OpCodes::BitwiseOr => format!(
-                "{} {}, {}",
-                self.opcode.mnemonic(),
-                self.params[0].to_asm(),
-                self.params[1].to_asm()
-            ),
-            OpCodes::DrawSprite => format!(
-                "{} {}, {}, {}",
-                self.opcode.mnemonic(),
-                self.params[0].to_asm(),
-                self.params[1].to_asm(),
-                self.params[2].to_asm()
-            ),
-            OpCodes::SkipIfKeyPressed
-            | OpCodes::SkipIfKeyNotPressed
-            | OpCodes::SetRegFromTimer
-            | OpCodes::SetDelayTimer
-            | OpCodes::SetSoundTimer
-            | OpCodes::AddMemReg
-            | OpCodes::JumpOffset
-            | OpCodes::SetMemReg
-            | OpCodes::Jump
-            | OpCodes::Call
-            | OpCodes::SetMemRegToDigitSprite
-            | OpCodes::SetMemRegToAsciiSprite
-            | OpCodes::ShiftRight
-            | OpCodes::ShiftLeft
-            | OpCodes::WaitForKey
-            | OpCodes::StoreBcd
-            | OpCodes::StoreRegs
-            | OpCodes::LoadRegs => {
-                format!("{} {}", self.opcode.mnemonic(), self.params[0].to_asm())
+            OperandKind::RegRegNibble => {
+                Operands::reg_reg_nibble(reg(0), reg(1), self.params[2].num())
             }
-        }
+        };
+        def.instruction(operands)
+    }
+
+    pub fn to_asm(&self) -> String {
+        self.instruction()
+            .to_asm()
+            .unwrap_or_else(|| panic!("Unable to convert {:?} to asm, please raise an issue", self))
     }
 }
 
 impl Param {
-    pub fn to_asm(&self) -> String {
+    fn reg(&self) -> u8 {
         match self {
-            Param::Reg(n) => format!("v{n}"),
-            Param::Addr(nnn) => format!("{:03X}", nnn),
-            Param::Num(n) => format!("{:02X}", n),
-            Param::MemReg => "I".to_string(),
-            Param::Data(txt) => txt.to_string(),
-            _ => panic!("Encountered {:?} when converting to asm", self)
+            Param::Reg(n) => *n,
+            _ => panic!(
+                "Expected register but found {:?} when converting to asm",
+                self
+            ),
+        }
+    }
+
+    fn num(&self) -> u8 {
+        match self {
+            Param::Num(n) => *n,
+            _ => panic!(
+                "Expected number but found {:?} when converting to asm",
+                self
+            ),
+        }
+    }
+
+    fn addr(&self) -> u16 {
+        match self {
+            Param::Addr(nnn) => *nnn,
+            _ => panic!(
+                "Expected address but found {:?} when converting to asm",
+                self
+            ),
         }
     }
 }
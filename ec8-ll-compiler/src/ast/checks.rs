@@ -2,7 +2,7 @@ use clap_common::CheckLevel;
 use crate::ast::Program;
 
 impl Program {
-    pub fn warnings(&self, ec8_level: CheckLevel, lint_level: CheckLevel) -> Option<String> {
+    pub fn warnings(&self, _ec8_level: CheckLevel, _lint_level: CheckLevel) -> Option<String> {
         None
     }
 }
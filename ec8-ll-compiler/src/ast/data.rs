@@ -5,12 +5,14 @@ use ec8_common::MAX_PROG_SIZE;
 pub struct Data {
     pub name: String,
     pub bytes: Vec<u8>,
-    pub addr: u16
+    pub addr: u16,
+    /// Line the data was defined on
+    pub line: usize,
 }
 
 impl Data {
-    pub fn new(name: String, addr: u16, bytes: Vec<u8>) -> Self {
-        Self { name, addr, bytes }
+    pub fn new(name: String, addr: u16, bytes: Vec<u8>, line: usize) -> Self {
+        Self { name, addr, bytes, line }
     }
 }
 
@@ -28,12 +30,12 @@ pub fn extract_data(lines: &[Line]) -> Result<Vec<Data>, String> {
     let mut addr = 0;
     for line in lines {
         if let Line::Data {
-            line: _,
+            line,
             name,
             data,
         } = line
         {
-            datas.push(Data::new(name.clone(), addr, data.clone()));
+            datas.push(Data::new(name.clone(), addr, data.clone(), *line));
             addr += data.len() as u16;
         }
     }
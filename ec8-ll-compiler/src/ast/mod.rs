@@ -186,7 +186,7 @@ pub fn build_opcodes(lines: &[Line]) -> Result<Program, String> {
 
     if !labels.is_empty() {
         return if labels[0].starts_with("__loop") {
-            Err(format!("again is not allowed as the last instruction"))
+            Err("again is not allowed as the last instruction".to_string())
         } else {
             Err(format!("{} unused labels at end of program", labels.len()))
         }
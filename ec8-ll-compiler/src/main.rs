@@ -26,12 +26,27 @@ fn main() -> Result<()> {
 }
 
 fn process(source: Vec<&str>, options: &Options) -> Result<Vec<String>> {
-    let program = parse(source).map_err(|str| eyre!("{str}"))?;
+    let program = parse(source.clone()).map_err(|str| eyre!("{str}"))?;
 
     if let Some(text) = program.warnings(options.ec8_level, options.lint_level) {
         eprintln!("Warning:\n{text}");
     }
 
+    if let Some(symbols_file) = &options.symbols_file {
+        let result = fs::write(symbols_file, program.symbols().to_string());
+        if let Err(err) = result {
+            eprintln!("Error writing symbols file: {err}");
+        }
+    }
+
+    if let Some(debug_file) = &options.debug_file {
+        let file = options.input_file.display().to_string();
+        let result = fs::write(debug_file, program.debug_info(&file, &source).to_json());
+        if let Err(err) = result {
+            eprintln!("Error writing debug info file: {err}");
+        }
+    }
+
     Ok(program.to_asm())
 }
 
@@ -39,12 +54,15 @@ fn process(source: Vec<&str>, options: &Options) -> Result<Vec<String>> {
 mod test {
     use clap_common::CheckLevel;
     use crate::args::Options;
+    use crate::parser::parse;
     use crate::process;
 
     fn make_options() -> Options {
         Options {
             input_file: Default::default(),
             output_file: Default::default(),
+            symbols_file: None,
+            debug_file: None,
             ec8_level: CheckLevel::Off,
             lint_level: CheckLevel::Off,
         }
@@ -54,14 +72,14 @@ mod test {
     fn check_progress_basic() {
         let input = vec!["lbl: v3 = xff", "data test 01a2", "goto lbl", "i = test"];
         let output = process(input, &make_options()).unwrap();
-        assert_eq!(output, vec!["set v3, FF", "jmp 200", "sti 206", "dat [01A2]"].iter().map(|s| s.to_string()).collect::<Vec<String>>());
+        assert_eq!(output, ["set v3, FF", "jmp 200", "sti 206", "dat [01A2]"].iter().map(|s| s.to_string()).collect::<Vec<String>>());
     }
 
     #[test]
     fn check_if() {
         let input = vec!["loop","if eq(v3,3) break", "again", "i = @0"];
         let output = process(input, &make_options()).unwrap();
-        assert_eq!(output, vec!["ske v3, 03","jmp 206","jmp 200","sti 000"])
+        assert_eq!(output, vec!["skn v3, 03","jmp 206","jmp 200","sti 000"])
     }
 
     #[test]
@@ -70,4 +88,33 @@ mod test {
         let output = process(input, &make_options()).unwrap();
         assert_eq!(output, vec!["jmp 200"])
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_symbols() {
+        let input = vec!["data text \"hi\"", "loop", "i = text", "again", "end: goto(end)"];
+        let program = parse(input).unwrap();
+        assert_eq!(
+            program.symbols().to_string(),
+            "200 code __loop_1_start\n204 code end\n206 data text\n"
+        );
+    }
+
+    #[test]
+    fn check_debug_info() {
+        let input = vec!["data text \"hi\"", "loop", "  i = text", "again", "end: goto(end)"];
+        let info = parse(input.clone()).unwrap().debug_info("test.ecc", &input);
+        assert_eq!(info.location(0x200), Some(String::from("test.ecc:3:3")));
+        assert_eq!(info.location(0x202), Some(String::from("test.ecc:4:1")));
+        assert_eq!(info.location(0x204), Some(String::from("test.ecc:5:6")));
+        assert_eq!(info.location(0x207), Some(String::from("test.ecc:1:1")));
+        assert_eq!(info.regions.len(), 2);
+        assert_eq!(info.symbols(), parse(input).unwrap().symbols());
+    }
+
+    #[test]
+    fn check_registers() {
+        let input = vec!["va = 1", "shr(va)", "vb += va"];
+        let output = process(input, &make_options()).unwrap();
+        assert_eq!(output, vec!["set vA, 01", "shr vA, vA", "add vB, vA"])
+    }
+}
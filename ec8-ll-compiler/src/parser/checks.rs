@@ -29,15 +29,11 @@ pub fn verify_labels(lines: &[Line]) -> Result<Option<String>, String> {
                     }
                 }
                 match token {
-                    Token::Set(target, value) => {
-                        if target == &MemReg {
-                            match value {
-                                Param::Label(lbl) => used_labels.push(lbl),
-                                Param::Data(dat) => used_datas.push(dat),
-                                _ => {}
-                            }
-                        }
-                    }
+                    Token::Set(target, value) if target == &MemReg => match value {
+                        Param::Label(lbl) => used_labels.push(lbl),
+                        Param::Data(dat) => used_datas.push(dat),
+                        _ => {}
+                    },
                     Token::GotoOffset(dest, _) | Token::Call(dest) | Token::Goto(dest) => {
                         match dest {
                             Param::Label(lbl) => used_labels.push(lbl),
@@ -79,14 +79,14 @@ pub fn parse_data_value(str: &str) -> Result<Vec<u8>, String> {
     let str = str.trim();
     if str.starts_with('"') && str.ends_with('"') {
         let text = str.trim_matches('"');
-        return if !text
+        if !text
             .chars()
             .all(|c| c.is_ascii_alphanumeric() || SYMBOLS.contains(&c))
         {
             Err("Unsupported characters in string".to_string())
         } else {
             Ok(text.chars().map(|c| c as u8).collect())
-        };
+        }
     } else if str.contains(',') {
         let mut bytes = vec![];
         for num_str in str.split(',') {
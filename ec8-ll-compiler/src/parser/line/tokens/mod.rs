@@ -389,7 +389,7 @@ mod test {
 
     #[test]
     fn check_macro_def() {
-        let lines = vec![
+        let lines = [
             "macro(read_data, l, r)",
             "i = $1",
             "i += $2",
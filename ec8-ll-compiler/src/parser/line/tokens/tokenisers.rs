@@ -140,7 +140,7 @@ pub fn tokenise_one_two_param(parts: &[String], defs: &[Definition]) -> Result<T
             ),
         }
     } else {
-        return Err(format!("{} requires one or two params", parts[0]));
+        Err(format!("{} requires one or two params", parts[0]))
     }
 }
 
@@ -150,7 +150,7 @@ pub fn tokenise_math(parts: &[String], defs: &[Definition]) -> Result<Option<Tok
     }
     let lhs = parse_param(&parts[0], defs)?;
     let rhs = parse_param(&parts[2], defs)?;
-    return match parts[1].as_str() {
+    match parts[1].as_str() {
         "=" => {
             if parts.len() == 5 && parts[3] == "-" {
                 if parts[0] != parts[4] {
@@ -195,7 +195,7 @@ pub fn tokenise_math(parts: &[String], defs: &[Definition]) -> Result<Option<Tok
             Ok(Some(Xor(lhs, rhs)))
         }
         _ => Ok(None),
-    };
+    }
 }
 
 pub fn tokenise_macro_call(parts: &[String], defs: &[Definition]) -> Result<Token, String> {
@@ -7,8 +7,8 @@ pub fn cleanup(parts: &[&str]) -> Vec<String> {
         .iter()
         .map(|part| {
             part.trim()
-                .trim_start_matches(|c| matches!(c, '(' | ','))
-                .trim_end_matches(|c| matches!(c, ')' | ','))
+                .trim_start_matches(['(', ','])
+                .trim_end_matches([')', ','])
         })
         .filter(|part| !part.is_empty())
         .map(|part| part.to_string())
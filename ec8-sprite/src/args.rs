@@ -0,0 +1,142 @@
+use clap::{arg, command, value_parser, ArgMatches, Command};
+use clap_common::arg_matcher::{create_output_default, ArgMatchesFiles};
+use clap_common::{arg_input_file, arg_output_file};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use ec8_common::sprite::SpriteSize;
+use std::path::PathBuf;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Asm,
+    Ll,
+}
+
+#[derive(Debug, Clone)]
+pub enum Options {
+    Import {
+        input_file: PathBuf,
+        output_file: PathBuf,
+        format: Format,
+        name: String,
+        size: SpriteSize,
+    },
+    Export {
+        input_file: PathBuf,
+        output_file: PathBuf,
+        address: u16,
+        rows: usize,
+        size: SpriteSize,
+    },
+}
+
+pub fn arg_matches() -> ArgMatches {
+    command!()
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("import")
+                .about("Convert an image to sprite data")
+                .arg(arg_input_file!("Image file (*.png, *.pbm, *.pgm)"))
+                .arg(arg_output_file!())
+                .arg(
+                    arg!(-f --format <FORMAT> "Output format, asm for ec8-assembler or ll for ec8-ll-compiler")
+                        .value_parser(["asm", "ll"])
+                        .default_value("asm"),
+                )
+                .arg(arg!(-n --name <NAME> "Data name (defaults to input file name)"))
+                .arg(arg!(--schip "Make 16x16 sprites")),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Convert sprite bytes from a program to an image")
+                .arg(arg_input_file!("EC8 file (*.c8)"))
+                .arg(arg_output_file!())
+                .arg(
+                    arg!(-a --address <ADDRESS> "Address of the sprite in hex")
+                        .default_value("200"),
+                )
+                .arg(
+                    arg!(-r --rows <ROWS> "Number of rows (defaults to 15, or 16 for SCHIP)")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(arg!(--schip "Sprite is 16x16")),
+        )
+        .get_matches()
+}
+
+pub fn read_options(matches: &ArgMatches) -> Result<Options> {
+    let (command, matches) = matches.subcommand().expect("Subcommand is required");
+    let input_file = matches
+        .get_file("INPUT_FILE", "Input file")
+        .map_err(|txt| eyre!(txt))?;
+    let size = if matches.get_flag("schip") {
+        SpriteSize::Schip
+    } else {
+        SpriteSize::Chip8
+    };
+
+    if command == "import" {
+        let format = match matches.get_one::<String>("format").map(String::as_str) {
+            Some("ll") => Format::Ll,
+            _ => Format::Asm,
+        };
+        let ext = match format {
+            Format::Asm => "eca",
+            Format::Ll => "ecc",
+        };
+        let output_file = matches
+            .get_output_file(
+                "output",
+                "Output file",
+                create_output_default(&input_file, ext, "Input file"),
+            )
+            .map_err(|txt| eyre!(txt))?;
+        let name = match matches.get_one::<String>("name") {
+            Some(name) => name.clone(),
+            None => input_file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .ok_or_else(|| eyre!("Input file has no name, use --name"))?,
+        };
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .map(|chr| chr.is_ascii_alphabetic() || chr == '_')
+            .unwrap_or(false)
+            && chars.all(|chr| chr.is_ascii_alphanumeric() || chr == '_');
+        if !valid {
+            return Err(eyre!("'{name}' can't be used as a label, use --name"));
+        }
+        Ok(Options::Import {
+            input_file,
+            output_file,
+            format,
+            name,
+            size,
+        })
+    } else {
+        let output_file = matches
+            .get_output_file(
+                "output",
+                "Output file",
+                create_output_default(&input_file, "png", "Input file"),
+            )
+            .map_err(|txt| eyre!(txt))?;
+        let address = matches
+            .get_one::<String>("address")
+            .expect("Address has a default");
+        let address = u16::from_str_radix(address.trim_start_matches("0x"), 16)
+            .map_err(|_| eyre!("Invalid address '{address}'"))?;
+        let rows = matches
+            .get_one::<usize>("rows")
+            .copied()
+            .unwrap_or(size.max_rows());
+        Ok(Options::Export {
+            input_file,
+            output_file,
+            address,
+            rows,
+            size,
+        })
+    }
+}
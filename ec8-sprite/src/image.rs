@@ -0,0 +1,280 @@
+//! Image files
+//!
+//! PBM set pixels (1, black) are sprite pixels, for PGM and PNG bright pixels are sprite pixels
+//! (and transparent pixels never are)
+
+use ec8_common::sprite::Bitmap;
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use std::path::Path;
+
+/// Pixels this bright (out of 255) or brighter are set
+const THRESHOLD: u16 = 128;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageFormat {
+    Pbm,
+    Pgm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<ImageFormat, String> {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "pbm" => Ok(ImageFormat::Pbm),
+            "pgm" => Ok(ImageFormat::Pgm),
+            "png" => Ok(ImageFormat::Png),
+            _ => Err(format!(
+                "Unsupported image {}, must be png, pbm or pgm",
+                path.display()
+            )),
+        }
+    }
+}
+
+pub fn read_image(bytes: &[u8], format: ImageFormat) -> Result<Bitmap, String> {
+    match format {
+        ImageFormat::Pbm | ImageFormat::Pgm => read_pnm(bytes),
+        ImageFormat::Png => read_png(bytes),
+    }
+}
+
+pub fn write_image(bitmap: &Bitmap, format: ImageFormat) -> Result<Vec<u8>, String> {
+    match format {
+        ImageFormat::Pbm => Ok(write_pbm(bitmap)),
+        ImageFormat::Pgm => Ok(write_pgm(bitmap)),
+        ImageFormat::Png => write_png(bitmap),
+    }
+}
+
+/// Reads P1, P2, P4 and P5 (plain and binary PBM and PGM)
+fn read_pnm(bytes: &[u8]) -> Result<Bitmap, String> {
+    let mut pos = 0;
+    let magic = next_token(bytes, &mut pos)?;
+    let (plain, pbm) = match magic.as_str() {
+        "P1" => (true, true),
+        "P2" => (true, false),
+        "P4" => (false, true),
+        "P5" => (false, false),
+        _ => return Err(format!("Unsupported image type '{magic}'")),
+    };
+    let width = next_number(bytes, &mut pos)? as usize;
+    let height = next_number(bytes, &mut pos)? as usize;
+    let max = if pbm {
+        1
+    } else {
+        next_number(bytes, &mut pos)?
+    };
+    if max == 0 {
+        return Err("Invalid max value 0".to_string());
+    }
+    let is_set = |value: u16| {
+        if pbm {
+            value == 1
+        } else {
+            (value as u32 * 255) / max as u32 >= THRESHOLD as u32
+        }
+    };
+
+    let mut bitmap = Bitmap::new(width, height);
+    if plain {
+        for y in 0..height {
+            for x in 0..width {
+                let value = if pbm {
+                    //plain PBM pixels don't need whitespace between them
+                    skip_whitespace(bytes, &mut pos);
+                    let value = match bytes.get(pos) {
+                        Some(b'0') => 0,
+                        Some(b'1') => 1,
+                        _ => return Err(format!("Invalid pixel at {x},{y}")),
+                    };
+                    pos += 1;
+                    value
+                } else {
+                    next_number(bytes, &mut pos)?
+                };
+                bitmap.set(x, y, is_set(value));
+            }
+        }
+    } else {
+        //exactly one whitespace character between the header and pixels
+        let raster = bytes.get(pos + 1..).unwrap_or_default();
+        let row_bytes = if pbm {
+            width.div_ceil(8)
+        } else if max > 255 {
+            width * 2
+        } else {
+            width
+        };
+        if raster.len() < row_bytes * height {
+            return Err("Image is missing pixels".to_string());
+        }
+        for y in 0..height {
+            let row = &raster[y * row_bytes..];
+            for x in 0..width {
+                let value = if pbm {
+                    ((row[x / 8] >> (7 - x % 8)) & 1) as u16
+                } else if max > 255 {
+                    u16::from_be_bytes([row[x * 2], row[x * 2 + 1]])
+                } else {
+                    row[x] as u16
+                };
+                bitmap.set(x, y, is_set(value));
+            }
+        }
+    }
+    Ok(bitmap)
+}
+
+fn skip_whitespace(bytes: &[u8], pos: &mut usize) {
+    while let Some(byte) = bytes.get(*pos) {
+        if *byte == b'#' {
+            while bytes.get(*pos).map(|byte| *byte != b'\n').unwrap_or(false) {
+                *pos += 1;
+            }
+        } else if !byte.is_ascii_whitespace() {
+            return;
+        }
+        *pos += 1;
+    }
+}
+
+fn next_token(bytes: &[u8], pos: &mut usize) -> Result<String, String> {
+    skip_whitespace(bytes, pos);
+    let start = *pos;
+    while bytes
+        .get(*pos)
+        .map(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(false)
+    {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err("Image is incomplete".to_string());
+    }
+    Ok(String::from_utf8_lossy(&bytes[start..*pos]).to_string())
+}
+
+fn next_number(bytes: &[u8], pos: &mut usize) -> Result<u16, String> {
+    let token = next_token(bytes, pos)?;
+    token
+        .parse()
+        .map_err(|_| format!("Invalid number '{token}' in image"))
+}
+
+/// Plain PBM (P1)
+fn write_pbm(bitmap: &Bitmap) -> Vec<u8> {
+    let mut output = format!("P1\n{} {}\n", bitmap.width, bitmap.height);
+    for row in bitmap.pixels.chunks(bitmap.width.max(1)) {
+        let row: Vec<&str> = row.iter().map(|on| if *on { "1" } else { "0" }).collect();
+        output.push_str(&row.join(" "));
+        output.push('\n');
+    }
+    output.into_bytes()
+}
+
+/// Binary PGM (P5)
+fn write_pgm(bitmap: &Bitmap) -> Vec<u8> {
+    let mut output = format!("P5\n{} {}\n255\n", bitmap.width, bitmap.height).into_bytes();
+    output.extend(bitmap.pixels.iter().map(|on| if *on { 255 } else { 0 }));
+    output
+}
+
+fn read_png(bytes: &[u8]) -> Result<Bitmap, String> {
+    let mut decoder = Decoder::new(bytes);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|err| err.to_string())?;
+    let (color, _) = reader.output_color_type();
+    let samples = color.samples();
+
+    let mut bitmap = Bitmap::new(frame.width as usize, frame.height as usize);
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let start = y * frame.line_size + x * samples;
+            let pixel = &buffer[start..start + samples];
+            let (brightness, alpha) = match color {
+                ColorType::Grayscale => (pixel[0] as u16, 255),
+                ColorType::GrayscaleAlpha => (pixel[0] as u16, pixel[1] as u16),
+                ColorType::Rgb => (average(pixel), 255),
+                ColorType::Rgba => (average(&pixel[..3]), pixel[3] as u16),
+                ColorType::Indexed => return Err("Unable to expand PNG palette".to_string()),
+            };
+            bitmap.set(x, y, brightness >= THRESHOLD && alpha >= THRESHOLD);
+        }
+    }
+    Ok(bitmap)
+}
+
+fn average(values: &[u8]) -> u16 {
+    values.iter().map(|value| *value as u16).sum::<u16>() / values.len() as u16
+}
+
+/// Greyscale PNG, set pixels are white
+fn write_png(bitmap: &Bitmap) -> Result<Vec<u8>, String> {
+    let mut output = vec![];
+    let mut encoder = Encoder::new(&mut output, bitmap.width as u32, bitmap.height as u32);
+    encoder.set_color(ColorType::Grayscale);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    let pixels: Vec<u8> = bitmap
+        .pixels
+        .iter()
+        .map(|on| if *on { 255 } else { 0 })
+        .collect();
+    writer
+        .write_image_data(&pixels)
+        .map_err(|err| err.to_string())?;
+    writer.finish().map_err(|err| err.to_string())?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::image::{read_image, write_image, ImageFormat};
+    use ec8_common::sprite::Bitmap;
+
+    fn make_bitmap() -> Bitmap {
+        let mut bitmap = Bitmap::new(10, 2);
+        bitmap.set(0, 0, true);
+        bitmap.set(9, 1, true);
+        bitmap
+    }
+
+    #[test]
+    fn check_round_trip() {
+        let bitmap = make_bitmap();
+        for format in [ImageFormat::Pbm, ImageFormat::Pgm, ImageFormat::Png] {
+            let bytes = write_image(&bitmap, format).unwrap();
+            assert_eq!(read_image(&bytes, format).unwrap(), bitmap, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn check_pnm() {
+        let bitmap = make_bitmap();
+        let plain = "P1\n# comment\n10 2\n1000000000\n0 0 0 0 0 0 0 0 0 1";
+        assert_eq!(
+            read_image(plain.as_bytes(), ImageFormat::Pbm).unwrap(),
+            bitmap
+        );
+
+        let binary = [b"P4 10 2\n".as_slice(), &[0x80, 0x00, 0x00, 0x40]].concat();
+        assert_eq!(read_image(&binary, ImageFormat::Pbm).unwrap(), bitmap);
+
+        let grey = "P2 10 2 15\n15 0 0 0 0 0 0 0 0 0\n0 0 0 0 0 0 0 0 0 8";
+        assert_eq!(
+            read_image(grey.as_bytes(), ImageFormat::Pgm).unwrap(),
+            bitmap
+        );
+
+        assert!(read_image(b"P3 1 1 1 0 0 0", ImageFormat::Pgm).is_err());
+        assert!(read_image(b"P4 10 2\n\x80", ImageFormat::Pbm).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+mod args;
+mod image;
+
+use crate::args::{arg_matches, read_options, Format, Options};
+use crate::image::{read_image, write_image, ImageFormat};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use ec8_common::sprite::{to_asm, to_ll, Bitmap};
+use ec8_common::PROG_START_ADDRESS;
+use std::fs;
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let options = read_options(&arg_matches())?;
+
+    match options {
+        Options::Import {
+            input_file,
+            output_file,
+            format,
+            name,
+            size,
+        } => {
+            let image_format = ImageFormat::from_path(&input_file).map_err(|err| eyre!(err))?;
+            let bitmap = read_image(&fs::read(&input_file)?, image_format)
+                .map_err(|err| eyre!("Unable to read {}: {err}", input_file.display()))?;
+            let sprites = bitmap.to_sprites(size);
+            let source = match format {
+                Format::Asm => to_asm(&name, &sprites),
+                Format::Ll => to_ll(&name, &sprites),
+            };
+            fs::write(output_file, source)?;
+        }
+        Options::Export {
+            input_file,
+            output_file,
+            address,
+            rows,
+            size,
+        } => {
+            let image_format = ImageFormat::from_path(&output_file).map_err(|err| eyre!(err))?;
+            let program = fs::read(&input_file)?;
+            let start = address
+                .checked_sub(PROG_START_ADDRESS)
+                .ok_or_else(|| eyre!("Address must be at least {PROG_START_ADDRESS:03X}"))?
+                as usize;
+            let end = start + rows * size.width() / 8;
+            let bytes = program
+                .get(start..end)
+                .ok_or_else(|| eyre!("Sprite is past the end of the program"))?;
+            let bitmap = Bitmap::from_sprite(bytes, size);
+            fs::write(
+                output_file,
+                write_image(&bitmap, image_format).map_err(|err| eyre!(err))?,
+            )?;
+        }
+    }
+
+    Ok(())
+}
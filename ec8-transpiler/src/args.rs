@@ -0,0 +1,66 @@
+use clap::{command, ArgMatches, Command};
+use clap_common::arg_matcher::{create_output_default, ArgMatchesFiles};
+use clap_common::{arg_input_file, arg_output_file};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub input_file: PathBuf,
+    pub output_file: PathBuf,
+}
+
+pub fn arg_matches() -> ArgMatches {
+    args().get_matches()
+}
+
+fn args() -> Command {
+    command!()
+        .arg(arg_input_file!("EC8 file (*.c8)"))
+        .arg(arg_output_file!())
+}
+
+pub fn read_options(matches: &ArgMatches) -> Result<Options> {
+    let input_file = matches
+        .get_file("INPUT_FILE", "Input file")
+        .map_err(|txt| eyre!(txt))?;
+    let output_file = matches
+        .get_output_file(
+            "output",
+            "Output file",
+            create_output_default(&input_file, ".c8", "Input file"),
+        )
+        .map_err(|txt| eyre!(txt))?;
+
+    Ok(Options {
+        input_file,
+        output_file,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::args::{args, read_options};
+    use std::fs;
+
+    #[test]
+    fn check_default_output() {
+        let dir = std::env::temp_dir().join("ec8-transpiler-args");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("game.c8");
+        fs::write(&input, [0x00, 0xE0]).unwrap();
+
+        let matches = args()
+            .try_get_matches_from(["ec8-transpiler", input.to_str().unwrap()])
+            .unwrap();
+        let options = read_options(&matches).unwrap();
+        assert_eq!(options.output_file, dir.join("game..c8"));
+
+        let matches = args()
+            .try_get_matches_from(["ec8-transpiler", input.to_str().unwrap(), "-o", "out.ch8"])
+            .unwrap();
+        let options = read_options(&matches).unwrap();
+        assert_eq!(options.output_file.to_str(), Some("out.ch8"));
+    }
+}
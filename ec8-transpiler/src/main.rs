@@ -0,0 +1,110 @@
+mod args;
+
+use crate::args::{arg_matches, read_options};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use ec8_common::transpiler::{transpile, Transpiled};
+use std::fs;
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let options = read_options(&arg_matches())?;
+
+    let program = fs::read(&options.input_file)?;
+    let transpiled =
+        transpile(&program).map_err(|err| eyre!("Unable to convert program: {err}"))?;
+
+    let (message, warning) = summary(&transpiled);
+    println!("{message}");
+    if let Some(warning) = warning {
+        eprintln!("{warning}");
+    }
+
+    fs::write(&options.output_file, transpiled.bytes)?;
+
+    Ok(())
+}
+
+/// What was replaced, and a warning if any code may not have been converted
+fn summary(transpiled: &Transpiled) -> (String, Option<String>) {
+    let message = if transpiled.replaced.is_empty() {
+        String::from("No ASC instructions, program is unchanged")
+    } else {
+        format!(
+            "Replaced {} ASC instructions, program is now {} bytes",
+            transpiled.replaced.len(),
+            transpiled.bytes.len()
+        )
+    };
+    let warning = if transpiled.unknown_jumps.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "Contains jumps with an offset at {}, code reached from these may not have been converted",
+            transpiled
+                .unknown_jumps
+                .iter()
+                .map(|addr| format!("{addr:03X}"))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))
+    };
+    (message, warning)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::summary;
+    use ec8_common::transpiler::transpile;
+
+    #[test]
+    fn check_unchanged() {
+        //Set V0 = 1, jump to self
+        let program = [0x60, 0x01, 0x12, 0x02];
+        let transpiled = transpile(&program).unwrap();
+        assert_eq!(transpiled.bytes, program.to_vec());
+        assert_eq!(
+            summary(&transpiled),
+            (
+                String::from("No ASC instructions, program is unchanged"),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn check_replaced() {
+        //ASC V3, ASC V3, jump to self
+        let transpiled = transpile(&[0xF3, 0x30, 0xF3, 0x30, 0x12, 0x04]).unwrap();
+        assert_eq!(transpiled.replaced, vec![0x200, 0x202]);
+        let (message, warning) = summary(&transpiled);
+        assert_eq!(
+            message,
+            format!(
+                "Replaced 2 ASC instructions, program is now {} bytes",
+                transpiled.bytes.len()
+            )
+        );
+        assert_eq!(warning, None);
+    }
+
+    #[test]
+    fn check_unknown_jumps() {
+        //ASC V0, jump to V0 + 0x200
+        let transpiled = transpile(&[0xF0, 0x30, 0xB2, 0x00]).unwrap();
+        assert_eq!(
+            summary(&transpiled).1,
+            Some(String::from(
+                "Contains jumps with an offset at 202, code reached from these may not have been converted"
+            ))
+        );
+    }
+
+    #[test]
+    fn check_too_large() {
+        let mut program = vec![0xF0, 0x30];
+        program.resize(0xD00, 0);
+        assert!(transpile(&program).is_err());
+    }
+}
@@ -9,8 +9,16 @@ cd ec8-client
 cd ..
 cargo build --release -q --manifest-path ec8-assembler/Cargo.toml
 cargo build --release -q --manifest-path ec8-ll-compiler/Cargo.toml
+cargo build --release -q --manifest-path ec8-headless/Cargo.toml
+cargo build --release -q --manifest-path ec8-inspect/Cargo.toml
+cargo build --release -q --manifest-path ec8-sprite/Cargo.toml
+cargo build --release -q --manifest-path ec8-transpiler/Cargo.toml
 
 mv ec8-client/target/release/ec8 build/bin
 mv ec8-client/target/release/ec8-logging build/bin
 mv ec8-assembler/target/release/ec8-assembler build/bin
-mv ec8-ll-compiler/target/release/ec8-ll-compiler build/bin
\ No newline at end of file
+mv ec8-ll-compiler/target/release/ec8-ll-compiler build/bin
+mv ec8-headless/target/release/ec8-headless build/bin
+mv ec8-inspect/target/release/ec8-inspect build/bin
+mv ec8-sprite/target/release/ec8-sprite build/bin
+mv ec8-transpiler/target/release/ec8-transpiler build/bin
\ No newline at end of file
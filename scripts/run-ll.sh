@@ -16,9 +16,10 @@ if test -f "build/bin/ec8-ll-compiler"; then
 	asm_output="build/output/$filename.eca"
 	bin_output="build/output/$filename.c8"
 	desc="build/output/$filename.desc"
+	sym="build/output/$filename.sym"
 
-	./build/bin/ec8-ll-compiler -o "$asm_output" "$input"
-	./build/bin/ec8-assembler -o "$bin_output" -d "$desc" "$asm_output"
+	./build/bin/ec8-ll-compiler -o "$asm_output" -s "$sym" "$input"
+	./build/bin/ec8-assembler -o "$bin_output" -d "$desc" -s "$sym" "$asm_output"
 
 
 	if [ "$1" == "log" ]; then